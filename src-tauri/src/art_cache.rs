@@ -0,0 +1,172 @@
+use std::{collections::HashMap, fs::{self, create_dir_all}, path::PathBuf, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+
+use crate::logger;
+
+/// Bump this whenever the shape of the art index changes, so old indexes get rebuilt.
+const ART_INDEX_VERSION: u32 = 1;
+
+/// Maps each album to the cached image it uses. Images are named by the hash of their contents.
+#[derive(Default, Serialize, Deserialize)]
+struct ArtIndex {
+  version: u32,
+  albums: HashMap<String, String>,
+  #[serde(skip)]
+  is_dirty: bool,
+}
+
+/// The album art cache. Identical images are only stored once, no matter how many albums or files they came from.
+pub struct ArtCache {
+  index: Mutex<Option<ArtIndex>>,
+}
+
+/// Gets the folder cached album art is stored in.
+fn get_albums_dir(app_handle: &AppHandle) -> PathBuf {
+  let bundle_id: String = app_handle.config().identifier.to_owned();
+
+  let app_cache_dir = app_handle.path().cache_dir().expect("Couldn't resolve app cache dir.");
+  let albums_dir = app_cache_dir.join(&bundle_id).join("albums");
+
+  if !albums_dir.exists() {
+    let _ = create_dir_all(&albums_dir);
+  }
+
+  return albums_dir;
+}
+
+/// Gets the path of the on disk art index.
+fn get_index_path(app_handle: &AppHandle) -> PathBuf {
+  return get_albums_dir(app_handle).join("index.json");
+}
+
+/// Hashes the provided bytes into a hex string.
+pub fn hash_to_hex(data: &[u8]) -> String {
+  return Sha256::digest(data).iter().map(| byte | format!("{:02x}", byte)).collect();
+}
+
+/// Gets the key an album is stored under. Albums with the same title by different artists get different keys.
+fn get_album_key(album_artist: Option<&str>, album_title: &str) -> String {
+  let identity = format!("{}\u{0}{}", album_artist.unwrap_or("").trim().to_lowercase(), album_title.trim().to_lowercase());
+  return hash_to_hex(identity.as_bytes());
+}
+
+/// Keeps only the characters of a file extension that are safe in a file name.
+fn sanitize_file_type(file_type: &str) -> String {
+  let sanitized: String = file_type.chars().filter(| character | character.is_ascii_alphanumeric()).collect::<String>().to_ascii_lowercase();
+  return if sanitized.is_empty() { String::from("img") } else { sanitized };
+}
+
+/// Loads the art index from disk, or starts a new one if it is missing or outdated.
+fn load_index(app_handle: &AppHandle) -> ArtIndex {
+  let index_path = get_index_path(app_handle);
+
+  if !index_path.exists() {
+    return ArtIndex { version: ART_INDEX_VERSION, ..Default::default() };
+  }
+
+  let index_res = fs::read_to_string(&index_path).map_err(| err | err.to_string()).and_then(| contents | {
+    return serde_json::from_str::<ArtIndex>(&contents).map_err(| err | err.to_string());
+  });
+
+  if index_res.is_err() {
+    let err = index_res.err().unwrap();
+    logger::log(app_handle, format!("Failed to load the album art index, starting a new one: {}", err).as_str(), 1);
+    return ArtIndex { version: ART_INDEX_VERSION, ..Default::default() };
+  }
+
+  let index = index_res.unwrap();
+
+  if index.version != ART_INDEX_VERSION {
+    logger::log(app_handle, "Album art index is outdated, starting a new one.", 0);
+    return ArtIndex { version: ART_INDEX_VERSION, ..Default::default() };
+  }
+
+  return index;
+}
+
+impl ArtCache {
+  /// Creates a new ArtCache. The index gets loaded the first time it is used.
+  pub fn new() -> ArtCache {
+    return ArtCache {
+      index: Mutex::new(None),
+    };
+  }
+
+  /// Runs the provided function with the art index, loading it if needed.
+  fn with_index<T>(&self, app_handle: &AppHandle, action: impl FnOnce(&mut ArtIndex) -> T) -> T {
+    let mut index_guard = self.index.lock().unwrap();
+    let index = index_guard.get_or_insert_with(| | load_index(app_handle));
+
+    return action(index);
+  }
+
+  /// Stores an album's art in the cache and returns its path.
+  /// Images already in the cache aren't written again, but the album still gets pointed at them.
+  pub fn store(&self, app_handle: &AppHandle, data: &[u8], file_type: &str, album_artist: Option<&str>, album_title: &str) -> Option<String> {
+    let file_name = format!("{}.{}", hash_to_hex(data), sanitize_file_type(file_type));
+    let file_path = get_albums_dir(app_handle).join(&file_name);
+
+    return self.with_index(app_handle, | index | {
+      // * The index lock is held while writing, so parallel scans can't write the same image at once.
+      if !file_path.exists() {
+        let write_res = fs::write(&file_path, data);
+
+        if write_res.is_err() {
+          let err = write_res.err().unwrap();
+          logger::log(app_handle, format!("Writing of {} failed with {}.", file_name, err.to_string()).as_str(), 2);
+          return None;
+        }
+
+        logger::log(app_handle, format!("Writing of {} finished.", file_name).as_str(), 0);
+      }
+
+      let album_key = get_album_key(album_artist, album_title);
+
+      if index.albums.get(&album_key) != Some(&file_name) {
+        index.albums.insert(album_key, file_name);
+        index.is_dirty = true;
+      }
+
+      return file_path.to_str().map(| path | path.to_owned());
+    });
+  }
+
+  /// Gets the path of an album's cached art, if it has any.
+  pub fn get(&self, app_handle: &AppHandle, album_artist: Option<&str>, album_title: &str) -> Option<String> {
+    let albums_dir = get_albums_dir(app_handle);
+
+    return self.with_index(app_handle, | index | {
+      let file_name = index.albums.get(&get_album_key(album_artist, album_title))?;
+      let file_path = albums_dir.join(file_name);
+
+      if !file_path.exists() {
+        return None;
+      }
+
+      return file_path.to_str().map(| path | path.to_owned());
+    });
+  }
+
+  /// Saves the art index to disk if it has changed.
+  pub fn save(&self, app_handle: &AppHandle) {
+    self.with_index(app_handle, | index | {
+      if !index.is_dirty {
+        return;
+      }
+
+      let contents = serde_json::to_string(index).expect("Can't serialize album art index to string.");
+      let write_res = fs::write(get_index_path(app_handle), contents);
+
+      if write_res.is_err() {
+        let err = write_res.err().unwrap();
+        logger::log(app_handle, format!("Failed to save the album art index: {}", err.to_string()).as_str(), 2);
+        return;
+      }
+
+      index.is_dirty = false;
+    });
+  }
+}
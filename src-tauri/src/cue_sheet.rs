@@ -0,0 +1,627 @@
+use std::{fs::{self, read_dir}, path::{Path, PathBuf}};
+
+use serde_json::Value;
+use symphonia::core::{formats::Cue, meta::StandardTagKey};
+use tauri::AppHandle;
+
+use crate::{file_results::{FileError, FileErrorKind}, logger, song::{parse_replay_gain, ReplayGain, Song}};
+
+/// The raw tag some rippers embed the whole cue sheet text in.
+const CUESHEET_TAG_KEY: &str = "cuesheet";
+
+/// Cue sheet timestamps count CD frames, of which there are 75 per second.
+const FRAMES_PER_SECOND: u64 = 75;
+
+/// FLAC uses these track numbers for the lead-out, which marks the end of the last track.
+const LEAD_OUT_TRACK_NUMBERS: [u32; 2] = [170, 255];
+
+#[derive(Clone, Default)]
+pub struct CueTrack {
+  pub number: u16,
+  pub title: Option<String>,
+  pub performer: Option<String>,
+  pub songwriter: Option<String>,
+  pub isrc: Option<String>,
+  pub start_ms: u64,
+  pub replay_gain: ReplayGain,
+}
+
+#[derive(Clone, Default)]
+pub struct CueFile {
+  pub name: String,
+  pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Clone, Default)]
+pub struct CueSheet {
+  pub title: Option<String>,
+  pub performer: Option<String>,
+  pub songwriter: Option<String>,
+  pub genre: Option<String>,
+  pub date: Option<String>,
+  pub replay_gain: ReplayGain,
+  pub files: Vec<CueFile>,
+}
+
+/// Splits a cue sheet command's arguments, keeping quoted arguments together.
+fn split_arguments(line: &str) -> Vec<String> {
+  let mut arguments: Vec<String> = vec![];
+  let mut current = String::new();
+  let mut in_quotes = false;
+
+  for character in line.chars() {
+    if character == '"' {
+      in_quotes = !in_quotes;
+    } else if character.is_whitespace() && !in_quotes {
+      if !current.is_empty() {
+        arguments.push(current.clone());
+        current.clear();
+      }
+    } else {
+      current.push(character);
+    }
+  }
+
+  if !current.is_empty() {
+    arguments.push(current);
+  }
+
+  return arguments;
+}
+
+/// Parses a cue sheet timestamp, like "03:25:42" (minutes, seconds, frames), into milliseconds.
+fn parse_cue_timestamp(timestamp: &str) -> Option<u64> {
+  let mut parts = timestamp.split(':');
+
+  let minutes = parts.next()?.trim().parse::<u64>().ok()?;
+  let seconds = parts.next()?.trim().parse::<u64>().ok()?;
+  let frames = parts.next()?.trim().parse::<u64>().ok()?;
+
+  return Some((minutes * 60 + seconds) * 1000 + frames * 1000 / FRAMES_PER_SECOND);
+}
+
+/// Joins the arguments after a command back into its value, so unquoted titles with spaces still work.
+fn get_value(arguments: &[String], start: usize) -> Option<String> {
+  if arguments.len() <= start {
+    return None;
+  }
+
+  let value = arguments[start..].join(" ").trim().to_owned();
+
+  return if value.is_empty() { None } else { Some(value) };
+}
+
+/// Decodes the contents of a cue sheet file. Older rippers write them in Latin-1 rather than UTF-8.
+fn decode_cue_sheet(bytes: &[u8]) -> String {
+  let contents = match std::str::from_utf8(bytes) {
+    Ok(text) => text.to_owned(),
+    Err(_) => bytes.iter().map(| byte | *byte as char).collect(),
+  };
+
+  return contents.trim_start_matches('\u{feff}').to_owned();
+}
+
+/// Parses the text of a cue sheet. Tracks without an INDEX 01 or INDEX 00 are skipped.
+pub fn parse_cue_sheet(contents: &str) -> CueSheet {
+  let mut sheet = CueSheet::default();
+  let mut current_track: Option<CueTrack> = None;
+  let mut track_start: Option<u64> = None;
+  let mut pregap_start: Option<u64> = None;
+
+  // * Pushes the current track onto the last file, using its pregap if there was no INDEX 01.
+  let finish_track = | sheet: &mut CueSheet, track: Option<CueTrack>, start: Option<u64>, pregap: Option<u64> | {
+    if let (Some(mut track), Some(start_ms)) = (track, start.or(pregap)) {
+      if let Some(file) = sheet.files.last_mut() {
+        track.start_ms = start_ms;
+        file.tracks.push(track);
+      }
+    }
+  };
+
+  for raw_line in contents.lines() {
+    let arguments = split_arguments(raw_line.trim());
+
+    if arguments.is_empty() {
+      continue;
+    }
+
+    let command = arguments[0].to_ascii_uppercase();
+
+    match command.as_str() {
+      "FILE" => {
+        finish_track(&mut sheet, current_track.take(), track_start.take(), pregap_start.take());
+
+        // * The last argument is the file type, like WAVE or MP3.
+        let name_end = if arguments.len() > 2 { arguments.len() - 1 } else { arguments.len() };
+
+        sheet.files.push(CueFile {
+          name: arguments[1..name_end].join(" "),
+          tracks: vec![],
+        });
+      }
+      "TRACK" => {
+        finish_track(&mut sheet, current_track.take(), track_start.take(), pregap_start.take());
+
+        let is_audio = arguments.get(2).map_or(true, | track_type | track_type.eq_ignore_ascii_case("AUDIO"));
+        let number_res = arguments.get(1).and_then(| number | number.parse::<u16>().ok());
+
+        if is_audio && number_res.is_some() {
+          current_track = Some(CueTrack {
+            number: number_res.unwrap(),
+            ..Default::default()
+          });
+        }
+      }
+      "INDEX" => {
+        let index_number = arguments.get(1).and_then(| number | number.parse::<u32>().ok());
+        let timestamp = arguments.get(2).and_then(| timestamp | parse_cue_timestamp(timestamp));
+
+        match index_number {
+          Some(0) => pregap_start = timestamp,
+          Some(1) => track_start = timestamp,
+          _ => {}
+        }
+      }
+      "TITLE" | "PERFORMER" | "SONGWRITER" | "ISRC" => {
+        let value = get_value(&arguments, 1);
+
+        if let Some(track) = current_track.as_mut() {
+          match command.as_str() {
+            "TITLE" => track.title = value,
+            "PERFORMER" => track.performer = value,
+            "SONGWRITER" => track.songwriter = value,
+            _ => track.isrc = value,
+          }
+        } else {
+          match command.as_str() {
+            "TITLE" => sheet.title = value,
+            "PERFORMER" => sheet.performer = value,
+            "SONGWRITER" => sheet.songwriter = value,
+            _ => {}
+          }
+        }
+      }
+      "REM" => {
+        let key = arguments.get(1).map(| key | key.to_ascii_uppercase()).unwrap_or_default();
+        let value = get_value(&arguments, 2);
+
+        match (key.as_str(), current_track.as_mut()) {
+          ("GENRE", None) => sheet.genre = value,
+          ("DATE", None) => sheet.date = value,
+          ("REPLAYGAIN_ALBUM_GAIN", _) => sheet.replay_gain.albumGain = value.and_then(| value | parse_replay_gain(&value)),
+          ("REPLAYGAIN_ALBUM_PEAK", _) => sheet.replay_gain.albumPeak = value.and_then(| value | parse_replay_gain(&value)),
+          ("REPLAYGAIN_TRACK_GAIN", Some(track)) => track.replay_gain.trackGain = value.and_then(| value | parse_replay_gain(&value)),
+          ("REPLAYGAIN_TRACK_PEAK", Some(track)) => track.replay_gain.trackPeak = value.and_then(| value | parse_replay_gain(&value)),
+          _ => {}
+        }
+      }
+      _ => {}
+    }
+  }
+
+  finish_track(&mut sheet, current_track.take(), track_start.take(), pregap_start.take());
+
+  return sheet;
+}
+
+/// Gets the tracks a cue sheet has for an audio file.
+/// Rips often get converted after the cue sheet was made, so the names are also compared without extensions.
+fn get_file_tracks(sheet: &CueSheet, file_path: &Path, allow_single_file: bool) -> Vec<CueTrack> {
+  if allow_single_file && sheet.files.len() == 1 {
+    return sheet.files[0].tracks.clone();
+  }
+
+  let file_name = file_path.file_name().map(| name | name.to_string_lossy().to_string()).unwrap_or_default();
+  let file_stem = file_path.file_stem().map(| stem | stem.to_string_lossy().to_string()).unwrap_or_default();
+
+  let file_res = sheet.files.iter().find(| file | {
+    let cue_name = Path::new(&file.name).file_name().map(| name | name.to_string_lossy().to_string()).unwrap_or_default();
+    let cue_stem = Path::new(&file.name).file_stem().map(| stem | stem.to_string_lossy().to_string()).unwrap_or_default();
+
+    return cue_name.eq_ignore_ascii_case(&file_name) || cue_stem.eq_ignore_ascii_case(&file_stem);
+  });
+
+  return file_res.map(| file | file.tracks.clone()).unwrap_or_default();
+}
+
+/// Reads and parses a cue sheet file.
+fn read_cue_sheet_file(app_handle: &AppHandle, cue_path: &Path) -> Option<CueSheet> {
+  let bytes_res = fs::read(cue_path);
+
+  if bytes_res.is_err() {
+    let err = bytes_res.err().unwrap();
+    logger::log(app_handle, format!("Failed to read {}: {}", cue_path.to_string_lossy(), err.to_string()).as_str(), 1);
+    return None;
+  }
+
+  return Some(parse_cue_sheet(&decode_cue_sheet(&bytes_res.unwrap())));
+}
+
+/// Finds the external cue sheet for an audio file, and the tracks it has for it.
+/// Cue sheets named after the file are checked first, then any other cue sheet in the folder that references it.
+fn find_external_cue_sheet(app_handle: &AppHandle, file_path: &Path) -> Option<(CueSheet, Vec<CueTrack>)> {
+  let mut named_path = file_path.as_os_str().to_owned();
+  named_path.push(".cue");
+
+  let named_paths = [file_path.with_extension("cue"), PathBuf::from(named_path)];
+
+  for cue_path in named_paths.iter().filter(| cue_path | cue_path.is_file()) {
+    if let Some(sheet) = read_cue_sheet_file(app_handle, cue_path) {
+      let tracks = get_file_tracks(&sheet, file_path, true);
+
+      if !tracks.is_empty() {
+        return Some((sheet, tracks));
+      }
+    }
+  }
+
+  let entries = read_dir(file_path.parent()?).ok()?;
+
+  for entry in entries.flatten() {
+    let cue_path = entry.path();
+    let is_cue = cue_path.extension().is_some_and(| extension | extension.eq_ignore_ascii_case("cue"));
+
+    if !is_cue || named_paths.contains(&cue_path) {
+      continue;
+    }
+
+    if let Some(sheet) = read_cue_sheet_file(app_handle, &cue_path) {
+      let tracks = get_file_tracks(&sheet, file_path, false);
+
+      if !tracks.is_empty() {
+        return Some((sheet, tracks));
+      }
+    }
+  }
+
+  return None;
+}
+
+/// Converts the cues symphonia reads from a FLAC CUESHEET block into tracks.
+/// The block doesn't keep index numbers around, so any pregap stays at the start of its track.
+pub fn get_embedded_cue_tracks(cues: &[Cue], sample_rate: u32) -> Vec<CueTrack> {
+  if sample_rate == 0 {
+    return vec![];
+  }
+
+  return cues.iter().filter(| cue | !LEAD_OUT_TRACK_NUMBERS.contains(&cue.index) && cue.index <= u16::MAX as u32).map(| cue | {
+    let first_point_offset = cue.points.first().map(| point | point.start_offset_ts).unwrap_or(0);
+    let isrc = cue.tags.iter().find(| tag | tag.std_key == Some(StandardTagKey::IdentIsrc)).map(| tag | tag.value.to_string());
+
+    return CueTrack {
+      number: cue.index as u16,
+      isrc,
+      start_ms: (cue.start_ts + first_point_offset) * 1000 / sample_rate as u64,
+      ..Default::default()
+    };
+  }).collect();
+}
+
+/// Finds the cue sheet tracks for a song, preferring an external cue sheet, then one embedded as a tag, then the embedded cues.
+pub fn find_cue_tracks(app_handle: &AppHandle, song: &Song, embedded_tracks: Vec<CueTrack>) -> (Option<CueSheet>, Vec<CueTrack>) {
+  let file_path = PathBuf::from(&song.filePath);
+
+  if let Some((sheet, tracks)) = find_external_cue_sheet(app_handle, &file_path) {
+    return (Some(sheet), tracks);
+  }
+
+  if let Some(cue_sheet_tag) = song.rawTags.get(CUESHEET_TAG_KEY).and_then(| value | value.as_str()) {
+    let sheet = parse_cue_sheet(cue_sheet_tag);
+    let tracks = get_file_tracks(&sheet, &file_path, true);
+
+    if !tracks.is_empty() {
+      return (Some(sheet), tracks);
+    }
+  }
+
+  return (None, embedded_tracks);
+}
+
+/// Splits a song into a virtual track for each cue sheet track.
+/// Returns the song as is if there aren't at least two tracks to split it into.
+pub fn split_song(song: Song, sheet: Option<&CueSheet>, tracks: &[CueTrack]) -> Vec<Song> {
+  let mut sorted_tracks = tracks.to_vec();
+  sorted_tracks.sort_by_key(| track | track.start_ms);
+
+  let file_length_ms = song.duration * 1000;
+  sorted_tracks.retain(| track | track.start_ms < file_length_ms);
+
+  if sorted_tracks.len() < 2 {
+    return vec![song];
+  }
+
+  let mut album_song = song;
+  album_song.rawTags.remove(CUESHEET_TAG_KEY);
+
+  // * Values from the cue sheet only fill in what the file's own tags are missing.
+  if let Some(sheet) = sheet {
+    if album_song.album.is_none() {
+      album_song.album = sheet.title.clone();
+    }
+
+    if album_song.albumArtist.is_none() {
+      album_song.albumArtist = sheet.performer.clone();
+    }
+
+    if album_song.genres.is_empty() {
+      album_song.genres = sheet.genre.iter().cloned().collect();
+    }
+
+    if album_song.year.is_none() {
+      album_song.year = sheet.date.as_ref().and_then(| date | date.get(..4)).and_then(| year | year.parse::<i32>().ok());
+    }
+
+    if album_song.replayGain.albumGain.is_none() {
+      album_song.replayGain.albumGain = sheet.replay_gain.albumGain;
+      album_song.replayGain.albumPeak = sheet.replay_gain.albumPeak;
+    }
+  }
+
+  let num_tracks = sorted_tracks.len();
+
+  return sorted_tracks.iter().enumerate().map(| (index, track) | {
+    let mut virtual_song = album_song.clone();
+    let end_ms = sorted_tracks.get(index + 1).map(| next_track | next_track.start_ms);
+
+    virtual_song.title = Some(track.title.clone().unwrap_or(format!("Track {:02}", track.number)));
+    virtual_song.trackNumber = Some(track.number);
+    virtual_song.trackTotal = Some(num_tracks as u16);
+    virtual_song.duration = (end_ms.unwrap_or(file_length_ms) - track.start_ms) / 1000;
+    virtual_song.cueTrack = Some(track.number);
+    virtual_song.startMs = Some(track.start_ms);
+    virtual_song.endMs = end_ms;
+
+    if let Some(performer) = track.performer.clone().or(sheet.and_then(| sheet | sheet.performer.clone())) {
+      virtual_song.artists = vec![performer];
+    }
+
+    if let Some(songwriter) = track.songwriter.clone().or(sheet.and_then(| sheet | sheet.songwriter.clone())) {
+      virtual_song.composers = vec![songwriter];
+    }
+
+    if let Some(isrc) = track.isrc.clone() {
+      virtual_song.rawTags.insert(String::from("isrc"), Value::String(isrc));
+    }
+
+    // * The file's track level values describe the whole rip, so they don't apply to a single track.
+    virtual_song.musicBrainzTrackId = None;
+    virtual_song.replayGain.trackGain = track.replay_gain.trackGain;
+    virtual_song.replayGain.trackPeak = track.replay_gain.trackPeak;
+
+    return virtual_song;
+  }).collect();
+}
+
+/// Finds the file name in a FILE line, returning where it starts and ends without its quotes.
+/// Unquoted names run up to the file type, like the parser reads them.
+fn find_file_name(line: &str) -> Option<(usize, usize)> {
+  let trimmed = line.trim_start_matches('\u{feff}').trim_start();
+
+  if !trimmed.get(..4).is_some_and(| command | command.eq_ignore_ascii_case("FILE")) {
+    return None;
+  }
+
+  let after_command = &trimmed[4..];
+  let arguments = after_command.trim_start();
+
+  // * The command has to be followed by whitespace, so other commands that start with FILE don't match.
+  if arguments.len() == after_command.len() || arguments.is_empty() {
+    return None;
+  }
+
+  let start = line.len() - arguments.len();
+
+  if let Some(quoted) = arguments.strip_prefix('"') {
+    let end = quoted.find('"')?;
+    return Some((start + 1, start + 1 + end));
+  }
+
+  let arguments = arguments.trim_end();
+  let name = arguments.rfind(char::is_whitespace).map(| type_start | arguments[..type_start].trim_end()).unwrap_or(arguments);
+
+  return Some((start, start + name.len()));
+}
+
+/// Gets where the last component of a cue sheet file name starts. Sheets made on Windows use backslashes.
+fn get_component_start(name: &str) -> usize {
+  return name.rfind(| character | character == '/' || character == '\\').map(| separator | separator + 1).unwrap_or(0);
+}
+
+/// Checks if a FILE entry names an audio file. File names aren't case sensitive on every platform, so neither is this.
+fn names_file(cue_name: &str, file_name: &str) -> bool {
+  return cue_name[get_component_start(cue_name)..].to_lowercase() == file_name.to_lowercase();
+}
+
+/// Checks if a cue sheet file has a FILE entry that names an audio file.
+pub fn references_file(cue_path: &Path, file_name: &str) -> bool {
+  let bytes_res = fs::read(cue_path);
+
+  if bytes_res.is_err() {
+    return false;
+  }
+
+  let sheet = parse_cue_sheet(&decode_cue_sheet(&bytes_res.unwrap()));
+
+  return sheet.files.iter().any(| file | names_file(&file.name, file_name));
+}
+
+/// Points the FILE entries of a cue sheet that name the old audio file at its new name.
+/// Sheets that aren't UTF-8 are read as Latin-1 and written back the same way, so the rest of their text is kept byte for byte.
+pub fn retarget_cue_sheet(cue_path: &Path, old_name: &str, new_name: &str) -> Result<(), FileError> {
+  let bytes = fs::read(cue_path)?;
+  let utf8_res = String::from_utf8(bytes.clone());
+  let is_utf8 = utf8_res.is_ok();
+
+  let contents = match utf8_res {
+    Ok(text) => text,
+    Err(_) => bytes.iter().map(| byte | *byte as char).collect(),
+  };
+
+  let mut changed = false;
+
+  let retargeted: Vec<String> = contents.split('\n').map(| line | {
+    let name_range = find_file_name(line).filter(| (start, end) | names_file(&line[*start..*end], old_name));
+
+    if name_range.is_none() {
+      return line.to_owned();
+    }
+
+    let (start, end) = name_range.unwrap();
+    let component_start = start + get_component_start(&line[start..end]);
+    let is_quoted = line[..start].ends_with('"');
+
+    changed = true;
+
+    // * Unquoted names can't have spaces, so they get quoted if the new name has any.
+    if !is_quoted && new_name.contains(char::is_whitespace) {
+      return format!("{}\"{}{}\"{}", &line[..start], &line[start..component_start], new_name, &line[end..]);
+    }
+
+    return format!("{}{}{}", &line[..component_start], new_name, &line[end..]);
+  }).collect();
+
+  if !changed {
+    return Ok(());
+  }
+
+  let retargeted = retargeted.join("\n");
+
+  if is_utf8 {
+    fs::write(cue_path, retargeted)?;
+    return Ok(());
+  }
+
+  if retargeted.chars().any(| character | character as u32 > 0xff) {
+    return Err(FileError::new(FileErrorKind::Other, format!("The cue sheet isn't UTF-8, and {} can't be written in its encoding.", new_name)));
+  }
+
+  fs::write(cue_path, retargeted.chars().map(| character | character as u8).collect::<Vec<u8>>())?;
+
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ALBUM_SHEET: &str = "REM GENRE Rock
+REM DATE 1999
+REM REPLAYGAIN_ALBUM_GAIN -7.50 dB
+PERFORMER \"The Band\"
+TITLE \"The Album\"
+FILE \"The Album.flac\" WAVE
+  TRACK 01 AUDIO
+    TITLE \"First\"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE \"Second\"
+    PERFORMER \"Guest\"
+    REM REPLAYGAIN_TRACK_GAIN -6.20 dB
+    INDEX 00 03:00:00
+    INDEX 01 03:02:00
+  TRACK 03 AUDIO
+    TITLE Hidden Pregap Only
+    INDEX 00 05:00:37
+  TRACK 04 MODE1/2352
+    INDEX 01 07:00:00
+";
+
+  /// Gets the number and start of each track, since tracks can't be compared directly.
+  fn to_starts(tracks: &[CueTrack]) -> Vec<(u16, u64)> {
+    return tracks.iter().map(| track | (track.number, track.start_ms)).collect();
+  }
+
+  #[test]
+  fn converts_frames_to_milliseconds() {
+    assert_eq!(parse_cue_timestamp("00:00:00"), Some(0));
+    assert_eq!(parse_cue_timestamp("03:25:42"), Some(205_560));
+    assert_eq!(parse_cue_timestamp("00:01:74"), Some(1_986));
+    assert_eq!(parse_cue_timestamp("74:59:74"), Some(4_499_986));
+    assert_eq!(parse_cue_timestamp("03:25"), None);
+  }
+
+  #[test]
+  fn parses_album_values() {
+    let sheet = parse_cue_sheet(ALBUM_SHEET);
+
+    assert_eq!(sheet.title.as_deref(), Some("The Album"));
+    assert_eq!(sheet.performer.as_deref(), Some("The Band"));
+    assert_eq!(sheet.genre.as_deref(), Some("Rock"));
+    assert_eq!(sheet.date.as_deref(), Some("1999"));
+    assert_eq!(sheet.replay_gain.albumGain, Some(-7.5));
+  }
+
+  #[test]
+  fn starts_tracks_at_index_01_or_their_pregap() {
+    let sheet = parse_cue_sheet(ALBUM_SHEET);
+
+    assert_eq!(sheet.files.len(), 1);
+    assert_eq!(sheet.files[0].name, "The Album.flac");
+
+    // * The data track is skipped, and track 3 falls back to its INDEX 00.
+    let tracks = &sheet.files[0].tracks;
+    assert_eq!(to_starts(tracks), vec![(1, 0), (2, 182_000), (3, 300_493)]);
+
+    assert_eq!(tracks[1].title.as_deref(), Some("Second"));
+    assert_eq!(tracks[1].performer.as_deref(), Some("Guest"));
+    assert_eq!(tracks[1].replay_gain.trackGain, Some(-6.2));
+    assert_eq!(tracks[2].title.as_deref(), Some("Hidden Pregap Only"));
+  }
+
+  #[test]
+  fn splits_tracks_by_file() {
+    let sheet = parse_cue_sheet("FILE Disc 1.wav WAVE\nTRACK 01 AUDIO\nINDEX 01 00:00:00\nFILE \"Disc 2.wav\" WAVE\nTRACK 02 AUDIO\nINDEX 01 00:00:00\nTRACK 03 AUDIO\nINDEX 01 04:00:00\n");
+
+    assert_eq!(sheet.files.iter().map(| file | file.name.as_str()).collect::<Vec<&str>>(), vec!["Disc 1.wav", "Disc 2.wav"]);
+    assert_eq!(to_starts(&get_file_tracks(&sheet, Path::new("/music/Disc 2.flac"), false)), vec![(2, 0), (3, 240_000)]);
+    assert!(get_file_tracks(&sheet, Path::new("/music/Disc 3.flac"), false).is_empty());
+  }
+
+  #[test]
+  fn decodes_latin1_and_bom() {
+    let sheet = parse_cue_sheet(&decode_cue_sheet(b"\xef\xbb\xbfTITLE \"Caf\xc3\xa9\"\n"));
+    assert_eq!(sheet.title.as_deref(), Some("Caf\u{e9}"));
+
+    let sheet = parse_cue_sheet(&decode_cue_sheet(b"TITLE \"Caf\xe9\"\n"));
+    assert_eq!(sheet.title.as_deref(), Some("Caf\u{e9}"));
+  }
+
+  #[test]
+  fn finds_file_names_in_file_lines() {
+    let line = "  FILE \"The Album.flac\" WAVE\r";
+    let (start, end) = find_file_name(line).unwrap();
+    assert_eq!(&line[start..end], "The Album.flac");
+
+    let line = "FILE Disc 1.wav WAVE";
+    let (start, end) = find_file_name(line).unwrap();
+    assert_eq!(&line[start..end], "Disc 1.wav");
+
+    assert!(find_file_name("FILES \"a.flac\" WAVE").is_none());
+    assert!(find_file_name("TITLE \"FILE a.flac\"").is_none());
+  }
+
+  #[test]
+  fn matches_only_whole_file_names() {
+    assert!(names_file("Old.FLAC", "old.flac"));
+    assert!(names_file("rips\\old.flac", "old.flac"));
+    assert!(!names_file("old.flac.bak", "old.flac"));
+    assert!(!names_file("gold.flac", "old.flac"));
+  }
+
+  #[test]
+  fn retargets_matching_file_lines() {
+    let cue_path = std::env::temp_dir().join(format!("svunes-retarget-{}.cue", std::process::id()));
+
+    fs::write(&cue_path, "FILE \"old.flac\" WAVE\r\n  TITLE \"old.flac\"\r\nFILE old.flac WAVE\r\nFILE \"gold.flac\" WAVE\r\n").unwrap();
+    retarget_cue_sheet(&cue_path, "old.flac", "New Name.flac").unwrap();
+    assert_eq!(fs::read_to_string(&cue_path).unwrap(), "FILE \"New Name.flac\" WAVE\r\n  TITLE \"old.flac\"\r\nFILE \"New Name.flac\" WAVE\r\nFILE \"gold.flac\" WAVE\r\n");
+
+    // * Sheets that aren't UTF-8 keep their encoding, and names it can't hold are refused.
+    fs::write(&cue_path, b"TITLE \"Caf\xe9\"\nFILE \"old.flac\" WAVE\n").unwrap();
+    retarget_cue_sheet(&cue_path, "old.flac", "n\u{e9}.flac").unwrap();
+    assert_eq!(fs::read(&cue_path).unwrap(), b"TITLE \"Caf\xe9\"\nFILE \"n\xe9.flac\" WAVE\n");
+    assert!(retarget_cue_sheet(&cue_path, "n\u{e9}.flac", "\u{20ac}.flac").is_err());
+
+    let _ = fs::remove_file(&cue_path);
+  }
+}
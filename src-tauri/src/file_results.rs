@@ -0,0 +1,85 @@
+use std::io;
+
+use serde::Serialize;
+
+/// Why an operation on a file failed.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FileErrorKind {
+  PermissionDenied,
+  NotFound,
+  UnsupportedFormat,
+  TagParse,
+  Other,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FileError {
+  pub kind: FileErrorKind,
+  pub message: String,
+}
+
+/// The outcome of an operation on a single file. Successful files have no error.
+#[derive(Clone, Serialize)]
+pub struct FileResult {
+  pub path: String,
+  pub error: Option<FileError>,
+}
+
+impl FileError {
+  /// Creates a new FileError of the provided kind.
+  pub fn new(kind: FileErrorKind, message: String) -> FileError {
+    return FileError { kind, message };
+  }
+}
+
+impl From<io::Error> for FileError {
+  fn from(err: io::Error) -> FileError {
+    let kind = match err.kind() {
+      io::ErrorKind::PermissionDenied => FileErrorKind::PermissionDenied,
+      io::ErrorKind::NotFound => FileErrorKind::NotFound,
+      _ => FileErrorKind::Other,
+    };
+
+    return FileError::new(kind, err.to_string());
+  }
+}
+
+impl From<id3::Error> for FileError {
+  fn from(err: id3::Error) -> FileError {
+    return match err.kind {
+      id3::ErrorKind::Io(io_err) => FileError::from(io_err),
+      id3::ErrorKind::UnsupportedFeature => FileError::new(FileErrorKind::UnsupportedFormat, err.description),
+      _ => FileError::new(FileErrorKind::TagParse, err.description),
+    };
+  }
+}
+
+impl From<metaflac::Error> for FileError {
+  fn from(err: metaflac::Error) -> FileError {
+    return match err.kind {
+      metaflac::ErrorKind::Io(io_err) => FileError::from(io_err),
+      _ => FileError::new(FileErrorKind::TagParse, err.description.to_owned()),
+    };
+  }
+}
+
+impl From<image::ImageError> for FileError {
+  fn from(err: image::ImageError) -> FileError {
+    return match err {
+      image::ImageError::IoError(io_err) => FileError::from(io_err),
+      image::ImageError::Unsupported(_) => FileError::new(FileErrorKind::UnsupportedFormat, err.to_string()),
+      _ => FileError::new(FileErrorKind::Other, err.to_string()),
+    };
+  }
+}
+
+impl FileResult {
+  /// Creates a new FileResult for the provided path from the outcome of its operation.
+  pub fn new(path: String, outcome: Result<(), FileError>) -> FileResult {
+    return FileResult {
+      path,
+      error: outcome.err(),
+    };
+  }
+}
@@ -0,0 +1,219 @@
+use std::path::Path;
+
+use rayon::prelude::*;
+use regex::Regex;
+
+use crate::{file_results::{FileError, FileErrorKind}, music_writers::SongEditFields};
+
+/// The fields a filename pattern can use, written as %field%, and the tag each one sets.
+/// %ignore% matches text without setting anything.
+const PATTERN_FIELDS: [(&str, Option<&str>); 10] = [
+  ("albumartist", Some("albumArtist")),
+  ("artist", Some("artists")),
+  ("album", Some("album")),
+  ("title", Some("title")),
+  ("year", Some("releaseYear")),
+  ("track", Some("trackNumber")),
+  ("disc", Some("discNumber")),
+  ("genre", Some("genres")),
+  ("composer", Some("composers")),
+  ("ignore", None),
+];
+
+/// A filename pattern compiled into a regex, along with the tag set by each of its groups.
+struct FilenamePattern {
+  regex: Regex,
+  fields: Vec<Option<&'static str>>,
+  component_count: usize,
+}
+
+/// Converts the text between fields into a regex, where any run of whitespace matches any other.
+fn text_to_regex(text: &str) -> String {
+  let mut regex = String::new();
+  let mut last_was_space = false;
+
+  for character in text.chars() {
+    if character.is_whitespace() {
+      if !last_was_space {
+        regex.push_str(r"\s+");
+      }
+
+      last_was_space = true;
+      continue;
+    }
+
+    regex.push_str(&regex::escape(&character.to_string()));
+    last_was_space = false;
+  }
+
+  return regex;
+}
+
+/// Compiles a filename pattern. Folders in the pattern match the folders the file is in.
+fn compile_pattern(pattern: &str) -> Result<FilenamePattern, FileError> {
+  let components: Vec<&str> = pattern.split(['/', '\\']).filter(| component | !component.trim().is_empty()).collect();
+
+  if components.is_empty() {
+    return Err(FileError::new(FileErrorKind::Other, String::from("The pattern is empty.")));
+  }
+
+  let mut regex = String::from("^");
+  let mut fields = Vec::new();
+
+  for (i, component) in components.iter().enumerate() {
+    if i != 0 {
+      regex.push('/');
+    }
+
+    let pieces: Vec<&str> = component.split('%').collect();
+
+    if pieces.len() % 2 == 0 {
+      return Err(FileError::new(FileErrorKind::Other, format!("The pattern has an unclosed field in \"{}\".", component)));
+    }
+
+    for (j, piece) in pieces.into_iter().enumerate() {
+      if j % 2 == 0 {
+        regex.push_str(&text_to_regex(piece));
+        continue;
+      }
+
+      let name = piece.to_ascii_lowercase();
+      let field = PATTERN_FIELDS.iter().find(| (field_name, _) | *field_name == name);
+
+      if field.is_none() {
+        return Err(FileError::new(FileErrorKind::Other, format!("The pattern field %{}% doesn't exist.", piece)));
+      }
+
+      let is_number = matches!(name.as_str(), "year" | "track" | "disc");
+      regex.push_str(if is_number { r"(\d+)" } else { "([^/]+?)" });
+      fields.push(field.unwrap().1);
+    }
+  }
+
+  regex.push('$');
+
+  let regex = Regex::new(&regex).map_err(| err | FileError::new(FileErrorKind::Other, format!("The pattern couldn't be compiled: {}", err)))?;
+
+  return Ok(FilenamePattern { regex, fields, component_count: components.len() });
+}
+
+/// Gets the part of a file's path a pattern is matched against: its name without the extension, and as many parent folders as the pattern has.
+fn get_matched_path(file_path: &str, component_count: usize) -> Option<String> {
+  let path = Path::new(file_path);
+  let stem = path.file_stem()?.to_string_lossy().into_owned();
+
+  let mut components: Vec<String> = path.parent()?.components().rev().take(component_count - 1).map(| component | component.as_os_str().to_string_lossy().into_owned()).collect();
+
+  if components.len() != component_count - 1 {
+    return None;
+  }
+
+  components.reverse();
+  components.push(stem);
+
+  return Some(components.join("/"));
+}
+
+/// Matches a pattern against a file's path, and gets the edits that set the tags it found.
+fn get_filename_edits(file_path: &str, pattern: &FilenamePattern) -> Result<SongEditFields, FileError> {
+  let matched_path = get_matched_path(file_path, pattern.component_count);
+  let captures = matched_path.as_ref().and_then(| matched_path | pattern.regex.captures(matched_path));
+
+  if captures.is_none() {
+    return Err(FileError::new(FileErrorKind::Other, String::from("The file's path doesn't match the pattern.")));
+  }
+
+  let captures = captures.unwrap();
+  let mut edited_fields = SongEditFields::default();
+
+  for (i, field) in pattern.fields.iter().enumerate() {
+    let Some(field) = field else { continue };
+    let value = captures.get(i + 1).map(| value | value.as_str().trim().to_owned()).filter(| value | !value.is_empty());
+
+    // * Fields that matched nothing are left alone, rather than clearing what's already tagged.
+    if value.is_some() {
+      edited_fields.set_from_text(field, value)?;
+    }
+  }
+
+  return Ok(edited_fields);
+}
+
+/// Parses tags out of the paths of a list of files using a pattern like "%track% - %artist% - %title%",
+/// and gets the edits for each one.
+pub fn get_filename_changes(file_paths: &[String], pattern: &str) -> Vec<(String, Result<SongEditFields, FileError>)> {
+  let pattern_res = compile_pattern(pattern);
+
+  if pattern_res.is_err() {
+    let err = pattern_res.err().unwrap();
+    return file_paths.iter().map(| file_path | (file_path.to_owned(), Err(err.clone()))).collect();
+  }
+
+  let pattern = pattern_res.unwrap();
+
+  return file_paths.par_iter().map(| file_path | {
+    return (file_path.to_owned(), get_filename_edits(file_path, &pattern));
+  }).collect();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Compiles a pattern and matches it against a path, getting the text of each group.
+  fn match_pattern(pattern: &str, matched_path: &str) -> Option<Vec<String>> {
+    let compiled = compile_pattern(pattern).unwrap();
+    let captures = compiled.regex.captures(matched_path)?;
+
+    return Some(captures.iter().skip(1).map(| group | group.map(| value | value.as_str().to_owned()).unwrap_or_default()).collect());
+  }
+
+  #[test]
+  fn escapes_the_text_between_fields() {
+    assert_eq!(match_pattern("%track%. (%title%) [%ignore%]", "01. (Song) [Live]"), Some(vec![String::from("01"), String::from("Song"), String::from("Live")]));
+    assert_eq!(match_pattern("%track%. (%title%) [%ignore%]", "01x (Song) [Live]"), None);
+    assert_eq!(match_pattern("%title%+", "Song+"), Some(vec![String::from("Song")]));
+  }
+
+  #[test]
+  fn numeric_fields_only_match_digits() {
+    assert_eq!(match_pattern("%track% - %title%", "07 - Song - Remix"), Some(vec![String::from("07"), String::from("Song - Remix")]));
+    assert_eq!(match_pattern("%track% - %title%", "A1 - Song"), None);
+    assert_eq!(match_pattern("%year%%title%", "1999Song"), Some(vec![String::from("1999"), String::from("Song")]));
+  }
+
+  #[test]
+  fn any_whitespace_matches_any_other() {
+    assert_eq!(match_pattern("%artist% - %title%", "Artist  -\tSong"), Some(vec![String::from("Artist"), String::from("Song")]));
+  }
+
+  #[test]
+  fn matches_folders() {
+    let compiled = compile_pattern("%artist%/%album%\\%track% %title%").unwrap();
+    assert_eq!(compiled.component_count, 3);
+    assert_eq!(compiled.fields, vec![Some("artists"), Some("album"), Some("trackNumber"), Some("title")]);
+
+    let matched_path = get_matched_path("/music/Artist/Album/01 Song.flac", compiled.component_count);
+    assert_eq!(matched_path.as_deref(), Some("Artist/Album/01 Song"));
+    assert!(compiled.regex.is_match(&matched_path.unwrap()));
+
+    assert_eq!(get_matched_path("01 Song.flac", compiled.component_count), None);
+  }
+
+  #[test]
+  fn rejects_bad_patterns() {
+    assert!(compile_pattern("").is_err());
+    assert!(compile_pattern(" / ").is_err());
+    assert!(compile_pattern("%track - %title%").is_err());
+    assert!(compile_pattern("%track% - %name%").is_err());
+  }
+
+  #[test]
+  fn sets_the_matched_fields() {
+    let pattern = compile_pattern("%track% - %artist% - %title%").unwrap();
+    let edits = get_filename_edits("/music/07 - A; B - Song.mp3", &pattern).unwrap();
+
+    assert_eq!(serde_json::to_value(edits).unwrap(), serde_json::json!({ "trackNumber": 7, "artists": ["A", "B"], "title": "Song" }));
+    assert!(get_filename_edits("/music/Song.mp3", &pattern).is_err());
+  }
+}
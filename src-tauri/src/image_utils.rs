@@ -1,43 +1,29 @@
-use std::{fs::{self, create_dir_all, File}, io::Write, path::PathBuf, time::Duration};
+use std::{fs::{self, create_dir_all, File}, io::{Cursor, Write}, path::PathBuf, time::Duration};
 
-use image::{imageops::FilterType, ImageReader};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, ImageFormat, ImageReader};
 use palette_extract::{get_palette_with_options, Color, MaxColors, PixelEncoding, PixelFilter, Quality};
 use serde_json::Value;
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Manager, State};
 use tauri_plugin_http::reqwest::Client;
 
-use crate::{logger, music_readers::format_album_name_for_image};
+use crate::{art_cache::ArtCache, file_results::{FileError, FileErrorKind}, logger};
 
+/// The JPEG quality used when artwork has to be re-encoded and no quality was chosen.
+const DEFAULT_ART_QUALITY: u8 = 90;
 
 fn color_to_rgb(color: &Color) -> String {
   return format!("{} {} {}", color.r, color.g, color.b);
 }
 
 #[tauri::command]
-/// copies the provided image to the albums directory
-pub async fn copy_album_image(app_handle: AppHandle, image_path: String, album_name: String) -> String {
-  let bundle_id: String = app_handle.config().identifier.to_owned();
-  
-  let app_cache_dir = app_handle.path().cache_dir().expect("Couldn't resolve app cache dir.");
-  let mut file_path = app_cache_dir.join(&bundle_id).join("albums");
-
-  if !file_path.exists() {
-    let _ = create_dir_all(&file_path);
-  }
-
-  let mut file_name = format_album_name_for_image(album_name);
-  let path_as_buf = PathBuf::from(&image_path);
-  let extension = path_as_buf.extension().expect("Couldn't get extension").to_str().unwrap();
-  file_name.push('.');
-  file_name.push_str(extension);
-  file_path.push(file_name);
-
+/// copies the provided image to the album art cache
+pub async fn copy_album_image(art_cache: State<'_, ArtCache>, app_handle: AppHandle, image_path: String, album_name: String, album_artist: Option<String>) -> Result<String, ()> {
   let image_reader_res = ImageReader::open(&image_path);
   
   if image_reader_res.is_err() {
     let err = image_reader_res.err().unwrap();
     logger::log(&app_handle, format!("failed to read {}: {}.", image_path, err.to_string()).as_str(), 2);
-    return "".to_owned();
+    return Ok("".to_owned());
   }
 
   let image_reader = image_reader_res.ok().unwrap();
@@ -47,23 +33,33 @@ pub async fn copy_album_image(app_handle: AppHandle, image_path: String, album_n
   if image_res.is_err() {
     let err = image_res.err().unwrap();
     logger::log(&app_handle, format!("failed to decode {}: {}.", image_path, err.to_string()).as_str(), 2);
-    return "".to_owned();
+    return Ok("".to_owned());
   }
 
   let img = image_res.ok().unwrap();
 
   let resized = img.resize(512, 512, FilterType::CatmullRom);
 
-  let write_res = resized.save_with_format(&file_path, image_format);
+  let mut data: Vec<u8> = vec![];
+  let encode_res = resized.write_to(&mut Cursor::new(&mut data), image_format);
 
-  if write_res.is_ok() {
-    logger::log(&app_handle, format!("Copying of {} finished.", image_path).as_str(), 0);
-  } else {
-    let err = write_res.err().unwrap();
+  if encode_res.is_err() {
+    let err = encode_res.err().unwrap();
     logger::log(&app_handle, format!("Copying of {} failed with {}.", image_path, err.to_string()).as_str(), 2);
+    return Ok("".to_owned());
   }
 
-  return file_path.as_mut_os_string().to_str().expect("failed to parse copied file path!").to_owned();
+  let file_type = image_format.extensions_str().first().copied().unwrap_or("img");
+  let file_path_res = art_cache.store(&app_handle, &data, file_type, album_artist.as_deref(), &album_name);
+  art_cache.save(&app_handle);
+
+  if file_path_res.is_none() {
+    return Ok("".to_owned());
+  }
+
+  logger::log(&app_handle, format!("Copying of {} finished.", image_path).as_str(), 0);
+
+  return Ok(file_path_res.unwrap());
 }
 
 #[tauri::command]
@@ -189,3 +185,56 @@ pub async fn download_image(app_handle: AppHandle, image_url: String, dest_path:
     return String::from("failed");
   }
 }
+
+/// Encodes an image as a JPEG, dropping any transparency.
+fn encode_jpeg(img: &image::DynamicImage, quality: u8) -> Result<Vec<u8>, FileError> {
+  let mut data: Vec<u8> = vec![];
+  img.to_rgb8().write_with_encoder(JpegEncoder::new_with_quality(&mut data, quality.clamp(1, 100)))?;
+
+  return Ok(data);
+}
+
+/// Prepares an image to be embedded as artwork, returning its mime type and data.
+/// The mime type comes from the image's magic bytes. Images larger than max_dimension get downscaled,
+/// and setting jpeg_quality recompresses them as a JPEG.
+pub fn prepare_embedded_art(data: Vec<u8>, max_dimension: Option<u32>, jpeg_quality: Option<u8>) -> Result<(String, Vec<u8>), FileError> {
+  let format_res = image::guess_format(&data);
+
+  if format_res.is_err() {
+    return Err(FileError::new(FileErrorKind::UnsupportedFormat, String::from("The artwork isn't a supported image.")));
+  }
+
+  let format = format_res.unwrap();
+
+  if max_dimension.is_none() && jpeg_quality.is_none() {
+    return Ok((format.to_mime_type().to_owned(), data));
+  }
+
+  let img = image::load_from_memory_with_format(&data, format)?;
+  let needs_resize = max_dimension.is_some_and(| max_dimension | img.width().max(img.height()) > max_dimension);
+
+  if !needs_resize && jpeg_quality.is_none() {
+    return Ok((format.to_mime_type().to_owned(), data));
+  }
+
+  let img = match max_dimension {
+    Some(max_dimension) if needs_resize => img.resize(max_dimension, max_dimension, FilterType::Lanczos3),
+    _ => img,
+  };
+
+  // * PNGs stay PNGs when they're only being resized, so their transparency survives.
+  if jpeg_quality.is_none() && format == ImageFormat::Png {
+    let mut resized: Vec<u8> = vec![];
+    img.write_to(&mut Cursor::new(&mut resized), ImageFormat::Png)?;
+    return Ok((format.to_mime_type().to_owned(), resized));
+  }
+
+  let encoded = encode_jpeg(&img, jpeg_quality.unwrap_or(DEFAULT_ART_QUALITY))?;
+
+  // * Recompressing an image that's already small can make it bigger, in which case the original is kept.
+  if !needs_resize && encoded.len() >= data.len() {
+    return Ok((format.to_mime_type().to_owned(), data));
+  }
+
+  return Ok((ImageFormat::Jpeg.to_mime_type().to_owned(), encoded));
+}
@@ -0,0 +1,464 @@
+use std::{fs::{self, create_dir_all}, io::Cursor, path::{Path, PathBuf}, sync::Mutex};
+
+use id3::no_tag_ok;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::{art_cache::hash_to_hex, file_results::{FileError, FileErrorKind}, logger, music_writers::{get_tag_format, TagFormat}, tag_trailers::{read_trailers, restore_trailers}};
+
+/// Bump this whenever the shape of the journal changes, so old journals get discarded.
+const JOURNAL_VERSION: u32 = 1;
+
+/// The number of edits and organizes kept before the oldest ones (and their backups) get removed.
+const MAX_OPERATIONS: usize = 25;
+/// The number of deletions kept. They're counted on their own, since dropping one empties its part of the trash for good.
+const MAX_DELETE_OPERATIONS: usize = 25;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationKind {
+  Edit,
+  Delete,
+  Organize,
+}
+
+/// How an entry's backup was taken, and so how it gets restored.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupKind {
+  /// The file's ID3v2 tag. A missing backup means the file had no tag.
+  Id3,
+  /// The file's FLAC metadata blocks.
+  Flac,
+  /// The whole file, moved into the trash.
+  Trash,
+  /// Nothing, the file was only moved to a new path.
+  Moved,
+  /// The whole contents of a file that got rewritten, like a cue sheet pointed at a renamed song.
+  /// A missing backup means the file didn't exist yet, so undoing removes it.
+  Contents,
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+  pub filePath: String,
+  pub backupKind: BackupKind,
+  /// The name of the backup in the operation's folder.
+  backupName: Option<String>,
+  /// The path a moved file came from.
+  #[serde(default)]
+  movedFrom: Option<String>,
+  /// The name of the backup of an MP3 file's ID3v1 and APE tags, if it had any.
+  #[serde(default)]
+  trailerName: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Operation {
+  pub id: String,
+  pub kind: OperationKind,
+  /// When the operation happened, in milliseconds since the epoch.
+  pub timestamp: i64,
+  pub entries: Vec<JournalEntry>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct JournalFile {
+  version: u32,
+  operations: Vec<Operation>,
+}
+
+/// An operation that is being run. Its backups are taken before each file is touched, and it gets recorded once it's done.
+pub struct PendingOperation {
+  id: String,
+  kind: OperationKind,
+  timestamp: i64,
+  dir: PathBuf,
+}
+
+/// Removes the oldest deletions, or the oldest other operations, past a limit, along with their backups.
+fn prune_operations(operations: &mut Vec<Operation>, journal_dir: &Path, deletions: bool, limit: usize) {
+  let count = operations.iter().filter(| operation | (operation.kind == OperationKind::Delete) == deletions).count();
+  let mut to_remove = count.saturating_sub(limit);
+
+  operations.retain(| operation | {
+    if to_remove == 0 || (operation.kind == OperationKind::Delete) != deletions {
+      return true;
+    }
+
+    to_remove -= 1;
+    let _ = fs::remove_dir_all(journal_dir.join(&operation.id));
+    return false;
+  });
+}
+
+/// The journal of tag edits, deletions, renames and moves, used to undo them.
+pub struct Journal {
+  operations: Mutex<Option<Vec<Operation>>>,
+}
+
+/// Gets the folder the journal and its backups are stored in.
+fn get_journal_dir(app_handle: &AppHandle) -> PathBuf {
+  let app_data_dir = app_handle.path().app_data_dir().expect("Couldn't resolve app data dir.");
+  return app_data_dir.join("journal");
+}
+
+/// Gets the path of the on disk journal.
+fn get_journal_path(app_handle: &AppHandle) -> PathBuf {
+  return get_journal_dir(app_handle).join("journal.json");
+}
+
+/// Loads the journal from disk, or starts a new one if it is missing or outdated.
+fn load_operations(app_handle: &AppHandle) -> Vec<Operation> {
+  let journal_path = get_journal_path(app_handle);
+
+  if !journal_path.exists() {
+    return Vec::new();
+  }
+
+  let journal_res = fs::read_to_string(&journal_path).map_err(| err | err.to_string()).and_then(| contents | {
+    return serde_json::from_str::<JournalFile>(&contents).map_err(| err | err.to_string());
+  });
+
+  if journal_res.is_err() {
+    let err = journal_res.err().unwrap();
+    logger::log(app_handle, format!("Failed to load the undo journal, starting a new one: {}", err).as_str(), 1);
+    return Vec::new();
+  }
+
+  let journal = journal_res.unwrap();
+
+  if journal.version != JOURNAL_VERSION {
+    logger::log(app_handle, "Undo journal is outdated, starting a new one.", 0);
+    return Vec::new();
+  }
+
+  return journal.operations;
+}
+
+/// Saves the journal to disk.
+fn save_operations(app_handle: &AppHandle, operations: &Vec<Operation>) {
+  let journal = JournalFile {
+    version: JOURNAL_VERSION,
+    operations: operations.clone(),
+  };
+
+  let contents = serde_json::to_string(&journal).expect("Can't serialize undo journal to string.");
+  let write_res = fs::write(get_journal_path(app_handle), contents);
+
+  if write_res.is_err() {
+    let err = write_res.err().unwrap();
+    logger::log(app_handle, format!("Failed to save the undo journal: {}", err.to_string()).as_str(), 2);
+  }
+}
+
+/// Moves a file, copying it if it has to cross file systems.
+pub fn move_file(from: &Path, to: &Path) -> Result<(), FileError> {
+  if fs::rename(from, to).is_ok() {
+    return Ok(());
+  }
+
+  fs::copy(from, to)?;
+  fs::remove_file(from)?;
+
+  return Ok(());
+}
+
+/// Reads the tag block of a music file so it can be put back later.
+fn read_tag_backup(file_path: &str) -> Result<(BackupKind, Option<Vec<u8>>), FileError> {
+  if get_tag_format(file_path)? == TagFormat::Id3 {
+    let tag = no_tag_ok(id3::Tag::read_from_path(file_path))?;
+
+    if tag.is_none() {
+      return Ok((BackupKind::Id3, None));
+    }
+
+    let tag = tag.unwrap();
+    let mut data = Vec::new();
+    tag.write_to(&mut data, tag.version())?;
+
+    return Ok((BackupKind::Id3, Some(data)));
+  }
+
+  let mut tag = metaflac::Tag::read_from_path(file_path)?;
+  let mut data = Vec::new();
+  tag.write_to(&mut data)?;
+
+  return Ok((BackupKind::Flac, Some(data)));
+}
+
+/// Puts the backed up tag block of a music file back.
+fn restore_tag_backup(file_path: &str, backup_kind: BackupKind, backup_path: Option<PathBuf>) -> Result<(), FileError> {
+  let data = match &backup_path {
+    Some(backup_path) => Some(fs::read(backup_path)?),
+    None => None,
+  };
+
+  match (backup_kind, data) {
+    (BackupKind::Id3, Some(data)) => {
+      let tag = id3::Tag::read_from2(Cursor::new(data))?;
+      tag.write_to_path(file_path, tag.version())?;
+    }
+    (BackupKind::Id3, None) => {
+      id3::Tag::remove_from_path(file_path)?;
+    }
+    (BackupKind::Flac, Some(data)) => {
+      let mut tag = metaflac::Tag::read_from(&mut Cursor::new(data))?;
+      tag.write_to_path(file_path)?;
+    }
+    _ => {
+      return Err(FileError::new(FileErrorKind::NotFound, String::from("The backup is missing.")));
+    }
+  }
+
+  return Ok(());
+}
+
+/// Puts the backed up ID3v1 and APE tags of an MP3 file back, if it had any.
+fn restore_trailer_backup(file_path: &str, trailer_path: Option<PathBuf>) -> Result<(), FileError> {
+  if let Some(trailer_path) = trailer_path {
+    restore_trailers(file_path, &fs::read(trailer_path)?)?;
+  }
+
+  return Ok(());
+}
+
+impl PendingOperation {
+  /// Gets the name a file's backup is stored under.
+  fn get_backup_name(file_path: &str, backup_kind: BackupKind) -> String {
+    let suffix = match backup_kind {
+      BackupKind::Id3 => "id3",
+      BackupKind::Flac => "flac",
+      BackupKind::Trash => "trash",
+      BackupKind::Moved => "moved",
+      BackupKind::Contents => "contents",
+    };
+
+    return format!("{}.{}", hash_to_hex(file_path.as_bytes()), suffix);
+  }
+
+  /// Backs up a music file's tags before they get edited.
+  pub fn backup_tags(&self, file_path: &str) -> Result<JournalEntry, FileError> {
+    let (backup_kind, data) = read_tag_backup(file_path)?;
+    let mut backup_name = None;
+
+    if let Some(data) = data {
+      let name = PendingOperation::get_backup_name(file_path, backup_kind);
+      fs::write(self.dir.join(&name), data)?;
+      backup_name = Some(name);
+    }
+
+    // * Writes can remove or rewrite the ID3v1 and APE tags at the end of MP3 files, so those get backed up too.
+    let mut trailer_name = None;
+
+    if backup_kind == BackupKind::Id3 {
+      if let Some(trailers) = read_trailers(file_path)? {
+        let name = format!("{}.trailers", hash_to_hex(file_path.as_bytes()));
+        fs::write(self.dir.join(&name), trailers)?;
+        trailer_name = Some(name);
+      }
+    }
+
+    return Ok(JournalEntry {
+      filePath: file_path.to_owned(),
+      backupKind: backup_kind,
+      backupName: backup_name,
+      movedFrom: None,
+      trailerName: trailer_name,
+    });
+  }
+
+  /// Backs up the whole contents of a file before it gets rewritten, created or removed.
+  pub fn backup_contents(&self, file_path: &str) -> Result<JournalEntry, FileError> {
+    let mut backup_name = None;
+
+    if Path::new(file_path).exists() {
+      let name = PendingOperation::get_backup_name(file_path, BackupKind::Contents);
+      fs::copy(file_path, self.dir.join(&name))?;
+      backup_name = Some(name);
+    }
+
+    return Ok(JournalEntry {
+      filePath: file_path.to_owned(),
+      backupKind: BackupKind::Contents,
+      backupName: backup_name,
+      movedFrom: None,
+      trailerName: None,
+    });
+  }
+
+  /// Removes the backups of an entry that won't be recorded, like when its file failed to be written.
+  pub fn discard(&self, entry: &JournalEntry) {
+    for name in entry.backupName.iter().chain(entry.trailerName.iter()) {
+      let _ = fs::remove_file(self.dir.join(name));
+    }
+  }
+
+  /// Moves a file into the trash instead of deleting it.
+  pub fn trash_file(&self, file_path: &str) -> Result<JournalEntry, FileError> {
+    let name = PendingOperation::get_backup_name(file_path, BackupKind::Trash);
+    move_file(Path::new(file_path), &self.dir.join(&name))?;
+
+    return Ok(JournalEntry {
+      filePath: file_path.to_owned(),
+      backupKind: BackupKind::Trash,
+      backupName: Some(name),
+      movedFrom: None,
+      trailerName: None,
+    });
+  }
+
+  /// Records that a file was moved, so it can be moved back.
+  pub fn record_move(&self, from: &str, to: &str) -> JournalEntry {
+    return JournalEntry {
+      filePath: to.to_owned(),
+      backupKind: BackupKind::Moved,
+      backupName: None,
+      movedFrom: Some(from.to_owned()),
+      trailerName: None,
+    };
+  }
+}
+
+impl Journal {
+  /// Creates a new Journal. The operations get loaded the first time they are used.
+  pub fn new() -> Journal {
+    return Journal {
+      operations: Mutex::new(None),
+    };
+  }
+
+  /// Runs the provided function with the journal's operations, loading them if needed.
+  fn with_operations<T>(&self, app_handle: &AppHandle, action: impl FnOnce(&mut Vec<Operation>) -> T) -> T {
+    let mut operations_guard = self.operations.lock().unwrap();
+    let operations = operations_guard.get_or_insert_with(| | load_operations(app_handle));
+
+    return action(operations);
+  }
+
+  /// Starts a new operation, creating the folder its backups go in.
+  pub fn begin(&self, app_handle: &AppHandle, kind: OperationKind) -> Result<PendingOperation, FileError> {
+    let timestamp = chrono::Utc::now().timestamp_millis();
+    let journal_dir = get_journal_dir(app_handle);
+
+    let mut id = timestamp.to_string();
+    let mut attempt = 1;
+
+    while journal_dir.join(&id).exists() {
+      id = format!("{}-{}", timestamp, attempt);
+      attempt += 1;
+    }
+
+    let dir = journal_dir.join(&id);
+    create_dir_all(&dir)?;
+
+    return Ok(PendingOperation { id, kind, timestamp, dir });
+  }
+
+  /// Records a finished operation. Operations that didn't touch any files are dropped.
+  pub fn record(&self, app_handle: &AppHandle, pending: PendingOperation, entries: Vec<JournalEntry>) {
+    if entries.is_empty() {
+      let _ = fs::remove_dir_all(&pending.dir);
+      return;
+    }
+
+    let journal_dir = get_journal_dir(app_handle);
+
+    self.with_operations(app_handle, | operations | {
+      operations.push(Operation {
+        id: pending.id,
+        kind: pending.kind,
+        timestamp: pending.timestamp,
+        entries,
+      });
+
+      // * A run of edits shouldn't be able to push deleted files out of the trash, so deletions are pruned separately.
+      prune_operations(operations, &journal_dir, true, MAX_DELETE_OPERATIONS);
+      prune_operations(operations, &journal_dir, false, MAX_OPERATIONS);
+
+      save_operations(app_handle, operations);
+    });
+  }
+
+  /// Gets the recorded operations, newest first.
+  pub fn list(&self, app_handle: &AppHandle) -> Vec<Operation> {
+    return self.with_operations(app_handle, | operations | {
+      return operations.iter().rev().cloned().collect();
+    });
+  }
+
+  /// Undoes the last recorded operation, putting its files back the way they were.
+  /// Entries that fail to restore stay in the journal so the undo can be retried.
+  pub fn undo_last(&self, app_handle: &AppHandle) -> Option<Operation> {
+    let journal_dir = get_journal_dir(app_handle);
+
+    return self.with_operations(app_handle, | operations | {
+      let mut operation = operations.pop()?;
+      let operation_dir = journal_dir.join(&operation.id);
+
+      let mut restored = Vec::new();
+      let mut failed = Vec::new();
+
+      for entry in operation.entries.drain(..) {
+        let backup_path = entry.backupName.as_ref().map(| name | operation_dir.join(name));
+
+        let restore_res = match entry.backupKind {
+          BackupKind::Trash => {
+            if Path::new(&entry.filePath).exists() {
+              Err(FileError::new(FileErrorKind::Other, String::from("A file already exists at its old path.")))
+            } else {
+              backup_path.ok_or(FileError::new(FileErrorKind::NotFound, String::from("The backup is missing."))).and_then(| backup_path | move_file(&backup_path, Path::new(&entry.filePath)))
+            }
+          }
+          BackupKind::Moved => {
+            let moved_from = PathBuf::from(entry.movedFrom.clone().unwrap_or_default());
+
+            if moved_from.exists() {
+              Err(FileError::new(FileErrorKind::Other, String::from("A file already exists at its old path.")))
+            } else {
+              moved_from.parent().map(create_dir_all).unwrap_or(Ok(())).map_err(FileError::from).and_then(| _ | move_file(Path::new(&entry.filePath), &moved_from))
+            }
+          }
+          BackupKind::Contents => {
+            match backup_path {
+              Some(backup_path) => fs::copy(&backup_path, &entry.filePath).map(| _ | ()).map_err(FileError::from),
+              None if Path::new(&entry.filePath).exists() => fs::remove_file(&entry.filePath).map_err(FileError::from),
+              None => Ok(()),
+            }
+          }
+          _ => {
+            let trailer_path = entry.trailerName.as_ref().map(| name | operation_dir.join(name));
+            restore_tag_backup(&entry.filePath, entry.backupKind, backup_path).and_then(| _ | restore_trailer_backup(&entry.filePath, trailer_path))
+          }
+        };
+
+        if restore_res.is_err() {
+          let err = restore_res.err().unwrap();
+          logger::log(app_handle, format!("Failed to restore {}: {}", &entry.filePath, err.message).as_str(), 2);
+          failed.push(entry);
+          continue;
+        }
+
+        restored.push(entry);
+      }
+
+      let failed_count = failed.len();
+
+      if failed.is_empty() {
+        let _ = fs::remove_dir_all(&operation_dir);
+      } else {
+        operations.push(Operation {
+          entries: failed,
+          ..operation.clone()
+        });
+      }
+
+      save_operations(app_handle, operations);
+      logger::log(app_handle, format!("Undid {} of {} files from operation {}.", restored.len(), restored.len() + failed_count, &operation.id).as_str(), 0);
+
+      operation.entries = restored;
+      return Some(operation);
+    });
+  }
+}
@@ -3,9 +3,24 @@ mod logger;
 mod symphonia_mock;
 mod music_readers;
 mod music_writers;
+mod tag_trailers;
+mod ratings;
+mod file_results;
+mod journal;
+mod organizer;
+mod tag_transforms;
+mod filename_tags;
+mod library_index;
+mod scan_progress;
+mod scan_rules;
+mod song;
+mod cue_sheet;
+mod lyrics;
 mod playback;
 mod watcher;
 mod image_utils;
+mod wavpack;
+mod art_cache;
 mod platforms;
 
 use platforms::init_platform_specifics;
@@ -14,11 +29,21 @@ use tauri_plugin_dialog::DialogExt;
 use tauri_plugin_fs::FsExt;
 use watcher::Watcher;
 
-use std::{fs, panic::{self, Location}, path::PathBuf, process::exit, sync::mpsc::channel};
+use std::{collections::HashSet, panic::{self, Location}, path::PathBuf, process::exit, sync::mpsc::channel};
 
-use music_readers::read_music_folder;
-use music_writers::{write_music_file, SongEditFields};
+use art_cache::ArtCache;
+use file_results::{FileError, FileErrorKind, FileResult};
+use filename_tags::get_filename_changes;
+use journal::{Journal, JournalEntry, Operation, OperationKind};
+use library_index::{LibraryDiff, LibraryIndex};
+use music_readers::{find_music_files, ScanError};
+use organizer::OrganizeResult;
+use music_writers::{export_lrc_sidecar, preview_music_file, write_music_file, FilePreview, SongEditFields, TagWriteSettings};
 use rayon::iter::IntoParallelRefIterator;
+use scan_progress::ScanState;
+use scan_rules::{CompiledScanRules, ScanRules};
+use song::Song;
+use tag_transforms::{get_transform_changes, TagTransform};
 use panic_message::get_panic_info_message;
 use serde_json::{Map, Value};
 use tauri::{self, menu::{MenuBuilder, MenuItem}, tray::TrayIconBuilder, App, AppHandle, Manager, RunEvent, State};
@@ -35,60 +60,305 @@ struct Payload {
   cwd: String,
 }
 
-#[tauri::command]
-/// Writes changes to a list of music files.
-fn write_music_files(app_handle: AppHandle, changes_str: String) -> bool {
-  let changes: Map<String, Value> = serde_json::from_str(&changes_str).expect("Couldn't deserialize changes map.");
+#[derive(serde::Serialize)]
+struct LibraryScan {
+  entries: Vec<Song>,
+  errors: Vec<ScanError>,
+  diff: LibraryDiff,
+  cancelled: bool,
+}
+
+/// Parses the settings tags are saved with, falling back to the defaults.
+fn parse_write_settings(write_settings_str: Option<String>) -> TagWriteSettings {
+  return write_settings_str.map(| settings_str | serde_json::from_str(&settings_str).expect("Couldn't deserialize tag write settings.")).unwrap_or_default();
+}
+
+/// Splits off changes that would write a single track's fields to a file that's split into cue sheet tracks.
+/// Those tracks all share the file, so the changes would apply to every one of them.
+fn reject_cue_track_edits(app_handle: &AppHandle, changes: Vec<(String, SongEditFields)>) -> (Vec<(String, SongEditFields)>, Vec<FileResult>) {
+  if changes.iter().all(| (_, changed_fields) | changed_fields.get_track_edits().is_empty()) {
+    return (changes, Vec::new());
+  }
+
+  let index = LibraryIndex::load(app_handle);
+  let mut rejected = Vec::new();
+
+  let allowed = changes.into_iter().filter_map(| (file_path, changed_fields) | {
+    let track_edits = changed_fields.get_track_edits();
+    let is_split = index.get_songs(&file_path).is_some_and(| songs | songs.iter().any(| song | song.cueTrack.is_some()));
+
+    if is_split && !track_edits.is_empty() {
+      let message = format!("{} is split into cue sheet tracks, so these fields can't be written to it: {}.", &file_path, track_edits.join(", "));
+      logger::log(app_handle, &message, 2);
+      rejected.push(FileResult::new(file_path, Err(FileError::new(FileErrorKind::Other, message))));
+      return None;
+    }
+
+    return Some((file_path, changed_fields));
+  }).collect();
+
+  return (allowed, rejected);
+}
+
+/// Writes changes to a list of music files. The old tags are journaled first so the changes can be undone.
+fn write_changes(journal: &Journal, app_handle: &AppHandle, changes: Vec<(String, SongEditFields)>, settings: &TagWriteSettings) -> Vec<FileResult> {
+  let (changes, mut rejected) = reject_cue_track_edits(app_handle, changes);
+
+  if changes.is_empty() {
+    return rejected;
+  }
+
+  let pending_res = journal.begin(app_handle, OperationKind::Edit);
+
+  if pending_res.is_err() {
+    let err = pending_res.err().unwrap();
+    logger::log(app_handle, format!("Failed to start the undo journal, so no changes were written: {}", err.message).as_str(), 2);
+    rejected.extend(changes.into_iter().map(| (file_path, _) | FileResult::new(file_path, Err(err.clone()))));
+    return rejected;
+  }
+
+  let pending = pending_res.unwrap();
   
   let (sender, receiver) = channel();
   
-  let status: Vec<bool> = changes.keys().par_bridge().map_with(sender, | log_sender, key | {
-    let file_path = key.to_owned();
+  let results: Vec<(FileResult, Vec<JournalEntry>)> = changes.into_par_iter().map_with(sender, | log_sender, (file_path, changed_fields) | {
+    // * Files are only written once their old tags are backed up, so every write can be undone.
+    let backup_res = pending.backup_tags(&file_path);
+
+    if backup_res.is_err() {
+      let err = backup_res.err().unwrap();
+      let _ = log_sender.send(format!("Failed to back up the tags of {}: {}", &file_path, err.message));
+      return (FileResult::new(file_path, Err(err)), Vec::new());
+    }
+
+    let entry = backup_res.unwrap();
+    let write_res = write_music_file(log_sender, file_path.clone(), changed_fields.clone(), settings);
+
+    // * Failed writes leave the file untouched, so there's nothing to undo.
+    if write_res.is_err() {
+      pending.discard(&entry);
+      return (FileResult::new(file_path, write_res), Vec::new());
+    }
+
+    if !settings.lrcSidecar {
+      return (FileResult::new(file_path, write_res), vec![entry]);
+    }
+
+    // * The sidecar is only exported once the tags are written, so a failed write leaves it alone.
+    let export_res = export_lrc_sidecar(&pending, &file_path, &changed_fields);
 
-    let value = changes.get(key).unwrap().to_owned();
-    let changed_fields: SongEditFields = serde_json::from_value(value).unwrap();
+    if export_res.is_err() {
+      let err = export_res.err().unwrap();
+      let _ = log_sender.send(format!("Failed to export the lyrics of {}: {}", &file_path, err.message));
+      return (FileResult::new(file_path, Err(err)), vec![entry]);
+    }
 
-    return write_music_file(log_sender, file_path, changed_fields);
+    return (FileResult::new(file_path, write_res), [Some(entry), export_res.unwrap()].into_iter().flatten().collect());
   }).collect();
 
   receiver.iter().for_each(| log: String | {
-    logger::log(&app_handle, &log, 2);
+    logger::log(app_handle, &log, 2);
   });
 
-  return status.into_iter().all(| success | { return success; });
+  let (results, entries): (Vec<FileResult>, Vec<Vec<JournalEntry>>) = results.into_iter().unzip();
+
+  journal.record(app_handle, pending, entries.into_iter().flatten().collect());
+
+  rejected.extend(results);
+  return rejected;
 }
 
 #[tauri::command]
-/// Deletes the given songs.
-fn delete_songs(app_handle: AppHandle, file_paths_str: String) -> bool {
-  let mut success = true;
+/// Writes changes to a list of music files. The old tags are journaled first so the changes can be undone.
+/// Returns the outcome of each file.
+fn write_music_files(journal: State<'_, Journal>, app_handle: AppHandle, changes_str: String, write_settings_str: Option<String>) -> Vec<FileResult> {
+  let changes: Map<String, Value> = serde_json::from_str(&changes_str).expect("Couldn't deserialize changes map.");
+  let settings = parse_write_settings(write_settings_str);
+
+  let changes: Vec<(String, SongEditFields)> = changes.into_iter().map(| (file_path, value) | {
+    return (file_path, serde_json::from_value(value).unwrap());
+  }).collect();
+
+  return write_changes(&journal, &app_handle, changes, &settings);
+}
+
+#[tauri::command]
+/// Previews the changes writing would make to a list of music files, without saving anything.
+fn preview_music_file_changes(changes_str: String, write_settings_str: Option<String>) -> Vec<FilePreview> {
+  let changes: Map<String, Value> = serde_json::from_str(&changes_str).expect("Couldn't deserialize changes map.");
+  let settings = parse_write_settings(write_settings_str);
+
+  return changes.iter().par_bridge().map(| (file_path, value) | {
+    let changed_fields: SongEditFields = serde_json::from_value(value.to_owned()).unwrap();
+    return preview_music_file(file_path.to_owned(), changed_fields, &settings);
+  }).collect();
+}
+
+/// Writes edits that were worked out from each file, like by transforms or filename patterns.
+/// Files whose edits couldn't be worked out are reported with their error.
+fn write_computed_changes(journal: &Journal, app_handle: &AppHandle, computed: Vec<(String, Result<SongEditFields, FileError>)>, settings: &TagWriteSettings) -> Vec<FileResult> {
+  let mut results = Vec::new();
+  let mut changes = Vec::new();
+
+  for (file_path, edits_res) in computed {
+    match edits_res {
+      Ok(edited_fields) => changes.push((file_path, edited_fields)),
+      Err(err) => {
+        logger::log(app_handle, format!("Failed to work out the changes to {}: {}", &file_path, err.message).as_str(), 2);
+        results.push(FileResult::new(file_path, Err(err)));
+      }
+    }
+  }
+
+  if !changes.is_empty() {
+    results.extend(write_changes(journal, app_handle, changes, settings));
+  }
+
+  return results;
+}
+
+/// Previews edits that were worked out from each file, without saving anything.
+fn preview_computed_changes(computed: Vec<(String, Result<SongEditFields, FileError>)>, settings: &TagWriteSettings) -> Vec<FilePreview> {
+  return computed.into_par_iter().map(| (file_path, edits_res) | {
+    return match edits_res {
+      Ok(edited_fields) => preview_music_file(file_path, edited_fields, settings),
+      Err(err) => FilePreview { path: file_path, changes: Vec::new(), error: Some(err) },
+    };
+  }).collect();
+}
+
+#[tauri::command]
+/// Runs batch tag transforms over a list of music files, in order, and writes the results.
+/// Tracks are numbered in the order the files are provided. Returns the outcome of each file.
+fn transform_music_files(journal: State<'_, Journal>, app_handle: AppHandle, transforms_str: String, file_paths_str: String, write_settings_str: Option<String>) -> Vec<FileResult> {
+  let transforms: Vec<TagTransform> = serde_json::from_str(&transforms_str).expect("Couldn't deserialize transforms array.");
   let file_paths: Vec<String> = serde_json::from_str(&file_paths_str).expect("Couldn't deserialize file paths array.");
 
-  for file_path_str in file_paths {
-    let file_path = PathBuf::from(&file_path_str);
+  let settings = parse_write_settings(write_settings_str);
 
-    let result = fs::remove_file(file_path);
+  return write_computed_changes(&journal, &app_handle, get_transform_changes(&file_paths, &transforms), &settings);
+}
 
-    if result.is_err() {
-      success = false;
-      let err = result.err().unwrap();
-      logger::log(&app_handle, format!("Failed to delete {}: {}", &file_path_str, err.to_string()).as_str(), 0);
-      break;
+#[tauri::command]
+/// Previews the changes batch tag transforms would make to a list of music files, without saving anything.
+fn preview_music_file_transforms(transforms_str: String, file_paths_str: String, write_settings_str: Option<String>) -> Vec<FilePreview> {
+  let transforms: Vec<TagTransform> = serde_json::from_str(&transforms_str).expect("Couldn't deserialize transforms array.");
+  let file_paths: Vec<String> = serde_json::from_str(&file_paths_str).expect("Couldn't deserialize file paths array.");
+
+  let settings = parse_write_settings(write_settings_str);
+
+  return preview_computed_changes(get_transform_changes(&file_paths, &transforms), &settings);
+}
+
+#[tauri::command]
+/// Tags a list of music files with values parsed from their paths, using a pattern like "%track% - %artist% - %title%".
+/// Folders in the pattern match the folders the files are in. Returns the outcome of each file.
+fn tag_files_from_names(journal: State<'_, Journal>, app_handle: AppHandle, pattern: String, file_paths_str: String, write_settings_str: Option<String>) -> Vec<FileResult> {
+  let file_paths: Vec<String> = serde_json::from_str(&file_paths_str).expect("Couldn't deserialize file paths array.");
+
+  let settings = parse_write_settings(write_settings_str);
+
+  return write_computed_changes(&journal, &app_handle, get_filename_changes(&file_paths, &pattern), &settings);
+}
+
+#[tauri::command]
+/// Previews the tags parsing a list of music files' paths would set, without saving anything.
+fn preview_tags_from_names(pattern: String, file_paths_str: String, write_settings_str: Option<String>) -> Vec<FilePreview> {
+  let file_paths: Vec<String> = serde_json::from_str(&file_paths_str).expect("Couldn't deserialize file paths array.");
+
+  let settings = parse_write_settings(write_settings_str);
+
+  return preview_computed_changes(get_filename_changes(&file_paths, &pattern), &settings);
+}
+
+#[tauri::command]
+/// Deletes the given songs by moving them to the trash, so the deletion can be undone.
+/// Returns the outcome of each file.
+fn delete_songs(journal: State<'_, Journal>, app_handle: AppHandle, file_paths_str: String) -> Vec<FileResult> {
+  let mut file_paths: Vec<String> = serde_json::from_str(&file_paths_str).expect("Couldn't deserialize file paths array.");
+
+  // * Cue sheet tracks share a file, so it only needs to be trashed once.
+  let mut seen_paths = HashSet::new();
+  file_paths.retain(| file_path | seen_paths.insert(file_path.clone()));
+
+  let pending_res = journal.begin(&app_handle, OperationKind::Delete);
+
+  if pending_res.is_err() {
+    let err = pending_res.err().unwrap();
+    logger::log(&app_handle, format!("Failed to start the undo journal, so no songs were deleted: {}", err.message).as_str(), 2);
+    return file_paths.into_iter().map(| file_path | FileResult::new(file_path, Err(err.clone()))).collect();
+  }
+
+  let pending = pending_res.unwrap();
+  let mut entries = Vec::new();
+  let mut results = Vec::new();
+
+  for file_path in file_paths {
+    let trash_res = pending.trash_file(&file_path);
+
+    if trash_res.is_err() {
+      let err = trash_res.err().unwrap();
+      logger::log(&app_handle, format!("Failed to delete {}: {}", &file_path, err.message).as_str(), 2);
+      results.push(FileResult::new(file_path, Err(err)));
+      continue;
     }
+
+    entries.push(trash_res.unwrap());
+    results.push(FileResult::new(file_path, Ok(())));
   }
 
-  if !success {
+  let deleted_count = entries.len();
+  journal.record(&app_handle, pending, entries);
+
+  if deleted_count == results.len() {
     logger::log(&app_handle, "Successfully deleted songs.", 0);
+  } else {
+    logger::log(&app_handle, format!("Deleted {} of {} songs.", deleted_count, results.len()).as_str(), 1);
   }
 
-  return success;
+  return results;
+}
+
+#[tauri::command]
+/// Moves and renames music files to match a path template built from their tags, along with their sidecar files.
+/// With dry_run set, nothing is moved and the planned moves are returned instead.
+fn organize_files(watcher: State<'_, Watcher>, journal: State<'_, Journal>, app_handle: AppHandle, template: String, file_paths_str: String, dry_run: bool) -> Vec<OrganizeResult> {
+  let file_paths: Vec<String> = serde_json::from_str(&file_paths_str).expect("Couldn't deserialize file paths array.");
+  return organizer::organize_files(&app_handle, &watcher, &journal, &template, file_paths, dry_run);
+}
+
+#[tauri::command]
+/// Renames music files in place to match a file name template built from their tags, along with their sidecar files.
+/// With dry_run set, nothing is renamed and the planned renames are returned instead.
+fn rename_files(watcher: State<'_, Watcher>, journal: State<'_, Journal>, app_handle: AppHandle, template: String, file_paths_str: String, dry_run: bool) -> Vec<OrganizeResult> {
+  let file_paths: Vec<String> = serde_json::from_str(&file_paths_str).expect("Couldn't deserialize file paths array.");
+  return organizer::rename_files(&app_handle, &watcher, &journal, &template, file_paths, dry_run);
+}
+
+#[tauri::command]
+/// Undoes the last tag edit or deletion. Returns the operation that was undone, if there was one.
+fn undo_last_operation(journal: State<'_, Journal>, app_handle: AppHandle) -> Option<Operation> {
+  return journal.undo_last(&app_handle);
+}
+
+#[tauri::command]
+/// Lists the tag edits, deletions, renames and moves that can be undone, newest first.
+fn list_operations(journal: State<'_, Journal>, app_handle: AppHandle) -> Vec<Operation> {
+  return journal.list(&app_handle);
 }
 
 #[tauri::command]
 /// Reads the contents of the provided directories.
-async fn read_music_folders(state: State<'_, Watcher>, app_handle: AppHandle, music_folder_paths_str: String, blacklist_folder_paths_str: String, max_length: u64) -> Result<String, ()> {
+async fn read_music_folders(state: State<'_, Watcher>, scan_state: State<'_, ScanState>, art_cache: State<'_, ArtCache>, app_handle: AppHandle, music_folder_paths_str: String, blacklist_folder_paths_str: String, max_length: u64, scan_rules_str: Option<String>) -> Result<String, ()> {
   let music_folder_paths: Vec<String> = serde_json::from_str(&music_folder_paths_str).expect("Couldn't deserialize music folders array.");
   let blacklist_folder_paths: Vec<String> = serde_json::from_str(&blacklist_folder_paths_str).expect("Couldn't deserialize blacklist folders array.");
+  let scan_rules: ScanRules = scan_rules_str.map(| rules_str | serde_json::from_str(&rules_str).expect("Couldn't deserialize scan rules.")).unwrap_or_default();
+
+  let (rules, invalid_patterns) = CompiledScanRules::new(&scan_rules, &blacklist_folder_paths);
+
+  for invalid_pattern in invalid_patterns {
+    logger::log(&app_handle, format!("Ignoring invalid scan rule {}", invalid_pattern).as_str(), 1);
+  }
 
   let (sender, receiver) = channel();
 
@@ -96,20 +366,50 @@ async fn read_music_folders(state: State<'_, Watcher>, app_handle: AppHandle, mu
     add_path_to_scope(app_handle.clone(), music_folder.clone()).await;
   }
 
-  let _ = state.update(music_folder_paths.clone(), blacklist_folder_paths.clone());
+  let _ = state.update(music_folder_paths.clone(), rules.clone());
+
+  let tracker = scan_state.start(app_handle.clone());
 
-  let entries: Vec<Value> = music_folder_paths.par_iter().filter(| folder | !blacklist_folder_paths.contains(&folder)).map_with(sender, | log_sender, music_folder | {
+  let file_paths: Vec<PathBuf> = music_folder_paths.par_iter().filter(| folder | !blacklist_folder_paths.contains(&folder)).map_with(sender, | log_sender, music_folder | {
     let folder_path: PathBuf = PathBuf::from(&music_folder);
 
-    let folder_entries = read_music_folder(&app_handle, log_sender, folder_path, &blacklist_folder_paths, max_length);
-    return folder_entries;
+    return find_music_files(log_sender, &tracker, &rules, folder_path);
   }).flatten().collect();
   
   receiver.iter().for_each(| log: String | {
     logger::log(&app_handle, &log, 2);
   });
 
-  return Ok(serde_json::to_string(&entries).expect("Can't serialize entries to string."));
+  let mut index = LibraryIndex::load(&app_handle);
+  let diff = index.refresh(&app_handle, &tracker, &rules, &file_paths);
+  index.save(&app_handle);
+  art_cache.save(&app_handle);
+
+  let cancelled = tracker.is_cancelled();
+
+  if cancelled {
+    logger::log(&app_handle, format!("Library scan was cancelled. Added: {}, Changed: {}.", diff.added.len(), diff.changed.len()).as_str(), 1);
+  } else {
+    logger::log(&app_handle, format!("Library scan finished. Added: {}, Changed: {}, Removed: {}.", diff.added.len(), diff.changed.len(), diff.removed.len()).as_str(), 0);
+  }
+
+  let mut errors = tracker.take_errors();
+  errors.append(&mut index.errors(&file_paths));
+
+  let scan = LibraryScan {
+    entries: index.entries(&file_paths, max_length),
+    errors,
+    diff,
+    cancelled,
+  };
+
+  return Ok(serde_json::to_string(&scan).expect("Can't serialize library scan to string."));
+}
+
+#[tauri::command]
+/// Cancels the running library scan. The scan will return the songs it has read so far.
+fn cancel_library_scan(state: State<ScanState>) {
+  state.cancel();
 }
 
 #[tauri::command]
@@ -222,9 +522,15 @@ pub fn run() {
   // * The reason for defining these here is that it will survive for the lifetime of the app.
   let player: AudioPlayer = AudioPlayer::new();
   let watcher = Watcher::new();
+  let scan_state = ScanState::new();
+  let art_cache = ArtCache::new();
+  let journal = Journal::new();
 
   builder.manage(player)
     .manage(watcher)
+    .manage(scan_state)
+    .manage(art_cache)
+    .manage(journal)
     .setup(| app | {
       setup_system_tray(app);
 
@@ -286,8 +592,19 @@ pub fn run() {
       logger::log_to_file,
       add_path_to_scope,
       read_music_folders,
+      cancel_library_scan,
       delete_songs,
       write_music_files,
+      preview_music_file_changes,
+      transform_music_files,
+      preview_music_file_transforms,
+      tag_files_from_names,
+      preview_tags_from_names,
+      rename_files,
+      undo_last_operation,
+      list_operations,
+      organize_files,
+      lyrics::get_lyrics,
       toggle_dev_tools,
       image_utils::get_colors_from_image,
       image_utils::copy_album_image,
@@ -299,6 +616,7 @@ pub fn run() {
       playback::ipc::load_file,
       playback::ipc::seek,
       playback::ipc::set_volume,
+      playback::ipc::set_normalization,
       playback::ipc::resume_playback,
       playback::ipc::pause_playback,
     ])
@@ -0,0 +1,233 @@
+use std::{collections::{HashMap, HashSet}, fs::{self, create_dir_all, read_dir}, path::{Path, PathBuf}, time::UNIX_EPOCH};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::{art_cache::hash_to_hex, logger, music_readers::{read_music_file, ScanError}, scan_progress::ScanTracker, scan_rules::CompiledScanRules, song::Song};
+
+/// Bump this whenever the shape of the cached entries changes, so old indexes get rebuilt.
+const INDEX_VERSION: u32 = 12;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IndexedFile {
+  pub mtime: u64,
+  pub size: u64,
+  /// The stamp of the file's folder when it was read. See get_folder_stamp.
+  pub folder_stamp: String,
+  pub songs: Vec<Song>,
+  pub error: Option<ScanError>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct LibraryDiff {
+  pub added: Vec<String>,
+  pub changed: Vec<String>,
+  pub removed: Vec<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct LibraryIndex {
+  version: u32,
+  files: HashMap<String, IndexedFile>,
+}
+
+/// The status of a file compared to the last time the index was saved.
+enum FileStatus {
+  Unchanged,
+  Added,
+  Changed,
+}
+
+/// Gets the path of the on disk library index.
+fn get_index_path(app_handle: &AppHandle) -> PathBuf {
+  let app_data_dir = app_handle.path().app_data_dir().expect("Couldn't resolve app data dir.");
+
+  if !app_data_dir.exists() {
+    let _ = create_dir_all(&app_data_dir);
+  }
+
+  return app_data_dir.join("library_index.json");
+}
+
+/// Gets the modified time (in milliseconds) and size of a file.
+fn get_file_stamp(file_path: &Path) -> Option<(u64, u64)> {
+  let metadata = fs::metadata(file_path).ok()?;
+  let modified = metadata.modified().ok()?;
+  let mtime = modified.duration_since(UNIX_EPOCH).map(| duration | duration.as_millis() as u64).unwrap_or(0);
+
+  return Some((mtime, metadata.len()));
+}
+
+/// Gets a stamp of the sidecars in a folder, like cue sheets, lyrics, and folder covers, along with the cover names the rules look for.
+/// Songs are read with their sidecars, so they need to be read again whenever the stamp changes.
+fn get_folder_stamp(rules: &CompiledScanRules, folder_path: &Path) -> String {
+  let mut sidecars: Vec<String> = read_dir(folder_path).into_iter().flatten().flatten().filter_map(| entry | {
+    let entry_path = entry.path();
+
+    if !rules.is_sidecar_file(&entry_path) {
+      return None;
+    }
+
+    let (mtime, size) = get_file_stamp(&entry_path)?;
+    return Some(format!("{}\u{0}{}\u{0}{}", entry.file_name().to_string_lossy(), mtime, size));
+  }).collect();
+
+  sidecars.sort();
+  sidecars.extend(rules.get_cover_names().into_iter().map(| name | name.to_owned()));
+
+  return hash_to_hex(sidecars.join("\n").as_bytes());
+}
+
+impl LibraryIndex {
+  /// Loads the library index from the app data dir, or returns an empty one if it doesn't exist.
+  pub fn load(app_handle: &AppHandle) -> LibraryIndex {
+    let index_path = get_index_path(app_handle);
+
+    if !index_path.exists() {
+      return LibraryIndex::default();
+    }
+
+    let contents_res = fs::read_to_string(&index_path);
+
+    if contents_res.is_err() {
+      let err = contents_res.err().unwrap();
+      logger::log(app_handle, format!("Failed to read the library index: {}", err.to_string()).as_str(), 2);
+      return LibraryIndex::default();
+    }
+
+    let index_res = serde_json::from_str::<LibraryIndex>(&contents_res.unwrap());
+
+    if index_res.is_err() {
+      let err = index_res.err().unwrap();
+      logger::log(app_handle, format!("Failed to parse the library index, rebuilding it: {}", err.to_string()).as_str(), 1);
+      return LibraryIndex::default();
+    }
+
+    let index = index_res.unwrap();
+
+    if index.version != INDEX_VERSION {
+      logger::log(app_handle, "Library index is out of date, rebuilding it.", 0);
+      return LibraryIndex::default();
+    }
+
+    return index;
+  }
+
+  /// Saves the library index to the app data dir.
+  pub fn save(&mut self, app_handle: &AppHandle) {
+    self.version = INDEX_VERSION;
+
+    let index_path = get_index_path(app_handle);
+    let contents = serde_json::to_string(&self).expect("Can't serialize library index to string.");
+
+    let write_res = fs::write(&index_path, contents);
+
+    if write_res.is_err() {
+      let err = write_res.err().unwrap();
+      logger::log(app_handle, format!("Failed to save the library index: {}", err.to_string()).as_str(), 2);
+    }
+  }
+
+  /// Brings the index up to date with the provided files, only re-reading files that are new or have changed, including their sidecars.
+  /// If the scan gets cancelled, files that weren't read keep their previous entries and nothing is marked as removed.
+  pub fn refresh(&mut self, app_handle: &AppHandle, tracker: &ScanTracker, rules: &CompiledScanRules, file_paths: &[PathBuf]) -> LibraryDiff {
+    let folder_paths: HashSet<&Path> = file_paths.iter().filter_map(| file_path | file_path.parent()).collect();
+    let folder_stamps: HashMap<&Path, String> = folder_paths.into_par_iter().map(| folder_path | (folder_path, get_folder_stamp(rules, folder_path))).collect();
+
+    let results: Vec<(String, IndexedFile, FileStatus)> = file_paths.par_iter().filter_map(| file_path | {
+      let file_path_str = file_path.as_os_str().to_str().unwrap().to_owned();
+
+      if tracker.is_cancelled() {
+        let indexed = self.files.get(&file_path_str)?;
+        return Some((file_path_str, indexed.clone(), FileStatus::Unchanged));
+      }
+
+      let (mtime, size) = get_file_stamp(file_path)?;
+      let folder_stamp = file_path.parent().and_then(| folder_path | folder_stamps.get(folder_path)).cloned().unwrap_or_default();
+
+      let status = match self.files.get(&file_path_str) {
+        Some(indexed) if indexed.mtime == mtime && indexed.size == size && indexed.folder_stamp == folder_stamp => {
+          tracker.file_processed(file_path);
+          return Some((file_path_str, indexed.clone(), FileStatus::Unchanged));
+        }
+        Some(_) => FileStatus::Changed,
+        None => FileStatus::Added,
+      };
+
+      let read_res = read_music_file(app_handle, rules, file_path.to_owned());
+      tracker.file_processed(file_path);
+
+      let indexed = match read_res {
+        Ok(songs) => IndexedFile { mtime, size, folder_stamp, songs, error: None },
+        Err(error) => {
+          logger::log(app_handle, format!("Failed to read {}: {}", &file_path_str, &error.message).as_str(), 2);
+          IndexedFile { mtime, size, folder_stamp, songs: vec![], error: Some(error) }
+        }
+      };
+
+      return Some((file_path_str, indexed, status));
+    }).collect();
+
+    tracker.finish();
+
+    let mut diff = LibraryDiff::default();
+    let mut files: HashMap<String, IndexedFile> = HashMap::with_capacity(results.len());
+
+    for (file_path, indexed, status) in results {
+      match status {
+        FileStatus::Added => diff.added.push(file_path.clone()),
+        FileStatus::Changed => diff.changed.push(file_path.clone()),
+        FileStatus::Unchanged => {}
+      }
+
+      files.insert(file_path, indexed);
+    }
+
+    for (file_path, indexed) in self.files.drain() {
+      if !files.contains_key(&file_path) {
+        if tracker.is_cancelled() {
+          files.insert(file_path, indexed);
+        } else {
+          diff.removed.push(file_path);
+        }
+      }
+    }
+
+    self.files = files;
+
+    return diff;
+  }
+
+  /// Gets the songs for the provided files that are no longer than the max length.
+  /// Files split by a cue sheet have their tracks checked against the max length instead of the whole file.
+  pub fn entries(&self, file_paths: &[PathBuf], max_length: u64) -> Vec<Song> {
+    return file_paths.iter().filter_map(| file_path | self.files.get(file_path.as_os_str().to_str().unwrap())).flat_map(| indexed | {
+      return indexed.songs.iter().filter(| song | song.duration <= max_length).cloned();
+    }).collect();
+  }
+
+  /// Gets the songs read from a file, if it's in the index.
+  pub fn get_songs(&self, file_path: &str) -> Option<&Vec<Song>> {
+    return self.files.get(file_path).map(| indexed | &indexed.songs);
+  }
+
+  /// Moves a file's entry to its new path, so the file isn't read again on the next scan.
+  pub fn move_file(&mut self, from: &str, to: &str) {
+    if let Some(mut indexed) = self.files.remove(from) {
+      for song in indexed.songs.iter_mut() {
+        song.filePath = to.to_owned();
+      }
+
+      self.files.insert(to.to_owned(), indexed);
+    }
+  }
+
+  /// Gets the errors for the provided files that couldn't be read.
+  pub fn errors(&self, file_paths: &[PathBuf]) -> Vec<ScanError> {
+    return file_paths.iter().filter_map(| file_path | {
+      let indexed = self.files.get(file_path.as_os_str().to_str().unwrap())?;
+      return indexed.error.clone();
+    }).collect();
+  }
+}
@@ -0,0 +1,403 @@
+use std::{fs::{self, File}, path::{Path, PathBuf}};
+
+use id3::frame::TimestampFormat;
+use serde::{Deserialize, Serialize};
+use symphonia::{core::{formats::FormatOptions, io::MediaSourceStream, meta::{MetadataOptions, MetadataRevision, StandardTagKey}, probe::Hint}, default::get_probe};
+use tauri::AppHandle;
+
+use crate::{logger, tag_trailers::{read_ape_tag, ApeValue}};
+
+/// Vorbis comment keys that hold lyrics.
+pub const VORBIS_LYRICS_KEYS: [&str; 3] = ["LYRICS", "UNSYNCEDLYRICS", "SYNCEDLYRICS"];
+
+#[derive(Clone, Serialize, Deserialize)]
+#[allow(non_snake_case)]
+pub struct LyricLine {
+  pub timeMs: u64,
+  pub text: String,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Lyrics {
+  pub plain: Option<String>,
+  pub synced: Vec<LyricLine>,
+}
+
+impl Lyrics {
+  /// Checks if no lyrics were found.
+  pub fn is_empty(&self) -> bool {
+    return self.plain.is_none() && self.synced.is_empty();
+  }
+
+  /// Adds lyrics text from a tag, treating it as synced lyrics if it is LRC formatted.
+  fn add_text(&mut self, text: &str) {
+    if text.trim().is_empty() {
+      return;
+    }
+
+    let lines = parse_lrc(text);
+
+    if !lines.is_empty() {
+      if self.synced.is_empty() {
+        self.synced = lines;
+      }
+    } else if self.plain.is_none() {
+      self.plain = Some(text.trim().to_owned());
+    }
+  }
+}
+
+/// Parses an LRC timestamp, like "01:23.45", into milliseconds.
+fn parse_lrc_timestamp(timestamp: &str) -> Option<u64> {
+  let (minutes_str, seconds_str) = timestamp.split_once(':')?;
+  let minutes = minutes_str.trim().parse::<u64>().ok()?;
+
+  let (whole_str, fraction_str) = seconds_str.split_once(['.', ':']).unwrap_or((seconds_str, "0"));
+  let seconds = whole_str.trim().parse::<u64>().ok()?;
+
+  // * Fractions can be hundredths or thousandths of a second, so scale them to milliseconds.
+  let fraction_digits = fraction_str.trim();
+  let fraction = if fraction_digits.is_empty() { 0 } else { fraction_digits.parse::<u64>().ok()? };
+  let fraction_ms = match fraction_digits.len() {
+    0 => 0,
+    1 => fraction * 100,
+    2 => fraction * 10,
+    3 => fraction,
+    len => fraction / 10u64.pow(len as u32 - 3),
+  };
+
+  return Some(minutes * 60_000 + seconds * 1000 + fraction_ms);
+}
+
+/// Removes enhanced LRC word timestamps, like "<01:23.45>", from a line.
+fn strip_word_timestamps(text: &str) -> String {
+  let mut stripped = String::with_capacity(text.len());
+  let mut rest = text;
+
+  while let Some(start) = rest.find('<') {
+    let end_res = rest[start..].find('>');
+
+    if end_res.is_none() || parse_lrc_timestamp(&rest[start + 1..start + end_res.unwrap()]).is_none() {
+      stripped.push_str(&rest[..start + 1]);
+      rest = &rest[start + 1..];
+      continue;
+    }
+
+    stripped.push_str(&rest[..start]);
+    rest = &rest[start + end_res.unwrap() + 1..];
+  }
+
+  stripped.push_str(rest);
+
+  return stripped.trim().to_owned();
+}
+
+/// Parses LRC formatted lyrics into timed lines. Returns no lines if the text isn't LRC.
+pub fn parse_lrc(contents: &str) -> Vec<LyricLine> {
+  let mut lines: Vec<LyricLine> = vec![];
+  let mut offset_ms: i64 = 0;
+
+  for raw_line in contents.lines() {
+    let mut rest = raw_line.trim();
+    let mut timestamps: Vec<u64> = vec![];
+
+    while rest.starts_with('[') {
+      let end_res = rest.find(']');
+
+      if end_res.is_none() {
+        break;
+      }
+
+      let end = end_res.unwrap();
+      let tag = &rest[1..end];
+
+      if let Some(timestamp) = parse_lrc_timestamp(tag) {
+        timestamps.push(timestamp);
+      } else if let Some((key, value)) = tag.split_once(':') {
+        if key.trim().eq_ignore_ascii_case("offset") {
+          offset_ms = value.trim().parse::<i64>().unwrap_or(0);
+        }
+      }
+
+      rest = &rest[end + 1..];
+    }
+
+    let text = strip_word_timestamps(rest);
+
+    for timestamp in timestamps {
+      // * A positive offset means the lyrics should show up sooner.
+      let time_ms = (timestamp as i64 - offset_ms).max(0) as u64;
+
+      lines.push(LyricLine {
+        timeMs: time_ms,
+        text: text.clone(),
+      });
+    }
+  }
+
+  lines.sort_by_key(| line | line.timeMs);
+
+  return lines;
+}
+
+/// Checks if lyrics text is LRC formatted.
+pub fn is_lrc(text: &str) -> bool {
+  return !parse_lrc(text).is_empty();
+}
+
+/// Renders timed lines as LRC text, with timestamps in hundredths of a second.
+pub fn render_lrc(lines: &[LyricLine]) -> String {
+  return lines.iter().map(| line | {
+    let minutes = line.timeMs / 60_000;
+    let seconds = line.timeMs / 1000 % 60;
+    let hundredths = line.timeMs % 1000 / 10;
+
+    return format!("[{:02}:{:02}.{:02}]{}", minutes, seconds, hundredths, line.text);
+  }).collect::<Vec<String>>().join("\n");
+}
+
+/// Gets the path of the sidecar .lrc file for a song.
+pub fn get_sidecar_path(file_path: &Path) -> PathBuf {
+  return file_path.with_extension("lrc");
+}
+
+/// Reads the USLT and SYLT frames of an ID3 tag.
+fn read_id3_lyrics(app_handle: &AppHandle, file_path: &Path, lyrics: &mut Lyrics) {
+  let tag_res = id3::Tag::read_from_path(file_path);
+
+  if tag_res.is_err() {
+    let err = tag_res.err().unwrap();
+
+    if !matches!(err.kind, id3::ErrorKind::NoTag) {
+      logger::log(app_handle, format!("Failed to read the ID3 tag of {}: {}", file_path.to_string_lossy(), err.to_string()).as_str(), 1);
+    }
+
+    return;
+  }
+
+  let tag = tag_res.unwrap();
+
+  for synced_lyrics in tag.synchronised_lyrics() {
+    if synced_lyrics.timestamp_format != TimestampFormat::Ms {
+      // * MPEG frame timestamps depend on the stream's frame length, which isn't worth guessing.
+      continue;
+    }
+
+    if lyrics.synced.is_empty() {
+      lyrics.synced = synced_lyrics.content.iter().map(| (time_ms, text) | LyricLine {
+        timeMs: *time_ms as u64,
+        text: text.trim().to_owned(),
+      }).collect();
+    }
+  }
+
+  for unsynced_lyrics in tag.lyrics() {
+    lyrics.add_text(&unsynced_lyrics.text);
+  }
+}
+
+/// Reads the lyrics Vorbis comments of a FLAC file.
+fn read_flac_lyrics(app_handle: &AppHandle, file_path: &Path, lyrics: &mut Lyrics) {
+  let tag_res = metaflac::Tag::read_from_path(file_path);
+
+  if tag_res.is_err() {
+    let err = tag_res.err().unwrap();
+    logger::log(app_handle, format!("Failed to read the FLAC tags of {}: {}", file_path.to_string_lossy(), err.to_string()).as_str(), 1);
+    return;
+  }
+
+  let tag = tag_res.unwrap();
+
+  if let Some(comments) = tag.vorbis_comments() {
+    for key in VORBIS_LYRICS_KEYS {
+      if let Some(values) = comments.get(key) {
+        for value in values {
+          lyrics.add_text(value);
+        }
+      }
+    }
+  }
+}
+
+/// Reads the lyrics items of an APE tag, like the ones WavPack files use.
+fn read_ape_lyrics(app_handle: &AppHandle, file_path: &Path, lyrics: &mut Lyrics) {
+  let tag_res = read_ape_tag(&file_path.to_string_lossy());
+
+  if tag_res.is_err() {
+    let err = tag_res.err().unwrap();
+    logger::log(app_handle, format!("Failed to read the APE tag of {}: {}", file_path.to_string_lossy(), err.message).as_str(), 1);
+    return;
+  }
+
+  let (items, _) = tag_res.unwrap();
+
+  for (key, value) in items {
+    if let ApeValue::Text(values) = value {
+      if VORBIS_LYRICS_KEYS.iter().any(| lyrics_key | lyrics_key.eq_ignore_ascii_case(&key)) {
+        for value in values {
+          lyrics.add_text(&value);
+        }
+      }
+    }
+  }
+}
+
+/// Reads the lyrics tags of any other container symphonia can open.
+fn read_symphonia_lyrics(app_handle: &AppHandle, file_path: &Path, lyrics: &mut Lyrics) {
+  let file_res = File::open(file_path);
+
+  if file_res.is_err() {
+    let err = file_res.err().unwrap();
+    logger::log(app_handle, format!("Failed to open {}: {}", file_path.to_string_lossy(), err.to_string()).as_str(), 1);
+    return;
+  }
+
+  let mss = MediaSourceStream::new(Box::new(file_res.unwrap()), Default::default());
+
+  let mut hint = Hint::new();
+
+  if let Some(extension) = file_path.extension().and_then(| extension | extension.to_str()) {
+    hint.with_extension(extension);
+  }
+
+  let probe_res = get_probe().format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default());
+
+  if probe_res.is_err() {
+    let err = probe_res.err().unwrap();
+    logger::log(app_handle, format!("Failed to probe {} for lyrics: {}", file_path.to_string_lossy(), err.to_string()).as_str(), 1);
+    return;
+  }
+
+  let mut probed = probe_res.unwrap();
+
+  let mut revisions: Vec<MetadataRevision> = vec![];
+
+  if let Some(revision) = probed.metadata.get().as_ref().and_then(| metadata | metadata.current()) {
+    revisions.push(revision.clone());
+  }
+
+  if let Some(revision) = probed.format.metadata().skip_to_latest() {
+    revisions.push(revision.clone());
+  }
+
+  for revision in revisions {
+    for tag in revision.tags() {
+      let is_lyrics = tag.std_key == Some(StandardTagKey::Lyrics) || VORBIS_LYRICS_KEYS.iter().any(| key | key.eq_ignore_ascii_case(&tag.key));
+
+      if is_lyrics {
+        lyrics.add_text(&tag.value.to_string());
+      }
+    }
+  }
+}
+
+/// Reads the embedded and sidecar lyrics of a song.
+pub fn read_lyrics(app_handle: &AppHandle, file_path: &Path) -> Lyrics {
+  let mut lyrics = Lyrics::default();
+
+  let file_type = file_path.extension().and_then(| extension | extension.to_str()).unwrap_or("").to_ascii_lowercase();
+
+  match file_type.as_str() {
+    "mp3" => read_id3_lyrics(app_handle, file_path, &mut lyrics),
+    "flac" => read_flac_lyrics(app_handle, file_path, &mut lyrics),
+    "wv" => read_ape_lyrics(app_handle, file_path, &mut lyrics),
+    _ => read_symphonia_lyrics(app_handle, file_path, &mut lyrics),
+  }
+
+  // * Sidecar files are only used for synced lyrics when the file doesn't have its own.
+  let sidecar_path = get_sidecar_path(file_path);
+
+  if lyrics.synced.is_empty() && sidecar_path.exists() {
+    let contents_res = fs::read_to_string(&sidecar_path);
+
+    if contents_res.is_ok() {
+      lyrics.synced = parse_lrc(&contents_res.unwrap());
+    } else {
+      let err = contents_res.err().unwrap();
+      logger::log(app_handle, format!("Failed to read {}: {}", sidecar_path.to_string_lossy(), err.to_string()).as_str(), 1);
+    }
+  }
+
+  if lyrics.plain.is_none() && !lyrics.synced.is_empty() {
+    let plain_lines: Vec<&str> = lyrics.synced.iter().map(| line | line.text.as_str()).collect();
+    lyrics.plain = Some(plain_lines.join("\n"));
+  }
+
+  return lyrics;
+}
+
+#[tauri::command]
+/// Gets the lyrics of a song, or nothing if it doesn't have any.
+pub fn get_lyrics(app_handle: AppHandle, file_path: String) -> Option<Lyrics> {
+  let lyrics = read_lyrics(&app_handle, Path::new(&file_path));
+
+  if lyrics.is_empty() {
+    return None;
+  }
+
+  return Some(lyrics);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Gets the time and text of each line, since lines can't be compared directly.
+  fn to_pairs(lines: &[LyricLine]) -> Vec<(u64, &str)> {
+    return lines.iter().map(| line | (line.timeMs, line.text.as_str())).collect();
+  }
+
+  #[test]
+  fn parses_timestamp_fractions() {
+    assert_eq!(parse_lrc_timestamp("01:23.45"), Some(83_450));
+    assert_eq!(parse_lrc_timestamp("01:23.456"), Some(83_456));
+    assert_eq!(parse_lrc_timestamp("01:23.4"), Some(83_400));
+    assert_eq!(parse_lrc_timestamp("01:23.4567"), Some(83_456));
+    assert_eq!(parse_lrc_timestamp("01:23"), Some(83_000));
+    assert_eq!(parse_lrc_timestamp("01:23:45"), Some(83_450));
+    assert_eq!(parse_lrc_timestamp("123:00.00"), Some(7_380_000));
+  }
+
+  #[test]
+  fn rejects_non_timestamps() {
+    assert_eq!(parse_lrc_timestamp("ar"), None);
+    assert_eq!(parse_lrc_timestamp("ar:Artist"), None);
+    assert_eq!(parse_lrc_timestamp("01:xx.00"), None);
+  }
+
+  #[test]
+  fn parses_lines_in_time_order() {
+    let lines = parse_lrc("[ar:Artist]\n[00:05.00]Second\n[00:01.00][00:10.00]Chorus\n\n[00:03.50]");
+
+    assert_eq!(to_pairs(&lines), vec![(1_000, "Chorus"), (3_500, ""), (5_000, "Second"), (10_000, "Chorus")]);
+  }
+
+  #[test]
+  fn applies_offsets() {
+    let sooner = parse_lrc("[offset:+500]\n[00:01.00]One\n[00:00.20]Zero");
+    assert_eq!(to_pairs(&sooner), vec![(0, "Zero"), (500, "One")]);
+
+    let later = parse_lrc("[offset:-250]\n[00:01.00]One");
+    assert_eq!(to_pairs(&later), vec![(1_250, "One")]);
+  }
+
+  #[test]
+  fn strips_enhanced_word_timestamps() {
+    let lines = parse_lrc("[00:10.00]<00:10.00>Hello <00:10.50>world <not a time>");
+
+    assert_eq!(to_pairs(&lines), vec![(10_000, "Hello world <not a time>")]);
+  }
+
+  #[test]
+  fn plain_text_isnt_lrc() {
+    assert!(!is_lrc("Just some lyrics\nwith [brackets] in them"));
+    assert!(is_lrc("[00:00.00]A line"));
+  }
+
+  #[test]
+  fn renders_what_it_parses() {
+    let text = "[00:01.20]One\n[01:05.00]Two";
+
+    assert_eq!(render_lrc(&parse_lrc(text)), text);
+  }
+}
@@ -1,38 +1,58 @@
+use std::{collections::HashSet, fs::{self, canonicalize, read_dir, DirEntry, File}, io::Error, path::PathBuf, sync::mpsc::Sender};
 
-use std::{fs::{create_dir_all, read_dir, DirEntry, File}, io::{Error, Write}, path::PathBuf, sync::mpsc::Sender};
-
-use serde_json::{Map, Value};
-use symphonia::{core::{codecs::CodecRegistry, formats::{FormatOptions, FormatReader}, io::MediaSourceStream, meta::{MetadataOptions, Visual}, probe::{Hint, Probe}, units::TimeBase}, default::{formats::FlacReader, register_enabled_codecs}};
+use serde::{Deserialize, Serialize};
+use symphonia::{core::{codecs::{CodecRegistry, CODEC_TYPE_OPUS}, formats::{FormatOptions, FormatReader}, io::MediaSourceStream, meta::{MetadataOptions, MetadataRevision, StandardTagKey, Tag, Visual}, probe::{Hint, Probe}, units::TimeBase}, default::{formats::{AiffReader, FlacReader, IsoMp4Reader, OggReader, WavReader}, get_codecs, register_enabled_codecs}};
+use id3::TagLike;
 use symphonia_metadata::id3v2::Id3v2Reader;
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Manager, State};
+
+use crate::{art_cache::ArtCache, cue_sheet::{self, CueTrack}, logger, lyrics, ratings, scan_progress::ScanTracker, scan_rules::CompiledScanRules, song::{parse_flag, BitrateMode, Song}, symphonia_mock::mpa_reader::{read_mp3_stream_info, MpaReader}, tag_trailers::read_ape_tag, wavpack};
 
-use crate::{logger, symphonia_mock::mpa_reader::MpaReader};
+/// The file extensions that the scanner knows how to read.
+const SUPPORTED_EXTENSIONS: [&str; 12] = ["mp3", "flac", "ogg", "oga", "opus", "m4a", "m4b", "wav", "aif", "aiff", "aifc", "wv"];
 
-/// Formats the album name for the album image cache.
-pub fn format_album_name_for_image(album_title: String) -> String {
-  let mut file_name = album_title.to_owned();
-  file_name = file_name.replace(&[':'][..], "");
-  return file_name;
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanStage {
+  Discover,
+  Open,
+  Probe,
+  Tracks,
+  Length,
 }
 
-/// Writes the album visual to the cache folder and returns the path
-fn write_visual_to_cache(app_handle: &AppHandle, visual: &Visual, album_title: String) -> String {
-  let bundle_id: String = app_handle.config().identifier.to_owned();
-  
-  let app_cache_dir = app_handle.path().cache_dir().expect("Couldn't resolve app cache dir.");
-  let mut file_path = app_cache_dir.join(&bundle_id).join("albums");
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ScanError {
+  pub path: String,
+  pub stage: ScanStage,
+  pub message: String,
+}
 
-  if !file_path.exists() {
-    let _ = create_dir_all(file_path.to_owned());
+impl ScanError {
+  /// Creates a new ScanError for the provided path.
+  pub fn new(path: &PathBuf, stage: ScanStage, message: String) -> ScanError {
+    return ScanError {
+      path: path.to_string_lossy().to_string(),
+      stage,
+      message,
+    };
   }
+}
+
+/// Checks if the provided file extension is one the scanner can read.
+pub fn is_supported_extension(file_type: &str) -> bool {
+  return SUPPORTED_EXTENSIONS.iter().any(| extension | extension.eq_ignore_ascii_case(file_type));
+}
 
+/// Writes the album visual to the cache folder and returns the path
+fn write_visual_to_cache(app_handle: &AppHandle, visual: &Visual, album_artist: Option<&str>, album_title: &str) -> Option<String> {
   let file_type;
   let lower_case = visual.media_type.to_ascii_lowercase();
   let png_header: Vec<u8> = vec![137, 80, 78, 71, 13, 10, 26, 10, 0];
   let mut data: Vec<u8> = vec![];
 
-  if visual.media_type.contains("/") {
-    file_type = &visual.media_type[6..];
+  if let Some((_, subtype)) = visual.media_type.split_once('/') {
+    file_type = subtype;
   } else if lower_case.eq_ignore_ascii_case("PNG") {
     // ! this accounts for symphonia messing up when the MIME is just "PNG"
     file_type = &lower_case;
@@ -43,107 +63,255 @@ fn write_visual_to_cache(app_handle: &AppHandle, visual: &Visual, album_title: S
 
   data = [data, visual.data.clone().into_vec()].concat();
 
-  let name_no_quotes = (&album_title[1..album_title.len() - 1]).to_owned();
-  let mut file_name = format_album_name_for_image(name_no_quotes);
-  file_name.push_str(".");
-  file_name.push_str(file_type);
+  return write_album_art_to_cache(app_handle, &data, file_type, album_artist, album_title);
+}
 
-  file_path = file_path.join(file_name.to_owned());
+/// Writes album art to the albums cache and returns the path.
+fn write_album_art_to_cache(app_handle: &AppHandle, data: &[u8], file_type: &str, album_artist: Option<&str>, album_title: &str) -> Option<String> {
+  let art_cache: State<ArtCache> = app_handle.state();
+  return art_cache.store(app_handle, data, file_type, album_artist, album_title);
+}
 
-  if !file_path.exists() {
-    let mut dest_file: File = File::create(&file_path).expect("Dest path should have existed.");
-    let write_res = dest_file.write_all(data.as_ref());
+/// Gets the Vorbis style key for a tag, so every container produces the same raw tag keys.
+fn normalized_tag_key(tag: &Tag) -> String {
+  if let Some(std_key) = tag.std_key {
+    let key = match std_key {
+      StandardTagKey::TrackTitle => "title",
+      StandardTagKey::Album => "album",
+      StandardTagKey::Artist => "artist",
+      StandardTagKey::AlbumArtist => "albumartist",
+      StandardTagKey::Composer => "composer",
+      StandardTagKey::Genre => "genre",
+      StandardTagKey::Date => "date",
+      StandardTagKey::TrackNumber => "tracknumber",
+      StandardTagKey::TrackTotal => "tracktotal",
+      StandardTagKey::DiscNumber => "discnumber",
+      StandardTagKey::DiscTotal => "disctotal",
+      StandardTagKey::Lyrics => "lyrics",
+      StandardTagKey::Compilation => "compilation",
+      StandardTagKey::SortArtist => "artistsort",
+      StandardTagKey::SortAlbum => "albumsort",
+      StandardTagKey::SortTrackTitle => "titlesort",
+      StandardTagKey::Bpm => "bpm",
+      StandardTagKey::Comment => "comment",
+      StandardTagKey::MusicBrainzTrackId => "musicbrainz_trackid",
+      StandardTagKey::MusicBrainzRecordingId => "musicbrainz_trackid",
+      StandardTagKey::MusicBrainzAlbumId => "musicbrainz_albumid",
+      StandardTagKey::MusicBrainzArtistId => "musicbrainz_artistid",
+      StandardTagKey::ReplayGainTrackGain => "replaygain_track_gain",
+      StandardTagKey::ReplayGainTrackPeak => "replaygain_track_peak",
+      StandardTagKey::ReplayGainAlbumGain => "replaygain_album_gain",
+      StandardTagKey::ReplayGainAlbumPeak => "replaygain_album_peak",
+      _ => "",
+    };
+
+    if !key.is_empty() {
+      return key.to_owned();
+    }
 
-    if write_res.is_ok() {
-      logger::log(app_handle, format!("Writing of {} finished.", file_name).as_str(), 0);
-    } else {
-      let err = write_res.err().unwrap();
-      logger::log(app_handle, format!("Writing of {} failed with {}.", file_name, err.to_string()).as_str(), 0);
+    if tag.key.is_empty() {
+      // * MP4 atoms don't have a textual key, so fall back to the standard key's name.
+      return format!("{:?}", std_key).to_ascii_lowercase();
     }
   }
 
-  return file_path.as_mut_os_string().to_str().expect("failed to parse file path!!").to_owned();
+  return tag.key.to_ascii_lowercase();
 }
 
-/// Reads a .flac file and returns the info.
-fn read_flac(app_handle: &AppHandle, file_path: PathBuf, max_length: u64) -> Map<String, Value> {
-  let file_src = File::open(file_path.to_owned()).expect("failed to open file");
-  let file_metadata = file_src.metadata().unwrap();
-  
-  let mss = MediaSourceStream::new(Box::new(file_src), Default::default());
-  let fmt_opts: FormatOptions = Default::default();
+/// Gets the short name of the codec used by a track.
+fn get_codec_name(codec: symphonia::core::codecs::CodecType) -> String {
+  if codec == CODEC_TYPE_OPUS {
+    // * Symphonia can demux opus but has no decoder for it, so it isn't in the registry.
+    return String::from("opus");
+  }
 
-  let flac_reader_res = FlacReader::try_new(mss, &fmt_opts);
+  return get_codecs().get_codec(codec).map(| descriptor | descriptor.short_name.to_owned()).unwrap_or(String::from("unknown"));
+}
 
-  let mut entry: Map<String, Value> = Map::new();
+/// Checks if a normalized tag key holds lyrics.
+fn is_lyrics_key(key: &str) -> bool {
+  return key.eq("lyrics") || key.eq("unsyncedlyrics") || key.eq("syncedlyrics");
+}
 
-  if flac_reader_res.is_ok() {
-    let mut flac_reader = flac_reader_res.ok().unwrap();
+/// Reads the song info out of an opened format reader, along with any metadata found before the container.
+/// If the size of the audio data is known, it is used for the bitrate instead of the file size.
+fn read_song(app_handle: &AppHandle, file_path: &PathBuf, file_size: u64, audio_size: Option<u64>, reader: &mut dyn FormatReader, container_metadata: Option<MetadataRevision>) -> Result<Song, ScanError> {
+  let file_path_str = file_path.to_string_lossy().to_string();
 
-    let default_track = flac_reader.default_track().unwrap();
-    let code_params = &default_track.codec_params;
+  let default_track_res = reader.default_track();
 
-    let time_base = code_params.time_base.unwrap();
-    let num_frames = code_params.n_frames.unwrap();
-    let length: symphonia::core::units::Time = TimeBase::calc_time(&time_base, num_frames);
+  if default_track_res.is_none() {
+    return Err(ScanError::new(file_path, ScanStage::Tracks, String::from("The file has no playable tracks.")));
+  }
 
-    if length.seconds > max_length {
-      return Map::new();
-    }
+  let code_params = &default_track_res.unwrap().codec_params;
 
-    entry.insert(String::from("length"), Value::String(length.seconds.to_string()));
+  let time_base_res = code_params.time_base.or(code_params.sample_rate.map(| rate | TimeBase::new(1, rate)));
 
+  if time_base_res.is_none() || code_params.n_frames.is_none() {
+    return Err(ScanError::new(file_path, ScanStage::Length, String::from("Couldn't determine the length of the song.")));
+  }
 
-    let sample_rate = code_params.sample_rate.unwrap();
-    let bits_per_sample = code_params.bits_per_sample.unwrap();
-    let channels = code_params.channels.unwrap();
-    let bit_rate = u64::from(bits_per_sample * sample_rate) * u64::try_from(channels.count()).unwrap();
+  let length: symphonia::core::units::Time = TimeBase::calc_time(&time_base_res.unwrap(), code_params.n_frames.unwrap());
 
-    entry.insert(String::from("bitrate"), Value::String(bit_rate.to_string()));
-    entry.insert(String::from("samplerate"), Value::String(sample_rate.to_string()));
+  let mut song = Song::new(file_path_str);
 
+  song.duration = length.seconds;
+  song.size = file_size;
+  song.codec = get_codec_name(code_params.codec);
+  song.sampleRate = code_params.sample_rate.unwrap_or(0);
+  song.channels = code_params.channels.map(| channels | channels.count());
+  song.bitsPerSample = code_params.bits_per_sample;
 
-    entry.insert(String::from("size"), Value::String(file_metadata.len().to_string()));
+  let mut revisions: Vec<MetadataRevision> = vec![];
 
+  if let Some(revision) = container_metadata {
+    revisions.push(revision);
+  }
 
-    let mut metadata = flac_reader.metadata();
+  if let Some(revision) = reader.metadata().skip_to_latest() {
+    revisions.push(revision.clone());
+  }
 
-    let newest_res = metadata.skip_to_latest();
+  // * Averaging over the file works for every codec, as long as embedded art isn't counted as audio.
+  let visuals_size: u64 = revisions.iter().flat_map(| revision | revision.visuals()).map(| visual | visual.data.len() as u64).sum();
+  let audio_bytes = audio_size.unwrap_or(file_size.saturating_sub(visuals_size));
+  let seconds = length.seconds as f64 + length.frac;
 
-    if newest_res.is_some() {
-      let newest = newest_res.unwrap();
-      let tags = newest.tags();
+  song.bitrate = if seconds > 0.0 { (audio_bytes as f64 * 8.0 / seconds).round() as u64 } else { 0 };
 
-      for tag in tags {
-        if !tag.key.eq_ignore_ascii_case("lyrics") && !tag.key.eq_ignore_ascii_case("lyricist") {
-          entry.insert(tag.key.to_ascii_lowercase().to_owned(), Value::String(tag.value.to_string()));
-        }
+  apply_metadata(app_handle, file_path, &mut song, &revisions);
+
+  return Ok(song);
+}
+
+/// Applies the tags and embedded art of the provided metadata to a song.
+fn apply_metadata(app_handle: &AppHandle, file_path: &PathBuf, song: &mut Song, revisions: &[MetadataRevision]) {
+  for revision in revisions {
+    for tag in revision.tags() {
+      let key = normalized_tag_key(tag);
+
+      if is_lyrics_key(&key) {
+        song.hasLyrics = true;
+      } else if !key.eq("lyricist") {
+        song.apply_tag(key, tag);
       }
-      
-      let visuals = newest.visuals();
-      
-      let album_title = entry.get("album").unwrap().to_string();
+    }
+  }
 
-      for visual in visuals {
-        let album_art_path = write_visual_to_cache(app_handle, visual, album_title.clone());
+  // * Lyrics are only flagged here, and get read on demand so they don't bloat the library.
+  if !song.hasLyrics && lyrics::get_sidecar_path(file_path).exists() {
+    song.hasLyrics = true;
+  }
+
+  if let Some(album_title) = song.album.clone() {
+    for revision in revisions {
+      for visual in revision.visuals() {
+        let album_art_path = write_visual_to_cache(app_handle, visual, song.albumArtist.as_deref(), &album_title);
 
-        if !entry.contains_key("albumpath") {
-          entry.insert(String::from("albumpath"), Value::String(album_art_path));
+        if song.artPath.is_none() {
+          song.artPath = album_art_path;
         }
       }
     }
-  } else {
-    logger::log(app_handle, format!("Failed to parse {} with flac parser.", file_path.as_os_str().to_str().unwrap()).as_str(), 2);
   }
-  
-  return entry;
+}
+
+/// Finds the highest resolution folder cover next to a song.
+fn find_folder_cover(rules: &CompiledScanRules, file_path: &PathBuf) -> Option<PathBuf> {
+  let folder_path = file_path.parent()?;
+  let entries = read_dir(folder_path).ok()?;
+
+  let mut best_cover: Option<(PathBuf, u64)> = None;
+
+  for entry in entries.flatten() {
+    let entry_path = entry.path();
+
+    if !entry_path.is_file() || !rules.is_cover_file(&entry_path) {
+      continue;
+    }
+
+    // * Only the image header gets read here, so checking every candidate stays cheap.
+    let dimensions_res = image::image_dimensions(&entry_path);
+
+    if dimensions_res.is_err() {
+      continue;
+    }
+
+    let (width, height) = dimensions_res.unwrap();
+    let num_pixels = u64::from(width) * u64::from(height);
+
+    if best_cover.as_ref().map_or(true, | (_, best_pixels) | num_pixels > *best_pixels) {
+      best_cover = Some((entry_path, num_pixels));
+    }
+  }
+
+  return best_cover.map(| (cover_path, _) | cover_path);
+}
+
+/// Copies the folder cover of a song into the albums cache and returns its path.
+fn read_folder_cover(app_handle: &AppHandle, rules: &CompiledScanRules, file_path: &PathBuf, album_artist: Option<&str>, album_title: &str) -> Option<String> {
+  let cover_path = find_folder_cover(rules, file_path)?;
+  let data_res = fs::read(&cover_path);
+
+  if data_res.is_err() {
+    let err = data_res.err().unwrap();
+    logger::log(app_handle, format!("Failed to read {}: {}", cover_path.to_string_lossy(), err.to_string()).as_str(), 1);
+    return None;
+  }
+
+  let extension = cover_path.extension().and_then(| extension | extension.to_str()).unwrap_or("").to_ascii_lowercase();
+  let file_type = if extension == "jpg" { "jpeg".to_owned() } else { extension };
+
+  return write_album_art_to_cache(app_handle, &data_res.unwrap(), &file_type, album_artist, album_title);
+}
+
+/// Opens a music file and gets its size.
+fn open_music_file(file_path: &PathBuf) -> Result<(File, u64), ScanError> {
+  let file_src = File::open(file_path).map_err(| err | ScanError::new(file_path, ScanStage::Open, err.to_string()))?;
+  let file_metadata = file_src.metadata().map_err(| err | ScanError::new(file_path, ScanStage::Open, err.to_string()))?;
+
+  return Ok((file_src, file_metadata.len()));
+}
+
+/// Opens a file with the provided symphonia format reader, and gets the file's size.
+fn open_with_format_reader<R: FormatReader>(file_path: &PathBuf, format_name: &str) -> Result<(R, u64), ScanError> {
+  let (file_src, file_size) = open_music_file(file_path)?;
+
+  let mss = MediaSourceStream::new(Box::new(file_src), Default::default());
+  let fmt_opts: FormatOptions = Default::default();
+
+  let reader_res = R::try_new(mss, &fmt_opts);
+
+  if reader_res.is_err() {
+    let err = reader_res.err().unwrap();
+    return Err(ScanError::new(file_path, ScanStage::Probe, format!("Failed to parse with {} parser: {}", format_name, err.to_string())));
+  }
+
+  return Ok((reader_res.ok().unwrap(), file_size));
+}
+
+/// Reads a file using the provided symphonia format reader and returns the info.
+fn read_with_format_reader<R: FormatReader>(app_handle: &AppHandle, file_path: PathBuf, format_name: &str) -> Result<Song, ScanError> {
+  let (mut reader, file_size) = open_with_format_reader::<R>(&file_path, format_name)?;
+
+  return read_song(app_handle, &file_path, file_size, None, &mut reader, None);
+}
+
+/// Reads a .flac file and returns the info.
+/// Any tracks in an embedded CUESHEET block get added to the provided list.
+fn read_flac(app_handle: &AppHandle, file_path: PathBuf, embedded_cue_tracks: &mut Vec<CueTrack>) -> Result<Song, ScanError> {
+  let (mut reader, file_size) = open_with_format_reader::<FlacReader>(&file_path, "flac")?;
+
+  let song = read_song(app_handle, &file_path, file_size, None, &mut reader, None)?;
+  embedded_cue_tracks.append(&mut cue_sheet::get_embedded_cue_tracks(reader.cues(), song.sampleRate));
+
+  return Ok(song);
 }
 
 /// Reads a .mp3 file and returns the info.
-fn read_mp3(app_handle: &AppHandle, file_path: PathBuf, max_length: u64) -> Map<String, Value> {
-  let file_src = File::open(file_path.to_owned()).expect("failed to open file");
-  let file_metadata = file_src.metadata().unwrap();
-  let mut entry: Map<String, Value> = Map::new();
+fn read_mp3(app_handle: &AppHandle, file_path: PathBuf) -> Result<Song, ScanError> {
+  let (file_src, file_size) = open_music_file(&file_path)?;
   
   let mss = MediaSourceStream::new(Box::new(file_src), Default::default());
   let meta_opts: MetadataOptions = Default::default();
@@ -159,85 +327,195 @@ fn read_mp3(app_handle: &AppHandle, file_path: PathBuf, max_length: u64) -> Map<
   probe.register_all::<MpaReader>();
   probe.register_all::<Id3v2Reader>();
 
-  let mut probed = probe.format(&hint, mss, &fmt_opts, &meta_opts).expect("unsupported format");
+  let probed_res = probe.format(&hint, mss, &fmt_opts, &meta_opts);
+
+  if probed_res.is_err() {
+    let err = probed_res.err().unwrap();
+    return Err(ScanError::new(&file_path, ScanStage::Probe, format!("Failed to parse with mp3 parser: {}", err.to_string())));
+  }
+
+  let mut probed = probed_res.ok().unwrap();
+
+  let id3_metadata = probed.metadata.get().as_ref().and_then(| metadata | metadata.current()).cloned();
+
+  let stream_info_res = read_mp3_stream_info(&file_path);
+  let audio_size = stream_info_res.as_ref().ok().and_then(| stream_info | stream_info.audio_bytes);
+
+  let mut song = read_song(app_handle, &file_path, file_size, audio_size, probed.format.as_mut(), id3_metadata)?;
+  read_id3_extras(app_handle, &file_path, &mut song);
 
-  let default_track = probed.format.default_track().unwrap();
-  let code_params = &default_track.codec_params;
+  if let Ok(stream_info) = stream_info_res {
+    // * CBR files without an Info tag can't have their audio size measured, but every frame has the same bitrate.
+    if stream_info.bitrate_mode == BitrateMode::Cbr && stream_info.audio_bytes.is_none() && stream_info.header_bitrate > 0 {
+      song.bitrate = u64::from(stream_info.header_bitrate);
+    }
 
-  let time_base = code_params.time_base.unwrap();
-  let num_frames = code_params.n_frames.unwrap();
-  let length: symphonia::core::units::Time = TimeBase::calc_time(&time_base, num_frames);
+    song.bitrateMode = Some(stream_info.bitrate_mode);
 
-  if length.seconds > max_length {
-    return Map::new();
+    if stream_info.encoder.is_some() {
+      song.encoder = stream_info.encoder;
+    }
+  } else {
+    let err = stream_info_res.err().unwrap();
+    logger::log(app_handle, format!("Failed to read the stream info of {}: {}", file_path.to_string_lossy(), err.to_string()).as_str(), 1);
   }
 
-  entry.insert(String::from("length"), Value::String(length.seconds.to_string()));
+  return Ok(song);
+}
 
+/// Reads the ID3 frames symphonia skips or loses information from, like TCMP, UFID, SYLT, POPM, PCNT and comment descriptions.
+fn read_id3_extras(app_handle: &AppHandle, file_path: &PathBuf, song: &mut Song) {
+  let tag_res = id3::Tag::read_from_path(file_path);
 
-  let sample_rate = code_params.sample_rate.unwrap();
-  let bit_rate = code_params.bits_per_sample.unwrap();
+  if tag_res.is_err() {
+    let err = tag_res.err().unwrap();
 
-  entry.insert(String::from("bitrate"), Value::String(bit_rate.to_string()));
-  entry.insert(String::from("samplerate"), Value::String(sample_rate.to_string()));
+    if !matches!(err.kind, id3::ErrorKind::NoTag) {
+      logger::log(app_handle, format!("Failed to read the ID3 tag of {}: {}", file_path.to_string_lossy(), err.to_string()).as_str(), 1);
+    }
 
+    return;
+  }
 
-  let file_size = file_metadata.len();
-  entry.insert(String::from("size"), Value::String(file_size.to_string()));
-    
+  let tag = tag_res.unwrap();
 
-  let mut tags = vec![];
-  let mut visuals = vec![];
-  // if let Some(metadata_rev) = probed.format.metadata().current() {
-  //   tags = metadata_rev.tags().to_owned();
-  //   visuals = metadata_rev.visuals().to_owned();
-  // } else 
-  if let Some(metadata_rev) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
-    tags = metadata_rev.tags().to_owned();
-    visuals = metadata_rev.visuals().to_owned();
+  if let Some(compilation) = tag.get("TCMP").and_then(| frame | frame.content().text()) {
+    song.compilation = song.compilation || parse_flag(compilation);
   }
 
-  for tag in tags {
-    if !tag.key.eq_ignore_ascii_case("lyrics") {
-      entry.insert(tag.key.to_ascii_lowercase().to_owned(), Value::String(tag.value.to_string()));
-    }
+  // * Comments with a description are usually encoder data, like iTunNORM, rather than something the user wrote.
+  song.comment = tag.comments()
+    .find(| comment | comment.description.is_empty() && !comment.text.trim().is_empty())
+    .map(| comment | comment.text.trim().to_owned());
+
+  if song.musicBrainzTrackId.is_none() {
+    song.musicBrainzTrackId = tag.unique_file_identifiers()
+      .find(| ufid | ufid.owner_identifier == "http://musicbrainz.org")
+      .and_then(| ufid | String::from_utf8(ufid.identifier.clone()).ok());
   }
 
-  for visual in visuals {
-    if entry.contains_key("talb") {
-      let album_title = entry.get("talb").unwrap().to_string();
-      entry.insert(String::from("albumpath"), Value::String(write_visual_to_cache(app_handle, &visual, album_title)));
-    } else if entry.contains_key("tal") {
-      let album_title = entry.get("tal").unwrap().to_string();
-      entry.insert(String::from("albumpath"), Value::String(write_visual_to_cache(app_handle, &visual, album_title)));
-    } else {
-      // entry.insert(String::from("albumpath"), Value::String());
-      // println!("{}", write_visual_to_cache(app_handle.to_owned(), &visual, "test".to_owned()));
-    }
+  if tag.synchronised_lyrics().next().is_some() {
+    song.hasLyrics = true;
   }
-  
-  return entry;
+
+  song.rating = ratings::get_id3_rating(&tag);
+  song.playCount = ratings::get_id3_play_count(&tag);
 }
 
-/// Reads a music file and returns the info.
-fn read_music_file(app_handle: &AppHandle, file_path: PathBuf, file_type: String, max_length: u64) -> Map<String, Value> {
-  if file_type.eq_ignore_ascii_case("mp3") {
-    return read_mp3(app_handle, file_path, max_length);
-  } else {
-    return read_flac(app_handle, file_path, max_length);
+/// Reads a .ogg, .oga or .opus file and returns the info.
+fn read_ogg(app_handle: &AppHandle, file_path: PathBuf) -> Result<Song, ScanError> {
+  return read_with_format_reader::<OggReader>(app_handle, file_path, "ogg");
+}
+
+/// Reads a .m4a or .m4b file (AAC or ALAC) and returns the info.
+fn read_m4a(app_handle: &AppHandle, file_path: PathBuf) -> Result<Song, ScanError> {
+  return read_with_format_reader::<IsoMp4Reader>(app_handle, file_path, "mp4");
+}
+
+/// Reads a .wav file and returns the info.
+fn read_wav(app_handle: &AppHandle, file_path: PathBuf) -> Result<Song, ScanError> {
+  return read_with_format_reader::<WavReader>(app_handle, file_path, "wav");
+}
+
+/// Reads a .aif, .aiff or .aifc file and returns the info.
+fn read_aiff(app_handle: &AppHandle, file_path: PathBuf) -> Result<Song, ScanError> {
+  return read_with_format_reader::<AiffReader>(app_handle, file_path, "aiff");
+}
+
+/// Reads a .wv file and returns the info.
+/// Symphonia can't read WavPack, so the stream info comes from its first block and the tags from its APE tag.
+fn read_wv(app_handle: &AppHandle, file_path: PathBuf) -> Result<Song, ScanError> {
+  let (_, file_size) = open_music_file(&file_path)?;
+  let file_path_str = file_path.to_string_lossy().to_string();
+
+  let info = wavpack::read_wavpack_info(&file_path).map_err(| err | ScanError::new(&file_path, ScanStage::Probe, format!("Failed to parse with wavpack parser: {}", err.message)))?;
+
+  if info.sample_rate == 0 || info.total_samples.is_none() {
+    return Err(ScanError::new(&file_path, ScanStage::Length, String::from("Couldn't determine the length of the song.")));
   }
+
+  let (ape_items, audio_size) = read_ape_tag(&file_path_str).map_err(| err | ScanError::new(&file_path, ScanStage::Probe, format!("Failed to read the APE tag: {}", err.message)))?;
+  let seconds = info.total_samples.unwrap() as f64 / f64::from(info.sample_rate);
+
+  let mut song = Song::new(file_path_str);
+
+  song.duration = seconds as u64;
+  song.size = file_size;
+  song.codec = String::from("wavpack");
+  song.sampleRate = info.sample_rate;
+  song.channels = Some(info.channels);
+  song.bitsPerSample = Some(info.bits_per_sample);
+  song.bitrate = if seconds > 0.0 { (audio_size as f64 * 8.0 / seconds).round() as u64 } else { 0 };
+
+  apply_metadata(app_handle, &file_path, &mut song, &[wavpack::get_ape_metadata(ape_items)]);
+
+  return Ok(song);
 }
 
-/// Reads the content of the provided directory.
-pub fn read_music_folder(app_handle: &AppHandle, log_sender: &mut Sender<String>, folder_path: PathBuf, blacklist: &[String], max_length: u64) -> Vec<Value> {
+/// Reads a music file and returns its songs. Files with a cue sheet get split into a song for each of its tracks.
+/// Songs without embedded art fall back to the cover image in their folder.
+pub fn read_music_file(app_handle: &AppHandle, rules: &CompiledScanRules, file_path: PathBuf) -> Result<Vec<Song>, ScanError> {
+  let file_type = file_path.extension().and_then(| extension | extension.to_str()).unwrap_or("").to_ascii_lowercase();
+  let mut embedded_cue_tracks: Vec<CueTrack> = vec![];
+
+  let mut song = match file_type.as_str() {
+    "mp3" => read_mp3(app_handle, file_path),
+    "flac" => read_flac(app_handle, file_path, &mut embedded_cue_tracks),
+    "ogg" | "oga" | "opus" => read_ogg(app_handle, file_path),
+    "m4a" | "m4b" => read_m4a(app_handle, file_path),
+    "wav" => read_wav(app_handle, file_path),
+    "aif" | "aiff" | "aifc" => read_aiff(app_handle, file_path),
+    "wv" => read_wv(app_handle, file_path),
+    _ => Err(ScanError::new(&file_path, ScanStage::Probe, format!("Unsupported file type \"{}\".", file_type))),
+  }?;
+
+  if song.artPath.is_none() {
+    if let Some(album_title) = song.album.clone() {
+      let album_artist = song.albumArtist.clone();
+      song.artPath = read_folder_cover(app_handle, rules, &PathBuf::from(&song.filePath), album_artist.as_deref(), &album_title);
+
+      // * Other songs from the same album may have already cached its art.
+      if song.artPath.is_none() {
+        let art_cache: State<ArtCache> = app_handle.state();
+        song.artPath = art_cache.get(app_handle, album_artist.as_deref(), &album_title);
+      }
+    }
+  }
+
+  let (cue_sheet, cue_tracks) = cue_sheet::find_cue_tracks(app_handle, &song, embedded_cue_tracks);
+
+  return Ok(cue_sheet::split_song(song, cue_sheet.as_ref(), &cue_tracks));
+}
+
+/// Finds all of the music files in the provided directory, following the scan rules.
+pub fn find_music_files(log_sender: &mut Sender<String>, tracker: &ScanTracker, rules: &CompiledScanRules, folder_path: PathBuf) -> Vec<PathBuf> {
+  let mut visited: HashSet<PathBuf> = HashSet::new();
+  return find_music_files_in(log_sender, tracker, rules, folder_path, 0, &mut visited);
+}
+
+/// Recursively finds the music files in a directory at the provided depth below its music folder.
+fn find_music_files_in(log_sender: &mut Sender<String>, tracker: &ScanTracker, rules: &CompiledScanRules, folder_path: PathBuf, depth: usize, visited: &mut HashSet<PathBuf>) -> Vec<PathBuf> {
+  let mut files: Vec<PathBuf> = vec![];
+
+  if tracker.is_cancelled() {
+    return files;
+  }
+
+  // * Symlinks can point back up the tree, so track the real path of every folder that was read.
+  let canonical_path = canonicalize(&folder_path).unwrap_or(folder_path.to_owned());
+
+  if !visited.insert(canonical_path) {
+    let _ = log_sender.send(format!("Skipping {} because it was already scanned. It is likely a symlink loop.", folder_path.to_string_lossy()));
+    return files;
+  }
+
   let contents_res = read_dir(folder_path.to_owned());
-  
-  let mut entries: Vec<Value> = vec![];
 
   if contents_res.is_err() {
     let err: Error = contents_res.err().unwrap();
-    let _ = log_sender.send(format!("Encountered error while reading {}. Error: {}", folder_path.to_owned().to_str().unwrap(), err.to_string()));
-    return entries;
+    let _ = log_sender.send(format!("Encountered error while reading {}. Error: {}", folder_path.to_string_lossy(), err.to_string()));
+    tracker.report_error(ScanError::new(&folder_path, ScanStage::Discover, err.to_string()));
+    return files;
   }
 
   let contents = contents_res.ok().unwrap();
@@ -245,35 +523,42 @@ pub fn read_music_folder(app_handle: &AppHandle, log_sender: &mut Sender<String>
   let mut sub_dirs: Vec<PathBuf> = vec![];
 
   for file_entry_res in contents {
-    let file_entry: DirEntry = file_entry_res.ok().expect("File entry should have been ok.");
+    if file_entry_res.is_err() {
+      let err: Error = file_entry_res.err().unwrap();
+      tracker.report_error(ScanError::new(&folder_path, ScanStage::Discover, err.to_string()));
+      continue;
+    }
+
+    let file_entry: DirEntry = file_entry_res.ok().unwrap();
     let file_path: PathBuf = file_entry.path();
-    let file_path_str = file_path.as_os_str().to_str().unwrap().to_owned();
 
-    if file_path.is_dir() && !blacklist.contains(&file_path_str) {
-      sub_dirs.push(file_path);
+    if file_path.to_str().is_none() {
+      tracker.report_error(ScanError::new(&file_path, ScanStage::Discover, String::from("The path is not valid UTF-8.")));
+      continue;
+    }
+
+    if file_path.is_dir() {
+      if rules.allows_dir(&file_path, depth + 1) {
+        sub_dirs.push(file_path);
+      }
     } else {
-      let file_type_str_res = file_path.extension();
+      let file_type_str_res = file_path.extension().and_then(| extension | extension.to_str());
       
       if file_type_str_res.is_some() {
-        let file_type_str = file_type_str_res.unwrap().to_owned();
-        let file_type = file_type_str.into_string().ok().expect("Should have been able to convert the file extension to a String.");
+        let file_type = file_type_str_res.unwrap();
       
-        if file_type.eq_ignore_ascii_case("mp3") || file_type.eq_ignore_ascii_case("flac") {
-          let mut file_entry = read_music_file(app_handle, file_path.to_owned(), file_type, max_length);
-          
-          if !file_entry.is_empty() {
-            file_entry.insert(String::from("filename"), Value::String(file_path_str));
-            entries.push(Value::Object(file_entry));
-          }
+        if is_supported_extension(file_type) && rules.allows_file(&file_path) {
+          tracker.file_discovered(&file_path);
+          files.push(file_path);
         }
       }
     }
   }
 
   for directory_path in sub_dirs {
-    let mut folder_entries = read_music_folder(app_handle, log_sender, directory_path.to_owned(), blacklist, max_length);
-    entries.append(&mut folder_entries);
+    let mut folder_files = find_music_files_in(log_sender, tracker, rules, directory_path.to_owned(), depth + 1, visited);
+    files.append(&mut folder_files);
   }
   
-  return entries;
+  return files;
 }
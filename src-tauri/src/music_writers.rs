@@ -1,19 +1,253 @@
-use std::{fs, path::PathBuf, sync::mpsc::Sender};
-use id3::{frame::Picture, no_tag_ok, Frame, TagLike};
+use std::{fs, path::Path, str::FromStr, sync::mpsc::Sender};
+use id3::{frame::{Comment, Content, ExtendedText, Lyrics, Picture, Popularimeter, SynchronisedLyrics, SynchronisedLyricsType, TimestampFormat, UniqueFileIdentifier, Unknown}, no_tag_ok, Frame, TagLike};
 use metaflac;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
+use crate::{art_cache::hash_to_hex, file_results::{FileError, FileErrorKind}, journal::{JournalEntry, PendingOperation}, image_utils::prepare_embedded_art, lyrics::{get_sidecar_path, is_lrc, parse_lrc, render_lrc, LyricLine, VORBIS_LYRICS_KEYS}, ratings::{get_id3_play_count, get_id3_rating, parse_fmps_rating, parse_vorbis_rating, render_pcnt, round_rating, stars_to_popm, PlayCountMode, POPM_OWNER}, tag_trailers::{preview_trailers, update_trailers, TrailerMode}};
+
+/// How an edit changes a single field. Fields left out of an edit are kept, null clears them, and a value sets them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum FieldEdit<T> {
+  #[default]
+  Keep,
+  Set(T),
+  Clear,
+}
+
+impl<T> FieldEdit<T> {
+  /// Checks if the field should be left untouched.
+  pub fn is_keep(&self) -> bool {
+    return matches!(self, FieldEdit::Keep);
+  }
+
+  /// Converts the value being set. Values that convert to nothing clear the field instead.
+  fn and_then<U>(self, convert: impl FnOnce(T) -> Option<U>) -> FieldEdit<U> {
+    return match self {
+      FieldEdit::Keep => FieldEdit::Keep,
+      FieldEdit::Set(value) => convert(value).map(FieldEdit::Set).unwrap_or(FieldEdit::Clear),
+      FieldEdit::Clear => FieldEdit::Clear,
+    };
+  }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for FieldEdit<T> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    // * Missing fields never get here, they use the default of Keep instead.
+    return Ok(match Option::<T>::deserialize(deserializer)? {
+      Some(value) => FieldEdit::Set(value),
+      None => FieldEdit::Clear,
+    });
+  }
+}
+
+impl<T: Serialize> Serialize for FieldEdit<T> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    return match self {
+      FieldEdit::Set(value) => value.serialize(serializer),
+      _ => serializer.serialize_none(),
+    };
+  }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 #[allow(non_snake_case)]
 pub struct SongEditFields {
-  artPath: Option<String>,
-  title: Option<String>,
-  album: Option<String>,
-  composer: Option<String>,
-  albumArtist: Option<String>,
-  artist: Option<String>,
-  releaseYear: Option<i32>,
-  genre: Option<String>,
-  trackNumber: Option<u16>
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  artPath: FieldEdit<String>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  title: FieldEdit<String>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  album: FieldEdit<String>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  composers: FieldEdit<Vec<String>>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  albumArtist: FieldEdit<String>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  artists: FieldEdit<Vec<String>>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  releaseYear: FieldEdit<i32>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  genres: FieldEdit<Vec<String>>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  trackNumber: FieldEdit<u16>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  trackTotal: FieldEdit<u16>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  discNumber: FieldEdit<u16>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  discTotal: FieldEdit<u16>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  compilation: FieldEdit<bool>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  sortArtist: FieldEdit<String>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  sortAlbum: FieldEdit<String>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  sortTitle: FieldEdit<String>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  bpm: FieldEdit<u16>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  comment: FieldEdit<String>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  rating: FieldEdit<f32>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  lyrics: FieldEdit<String>,
+  /// LRC formatted text, like "[01:23.45]A line".
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  syncedLyrics: FieldEdit<String>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  playCount: FieldEdit<u64>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  musicBrainzTrackId: FieldEdit<String>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  musicBrainzAlbumId: FieldEdit<String>,
+  #[serde(skip_serializing_if = "FieldEdit::is_keep")]
+  musicBrainzArtistId: FieldEdit<String>,
+}
+
+/// Converts an optional value into an edit, where no value clears the field.
+fn to_edit<T>(value: Option<T>) -> FieldEdit<T> {
+  return value.map(FieldEdit::Set).unwrap_or(FieldEdit::Clear);
+}
+
+/// Parses the text value of a number field into an edit.
+fn parse_edit<T: FromStr>(field: &str, value: Option<String>) -> Result<FieldEdit<T>, FileError> {
+  if value.is_none() {
+    return Ok(FieldEdit::Clear);
+  }
+
+  let text = value.unwrap();
+  let parsed = text.trim().parse::<T>();
+
+  if parsed.is_err() {
+    return Err(FileError::new(FileErrorKind::Other, format!("\"{}\" isn't a valid value for {}.", text, field)));
+  }
+
+  return Ok(FieldEdit::Set(parsed.ok().unwrap()));
+}
+
+impl SongEditFields {
+  /// Sets a field from its text value, like the ones previews show. No value clears the field.
+  /// Multi-valued fields are split on semicolons.
+  pub fn set_from_text(&mut self, field: &str, value: Option<String>) -> Result<(), FileError> {
+    let values = value.clone().map(| text | text.split(';').map(| value | value.trim().to_owned()).collect::<Vec<String>>());
+
+    match field {
+      "title" => self.title = to_edit(value),
+      "album" => self.album = to_edit(value),
+      "composers" => self.composers = to_edit(values),
+      "albumArtist" => self.albumArtist = to_edit(value),
+      "artists" => self.artists = to_edit(values),
+      "releaseYear" => self.releaseYear = parse_edit(field, value)?,
+      "genres" => self.genres = to_edit(values),
+      "trackNumber" => self.trackNumber = parse_edit(field, value)?,
+      "trackTotal" => self.trackTotal = parse_edit(field, value)?,
+      "discNumber" => self.discNumber = parse_edit(field, value)?,
+      "discTotal" => self.discTotal = parse_edit(field, value)?,
+      "compilation" => self.compilation = to_edit(value.map(| text | matches!(text.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))),
+      "sortArtist" => self.sortArtist = to_edit(value),
+      "sortAlbum" => self.sortAlbum = to_edit(value),
+      "sortTitle" => self.sortTitle = to_edit(value),
+      "bpm" => self.bpm = parse_edit(field, value)?,
+      "comment" => self.comment = to_edit(value),
+      "rating" => self.rating = parse_edit(field, value)?,
+      "playCount" => self.playCount = parse_edit(field, value)?,
+      "lyrics" => self.lyrics = to_edit(value),
+      "syncedLyrics" => self.syncedLyrics = to_edit(value),
+      "musicBrainzTrackId" => self.musicBrainzTrackId = to_edit(value),
+      "musicBrainzAlbumId" => self.musicBrainzAlbumId = to_edit(value),
+      "musicBrainzArtistId" => self.musicBrainzArtistId = to_edit(value),
+      _ => return Err(FileError::new(FileErrorKind::Other, format!("The field {} can't be edited.", field))),
+    }
+
+    return Ok(());
+  }
+
+  /// Gets the names of the edited fields that describe a single track rather than the whole file.
+  /// Cue sheet tracks share their file, so writing these would change every track in it.
+  pub fn get_track_edits(&self) -> Vec<&'static str> {
+    let fields = [
+      ("title", self.title.is_keep()),
+      ("artists", self.artists.is_keep()),
+      ("composers", self.composers.is_keep()),
+      ("trackNumber", self.trackNumber.is_keep()),
+      ("trackTotal", self.trackTotal.is_keep()),
+      ("sortTitle", self.sortTitle.is_keep()),
+      ("bpm", self.bpm.is_keep()),
+      ("rating", self.rating.is_keep()),
+      ("lyrics", self.lyrics.is_keep()),
+      ("syncedLyrics", self.syncedLyrics.is_keep()),
+      ("playCount", self.playCount.is_keep()),
+      ("musicBrainzTrackId", self.musicBrainzTrackId.is_keep()),
+    ];
+
+    return fields.into_iter().filter(| (_, is_keep) | !is_keep).map(| (field, _) | field).collect();
+  }
+}
+
+/// The owner of the UFID frame MusicBrainz uses for recording ids.
+const MUSICBRAINZ_UFID_OWNER: &str = "http://musicbrainz.org";
+
+/// Trims a text field, where an empty value means it should be cleared.
+fn text_edit(edit: FieldEdit<String>) -> FieldEdit<String> {
+  return edit.and_then(| text | {
+    let trimmed = text.trim();
+    return if trimmed.is_empty() { None } else { Some(trimmed.to_owned()) };
+  });
+}
+
+/// Converts a year edit, treating unknown years (0 or less) as a clear.
+fn year_edit(edit: FieldEdit<i32>) -> FieldEdit<i32> {
+  return edit.and_then(| year | if year <= 0 { None } else { Some(year) });
+}
+
+/// Converts a number field into a text field, where 0 means it should be cleared.
+fn number_edit(edit: FieldEdit<u16>) -> FieldEdit<String> {
+  return edit.and_then(| number | if number == 0 { None } else { Some(number.to_string()) });
+}
+
+/// Converts a number field for ID3 frames that take numbers, where 0 means it should be cleared.
+fn nonzero_edit(edit: FieldEdit<u16>) -> FieldEdit<u32> {
+  return edit.and_then(| number | if number == 0 { None } else { Some(u32::from(number)) });
+}
+
+/// Converts a flag field into a text field, where false means it should be cleared.
+fn flag_edit(edit: FieldEdit<bool>) -> FieldEdit<String> {
+  return edit.and_then(| flag | if flag { Some(String::from("1")) } else { None });
+}
+
+/// Rounds a rating to the nearest half star, where no stars means it should be cleared.
+fn rating_edit(edit: FieldEdit<f32>) -> FieldEdit<f32> {
+  return edit.and_then(| stars | {
+    let rounded = round_rating(stars);
+    return if !stars.is_finite() || rounded == 0.0 { None } else { Some(rounded) };
+  });
+}
+
+/// Parses a synced lyrics edit, where empty text means it should be cleared.
+fn synced_lyrics_edit(edit: FieldEdit<String>) -> Result<FieldEdit<Vec<LyricLine>>, FileError> {
+  let trimmed_edit = text_edit(edit);
+
+  if let FieldEdit::Set(text) = &trimmed_edit {
+    if !is_lrc(text) {
+      return Err(FileError::new(FileErrorKind::Other, String::from("The synced lyrics aren't LRC formatted.")));
+    }
+  }
+
+  return Ok(trimmed_edit.and_then(| text | Some(parse_lrc(&text))));
+}
+
+/// Trims the values of a multi-valued field, where no non empty values means it should be cleared.
+fn values_edit(edit: FieldEdit<Vec<String>>) -> FieldEdit<Vec<String>> {
+  return edit.and_then(| values | {
+    let filtered: Vec<String> = values.into_iter()
+      .map(| value | value.trim().to_owned())
+      .filter(| value | !value.is_empty())
+      .collect();
+
+    return if filtered.is_empty() { None } else { Some(filtered) };
+  });
 }
 
 /// Sets a flac meta tag.
@@ -21,251 +255,857 @@ fn set_flac_tag(tag: &mut metaflac::Tag, key: &str, value: &str) {
   tag.vorbis_comments_mut().set(key, vec![value]);
 }
 
+/// Sets a flac meta tag to multiple values, writing one comment per value.
+fn set_flac_tag_values(tag: &mut metaflac::Tag, key: &str, values: Vec<String>) {
+  tag.vorbis_comments_mut().set(key, values);
+}
+
 /// Removes a flag meta tag.
 fn remove_flac_tag(tag: &mut metaflac::Tag, key: &str) {
   tag.vorbis_comments_mut().comments.remove(key);
 }
 
-// Writes changes to a flac file.
-fn write_flac_file(log_sender: &mut Sender<String>, file_path: String, edited_fields: SongEditFields) -> bool {
-  let tag_res = metaflac::Tag::read_from_path(file_path.clone());
+/// Removes either the plain or the LRC formatted values of the lyrics flac meta tags, keeping the other kind.
+fn remove_flac_lyrics(tag: &mut metaflac::Tag, synced: bool) {
+  let comments = &mut tag.vorbis_comments_mut().comments;
 
-  if tag_res.is_err() {
-    let err = tag_res.err().unwrap();
-    let _ = log_sender.send(format!("Failed to open {}: {}", &file_path, err.to_string()));
-    return false;
+  for key in VORBIS_LYRICS_KEYS {
+    if let Some(values) = comments.get_mut(key) {
+      values.retain(| value | is_lrc(value) != synced);
+
+      if values.is_empty() {
+        comments.remove(key);
+      }
+    }
   }
-  let mut tag = tag_res.unwrap();
+}
 
-  if edited_fields.artPath.is_some() {
-    let image_path = edited_fields.artPath.unwrap();
-    let data_res = fs::read(image_path.clone());
+/// Sets or removes a flac meta tag, unless the field is being kept.
+fn apply_flac_field(tag: &mut metaflac::Tag, key: &str, edit: FieldEdit<String>) {
+  match edit {
+    FieldEdit::Keep => {}
+    FieldEdit::Set(text) => set_flac_tag(tag, key, &text),
+    FieldEdit::Clear => remove_flac_tag(tag, key),
+  }
+}
+
+/// Sets or removes a multi-valued flac meta tag, unless the field is being kept.
+fn apply_flac_values_field(tag: &mut metaflac::Tag, key: &str, edit: FieldEdit<Vec<String>>) {
+  match edit {
+    FieldEdit::Keep => {}
+    FieldEdit::Set(values) => set_flac_tag_values(tag, key, values),
+    FieldEdit::Clear => remove_flac_tag(tag, key),
+  }
+}
 
-    if data_res.is_err() {
-      let err = data_res.err().unwrap();
-      let _ = log_sender.send(format!("Failed to read {}: {}", &file_path, err.to_string()));
-      return false;
+/// Sets or removes an ID3 text frame, unless the field is being kept.
+fn apply_id3_text_field(tag: &mut id3::Tag, id: &str, edit: FieldEdit<String>) {
+  match edit {
+    FieldEdit::Keep => {}
+    FieldEdit::Set(text) => tag.set_text(id, text),
+    FieldEdit::Clear => {
+      tag.remove(id);
     }
+  }
+}
 
-    let data = data_res.ok().unwrap();
+/// Sets or removes a multi-valued ID3 text frame, unless the field is being kept.
+fn apply_id3_values_field(tag: &mut id3::Tag, id: &str, edit: FieldEdit<Vec<String>>) {
+  match edit {
+    FieldEdit::Keep => {}
+    FieldEdit::Set(values) => tag.set_text_values(id, values),
+    FieldEdit::Clear => {
+      tag.remove(id);
+    }
+  }
+}
 
-    if data.len() > 0 {
-      tag.remove_picture_type(metaflac::block::PictureType::CoverFront);
-      tag.remove_picture_type(metaflac::block::PictureType::Other);
-      tag.remove_picture_type(metaflac::block::PictureType::OtherIcon);
+/// Sets or removes an ID3 TXXX frame, unless the field is being kept.
+fn apply_id3_extended_field(tag: &mut id3::Tag, description: &str, edit: FieldEdit<String>) {
+  if edit.is_keep() {
+    return;
+  }
+
+  tag.remove_extended_text(Some(description), None);
 
-      let mut mime = "image/jpeg";
-      if file_path.to_ascii_lowercase().ends_with(".png") {
-        mime = "image/png";
+  if let FieldEdit::Set(text) = edit {
+    tag.add_frame(ExtendedText {
+      description: description.to_owned(),
+      value: text
+    });
+  }
+}
+
+/// Gets the POPM frames in an ID3 tag.
+fn get_popularimeters(tag: &id3::Tag) -> Vec<Popularimeter> {
+  return tag.frames().filter_map(| frame | frame.content().popularimeter()).cloned().collect();
+}
+
+/// Gets the POPM frame ratings are written to, or an empty one if the tag doesn't have it.
+fn get_own_popularimeter(tag: &id3::Tag) -> Popularimeter {
+  return get_popularimeters(tag).into_iter().find(| popularimeter | popularimeter.user == POPM_OWNER).unwrap_or(Popularimeter {
+    user: POPM_OWNER.to_owned(),
+    rating: 0,
+    counter: 0
+  });
+}
+
+/// Saves a POPM frame, removing it if it no longer holds anything.
+fn save_popularimeter(tag: &mut id3::Tag, popularimeter: Popularimeter) {
+  let other_frames: Vec<Frame> = tag.remove("POPM").into_iter()
+    .filter(| frame | frame.content().popularimeter().is_some_and(| existing | existing.user != popularimeter.user))
+    .collect();
+
+  for frame in other_frames {
+    tag.add_frame(frame);
+  }
+
+  if popularimeter.rating != 0 || popularimeter.counter != 0 {
+    tag.add_frame(popularimeter);
+  }
+}
+
+/// Sets or clears the rating in an ID3 tag, unless the field is being kept.
+fn apply_id3_rating(tag: &mut id3::Tag, edit: FieldEdit<f32>) {
+  match edit {
+    FieldEdit::Keep => {}
+    FieldEdit::Set(stars) => {
+      // * Other players' POPM frames are left alone, since ours is the one read first.
+      let mut popularimeter = get_own_popularimeter(tag);
+      popularimeter.rating = stars_to_popm(stars);
+      save_popularimeter(tag, popularimeter);
+    }
+    FieldEdit::Clear => {
+      for mut popularimeter in get_popularimeters(tag) {
+        popularimeter.rating = 0;
+        save_popularimeter(tag, popularimeter);
       }
-  
-      tag.add_picture(mime, metaflac::block::PictureType::CoverFront, data.to_owned());
     }
-  } else {
+  }
+}
+
+/// Sets or clears the play count in an ID3 tag, in the frame the settings pick.
+fn apply_id3_play_count(tag: &mut id3::Tag, edit: FieldEdit<u64>, mode: PlayCountMode) {
+  if edit.is_keep() {
+    return;
+  }
+
+  let count = if let FieldEdit::Set(count) = edit { count } else { 0 };
+
+  match mode {
+    PlayCountMode::Off => {}
+    PlayCountMode::Pcnt => {
+      tag.remove("PCNT");
+
+      if count > 0 {
+        tag.add_frame(Frame::with_content("PCNT", Content::Unknown(Unknown { data: render_pcnt(count), version: tag.version() })));
+      }
+    }
+    PlayCountMode::Popm => {
+      let mut popularimeter = get_own_popularimeter(tag);
+      popularimeter.counter = count;
+      save_popularimeter(tag, popularimeter);
+    }
+  }
+}
+
+/// Reads the image an edit sets as the artwork, along with its mime type, resizing it if the settings ask for it.
+fn read_art_edit(art_path: &FieldEdit<String>, settings: &TagWriteSettings) -> Result<FieldEdit<(String, Vec<u8>)>, FileError> {
+  let image_path = match art_path {
+    FieldEdit::Keep => return Ok(FieldEdit::Keep),
+    FieldEdit::Clear => return Ok(FieldEdit::Clear),
+    FieldEdit::Set(image_path) => image_path,
+  };
+
+  let data_res = fs::read(image_path);
+
+  if data_res.is_err() {
+    let mut err = FileError::from(data_res.err().unwrap());
+    err.message = format!("Failed to read the image {}: {}", image_path, err.message);
+    return Err(err);
+  }
+
+  let data = data_res.unwrap();
+
+  // * An empty image can't be embedded, so the current artwork is kept.
+  if data.is_empty() {
+    return Ok(FieldEdit::Keep);
+  }
+
+  let max_dimension = (settings.artMaxDimension != 0).then_some(settings.artMaxDimension);
+  let jpeg_quality = (settings.artJpegQuality != 0).then_some(settings.artJpegQuality);
+  let art_res = prepare_embedded_art(data, max_dimension, jpeg_quality);
+
+  if art_res.is_err() {
+    let mut err = art_res.err().unwrap();
+    err.message = format!("Failed to embed the image {}: {}", image_path, err.message);
+    return Err(err);
+  }
+
+  return Ok(FieldEdit::Set(art_res.unwrap()));
+}
+
+/// Applies changes to a flac tag without saving it.
+fn apply_flac_edits(tag: &mut metaflac::Tag, edited_fields: SongEditFields, settings: &TagWriteSettings) -> Result<(), FileError> {
+  let art_edit = read_art_edit(&edited_fields.artPath, settings)?;
+
+  if !art_edit.is_keep() {
     tag.remove_picture_type(metaflac::block::PictureType::CoverFront);
     tag.remove_picture_type(metaflac::block::PictureType::Other);
     tag.remove_picture_type(metaflac::block::PictureType::OtherIcon);
   }
 
-  if edited_fields.title.is_some() {
-    let title = edited_fields.title.unwrap();
-    set_flac_tag(&mut tag, "TITLE", &title);
-  } else {
-    remove_flac_tag(&mut tag, "TITLE");
+  if let FieldEdit::Set((mime, data)) = art_edit {
+    tag.add_picture(mime, metaflac::block::PictureType::CoverFront, data);
   }
-  
-  if edited_fields.album.is_some() {
-    let album = edited_fields.album.unwrap();
-    set_flac_tag(&mut tag, "ALBUM", &album);
-  } else {
-    remove_flac_tag(&mut tag, "ALBUM");
+
+  apply_flac_field(tag, "TITLE", text_edit(edited_fields.title));
+  apply_flac_field(tag, "ALBUM", text_edit(edited_fields.album));
+  apply_flac_values_field(tag, "COMPOSER", values_edit(edited_fields.composers));
+  apply_flac_field(tag, "ALBUMARTIST", text_edit(edited_fields.albumArtist));
+  apply_flac_values_field(tag, "ARTIST", values_edit(edited_fields.artists));
+
+  match year_edit(edited_fields.releaseYear) {
+    FieldEdit::Keep => {}
+    FieldEdit::Set(release_year) => set_flac_tag(tag, "YEAR", &release_year.to_string()),
+    FieldEdit::Clear => {
+      remove_flac_tag(tag, "YEAR");
+      remove_flac_tag(tag, "DATE");
+    }
   }
-  
-  if edited_fields.composer.is_some() {
-    let composer = edited_fields.composer.unwrap();
-    set_flac_tag(&mut tag, "COMPOSER", &composer);
-  } else {
-    remove_flac_tag(&mut tag, "COMPOSER");
+
+  apply_flac_values_field(tag, "GENRE", values_edit(edited_fields.genres));
+  apply_flac_field(tag, "TRACKNUMBER", number_edit(edited_fields.trackNumber));
+
+  if !edited_fields.trackTotal.is_keep() {
+    remove_flac_tag(tag, "TOTALTRACKS");
   }
-  
-  if edited_fields.albumArtist.is_some() {
-    let album_artist = edited_fields.albumArtist.unwrap();
-    set_flac_tag(&mut tag, "ALBUMARTIST", &album_artist);
-  } else {
-    remove_flac_tag(&mut tag, "ALBUMARTIST");
+  apply_flac_field(tag, "TRACKTOTAL", number_edit(edited_fields.trackTotal));
+  apply_flac_field(tag, "DISCNUMBER", number_edit(edited_fields.discNumber));
+  if !edited_fields.discTotal.is_keep() {
+    remove_flac_tag(tag, "TOTALDISCS");
   }
-  
-  if edited_fields.artist.is_some() {
-    let artist = edited_fields.artist.unwrap();
-    set_flac_tag(&mut tag, "ARTIST", &artist);
-  } else {
-    remove_flac_tag(&mut tag, "ARTIST");
-  }
-  
-  if edited_fields.releaseYear.is_some() {
-    let release_year = edited_fields.releaseYear.unwrap();
-    set_flac_tag(&mut tag, "YEAR", &release_year.to_string());
-  } else {
-    remove_flac_tag(&mut tag, "YEAR");
-    remove_flac_tag(&mut tag, "DATE");
+  apply_flac_field(tag, "DISCTOTAL", number_edit(edited_fields.discTotal));
+  apply_flac_field(tag, "COMPILATION", flag_edit(edited_fields.compilation));
+  apply_flac_field(tag, "ARTISTSORT", text_edit(edited_fields.sortArtist));
+  apply_flac_field(tag, "ALBUMSORT", text_edit(edited_fields.sortAlbum));
+  apply_flac_field(tag, "TITLESORT", text_edit(edited_fields.sortTitle));
+  apply_flac_field(tag, "BPM", number_edit(edited_fields.bpm));
+  apply_flac_field(tag, "COMMENT", text_edit(edited_fields.comment));
+
+  // * RATING is written out of 100, so it can't be mistaken for stars, and FMPS_RATING covers players that only read that.
+  let rating_edit = rating_edit(edited_fields.rating);
+  apply_flac_field(tag, "RATING", rating_edit.clone().and_then(| stars | Some(((stars * 20.0).round() as u8).to_string())));
+  apply_flac_field(tag, "FMPS_RATING", rating_edit.and_then(| stars | Some((stars / 5.0).to_string())));
+
+  // * Other taggers store both kinds of lyrics under any of the keys, so only the kind being edited is removed.
+  let lyrics_edit = text_edit(edited_fields.lyrics);
+  if !lyrics_edit.is_keep() {
+    remove_flac_lyrics(tag, false);
+
+    if let FieldEdit::Set(lyrics) = lyrics_edit {
+      tag.vorbis_comments_mut().comments.entry(String::from("LYRICS")).or_default().insert(0, lyrics);
+    }
   }
-  
-  if edited_fields.genre.is_some() {
-    let genre = edited_fields.genre.unwrap();
-    set_flac_tag(&mut tag, "GENRE", &genre);
-  } else {
-    remove_flac_tag(&mut tag, "GENRE");
+
+  let synced_edit = synced_lyrics_edit(edited_fields.syncedLyrics)?;
+  if !synced_edit.is_keep() {
+    remove_flac_lyrics(tag, true);
+
+    if let FieldEdit::Set(lines) = synced_edit {
+      set_flac_tag(tag, "SYNCEDLYRICS", &render_lrc(&lines));
+    }
   }
-  
-  if edited_fields.trackNumber.is_some() {
-    let track_number = edited_fields.trackNumber.unwrap();
-    set_flac_tag(&mut tag, "TRACKNUMBER", &track_number.to_string());
-  } else {
-    remove_flac_tag(&mut tag, "TRACKNUMBER");
+
+  if settings.playCounts != PlayCountMode::Off {
+    apply_flac_field(tag, "FMPS_PLAYCOUNT", edited_fields.playCount.and_then(| count | Some(count.to_string())));
   }
 
-  let result = tag.write_to_path(&file_path);
+  apply_flac_field(tag, "MUSICBRAINZ_TRACKID", text_edit(edited_fields.musicBrainzTrackId));
+  apply_flac_field(tag, "MUSICBRAINZ_ALBUMID", text_edit(edited_fields.musicBrainzAlbumId));
+  apply_flac_field(tag, "MUSICBRAINZ_ARTISTID", text_edit(edited_fields.musicBrainzArtistId));
 
-  if result.is_err() {
-    let err = result.err().unwrap();
-    let _ = log_sender.send(format!("Failed to save {}: {}", &file_path, err.to_string()));
-    return false;
+  return Ok(());
+}
+
+/// Applies changes to an ID3 tag without saving it.
+fn apply_id3_edits(tag: &mut id3::Tag, edited_fields: SongEditFields, settings: &TagWriteSettings) -> Result<(), FileError> {
+  let art_edit = read_art_edit(&edited_fields.artPath, settings)?;
+
+  if !art_edit.is_keep() {
+    tag.remove_picture_by_type(id3::frame::PictureType::CoverFront);
+    tag.remove_picture_by_type(id3::frame::PictureType::Other);
+    tag.remove_picture_by_type(id3::frame::PictureType::OtherIcon);
   }
 
-  return true;
-}
+  if let FieldEdit::Set((mime, data)) = art_edit {
+    tag.add_frame(Picture {
+      mime_type: mime,
+      picture_type: id3::frame::PictureType::CoverFront,
+      description: "".to_owned(),
+      data
+    });
+  }
 
-// Writes changes to a mp3 file.
-fn write_mp3_file(log_sender: &mut Sender<String>, file_path: String, edited_fields: SongEditFields) -> bool {
-  let tag_res = no_tag_ok(id3::Tag::read_from_path(file_path.clone()));
+  apply_id3_text_field(tag, "TIT2", text_edit(edited_fields.title));
+  apply_id3_text_field(tag, "TALB", text_edit(edited_fields.album));
+  apply_id3_values_field(tag, "TCOM", values_edit(edited_fields.composers));
+  apply_id3_text_field(tag, "TPE2", text_edit(edited_fields.albumArtist));
+  apply_id3_values_field(tag, "TPE1", values_edit(edited_fields.artists));
 
-  let mut tag: id3::Tag;
+  match year_edit(edited_fields.releaseYear) {
+    FieldEdit::Keep => {}
+    FieldEdit::Set(release_year) => tag.set_year(release_year),
+    FieldEdit::Clear => {
+      tag.remove_date_recorded();
+      tag.remove_year();
+    }
+  }
 
-  if tag_res.is_err() {
-    let err = tag_res.err().unwrap();
-    let _ = log_sender.send(format!("Failed to open {}: {}", &file_path, err.to_string()));
-    return false;
+  apply_id3_values_field(tag, "TCON", values_edit(edited_fields.genres));
+
+  match nonzero_edit(edited_fields.trackNumber) {
+    FieldEdit::Keep => {}
+    FieldEdit::Set(track_number) => tag.set_track(track_number),
+    FieldEdit::Clear => tag.remove_track(),
   }
 
-  let tag_opt = tag_res.unwrap();
+  match nonzero_edit(edited_fields.trackTotal) {
+    FieldEdit::Keep => {}
+    FieldEdit::Set(track_total) => tag.set_total_tracks(track_total),
+    FieldEdit::Clear => tag.remove_total_tracks(),
+  }
 
-  if tag_opt.is_some() {
-    tag = tag_opt.unwrap();
-  } else {
-    tag = id3::Tag::new();
+  match nonzero_edit(edited_fields.discNumber) {
+    FieldEdit::Keep => {}
+    FieldEdit::Set(disc_number) => tag.set_disc(disc_number),
+    FieldEdit::Clear => tag.remove_disc(),
   }
 
-  if edited_fields.artPath.is_some() {
-    let image_path = edited_fields.artPath.unwrap();
-    let data_res = fs::read(image_path.clone());
+  match nonzero_edit(edited_fields.discTotal) {
+    FieldEdit::Keep => {}
+    FieldEdit::Set(disc_total) => tag.set_total_discs(disc_total),
+    FieldEdit::Clear => tag.remove_total_discs(),
+  }
+
+  apply_id3_text_field(tag, "TCMP", flag_edit(edited_fields.compilation));
+  apply_id3_text_field(tag, "TSOP", text_edit(edited_fields.sortArtist));
+  apply_id3_text_field(tag, "TSOA", text_edit(edited_fields.sortAlbum));
+  apply_id3_text_field(tag, "TSOT", text_edit(edited_fields.sortTitle));
+  apply_id3_text_field(tag, "TBPM", number_edit(edited_fields.bpm));
+  apply_id3_rating(tag, rating_edit(edited_fields.rating));
+  apply_id3_play_count(tag, edited_fields.playCount, settings.playCounts);
+
+  let lyrics_edit = text_edit(edited_fields.lyrics);
+  if !lyrics_edit.is_keep() {
+    tag.remove_all_lyrics();
 
-    if data_res.is_err() {
-      let err = data_res.err().unwrap();
-      let _ = log_sender.send(format!("Failed to read {}: {}", &file_path, err.to_string()));
-      return false;
+    if let FieldEdit::Set(lyrics) = lyrics_edit {
+      tag.add_frame(Lyrics {
+        lang: "eng".to_owned(),
+        description: "".to_owned(),
+        text: lyrics
+      });
     }
+  }
 
-    let data = data_res.ok().unwrap();
+  let synced_edit = synced_lyrics_edit(edited_fields.syncedLyrics)?;
+  if !synced_edit.is_keep() {
+    tag.remove_all_synchronised_lyrics();
+
+    if let FieldEdit::Set(lines) = synced_edit {
+      tag.add_frame(SynchronisedLyrics {
+        lang: "eng".to_owned(),
+        timestamp_format: TimestampFormat::Ms,
+        content_type: SynchronisedLyricsType::Lyrics,
+        description: "".to_owned(),
+        content: lines.into_iter().map(| line | (line.timeMs.min(u32::MAX as u64) as u32, line.text)).collect()
+      });
+    }
+  }
 
-    if data.len() > 0 {
-      tag.remove_picture_by_type(id3::frame::PictureType::CoverFront);
-      tag.remove_picture_by_type(id3::frame::PictureType::Other);
-      tag.remove_picture_by_type(id3::frame::PictureType::OtherIcon);
+  let comment_edit = text_edit(edited_fields.comment);
+  if !comment_edit.is_keep() {
+    // * Only the comment without a description is touched, so encoder comments like iTunNORM survive.
+    tag.remove_comment(Some(""), None);
 
-      let mut mime = "image/jpeg";
-      if file_path.to_ascii_lowercase().ends_with(".png") {
-        mime = "image/png";
-      }
-  
-      tag.add_frame(Picture {
-        mime_type: mime.to_owned(),
-        picture_type: id3::frame::PictureType::CoverFront,
+    if let FieldEdit::Set(comment) = comment_edit {
+      tag.add_frame(Comment {
+        lang: "eng".to_owned(),
         description: "".to_owned(),
-        data: data.to_owned()
+        text: comment
       });
     }
-  } else {
-    tag.remove_picture_by_type(id3::frame::PictureType::CoverFront);
-    tag.remove_picture_by_type(id3::frame::PictureType::Other);
-    tag.remove_picture_by_type(id3::frame::PictureType::OtherIcon);
   }
 
-  if edited_fields.title.is_some() {
-    let title = edited_fields.title.unwrap();
-    tag.set_title(&title);
-  } else {
-    tag.remove_title();
+  let track_id_edit = text_edit(edited_fields.musicBrainzTrackId);
+  if !track_id_edit.is_keep() {
+    tag.remove_unique_file_identifier_by_owner_identifier(MUSICBRAINZ_UFID_OWNER);
+
+    if let FieldEdit::Set(track_id) = track_id_edit {
+      tag.add_frame(UniqueFileIdentifier {
+        owner_identifier: MUSICBRAINZ_UFID_OWNER.to_owned(),
+        identifier: track_id.as_bytes().to_vec()
+      });
+    }
   }
-  
-  if edited_fields.album.is_some() {
-    let album = edited_fields.album.unwrap();
-    tag.set_album(&album);
+
+  apply_id3_extended_field(tag, "MusicBrainz Album Id", text_edit(edited_fields.musicBrainzAlbumId));
+  apply_id3_extended_field(tag, "MusicBrainz Artist Id", text_edit(edited_fields.musicBrainzArtistId));
+
+  return Ok(());
+}
+
+/// Reads the ID3 tag of a music file, starting a new one if it doesn't have one.
+fn read_id3_tag(file_path: &str) -> Result<id3::Tag, FileError> {
+  let tag_opt = no_tag_ok(id3::Tag::read_from_path(file_path))?;
+  return Ok(tag_opt.unwrap_or_else(id3::Tag::new));
+}
+
+/// The ID3v2 version MP3 files are saved as.
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum Id3Version {
+  /// Keep whatever version the file already has.
+  #[default]
+  #[serde(rename = "keep")]
+  Keep,
+  /// ID3v2.3, for older players like car stereos.
+  #[serde(rename = "v2.3")]
+  V23,
+  /// ID3v2.4, which supports multiple values per frame.
+  #[serde(rename = "v2.4")]
+  V24,
+}
+
+/// How tags get saved, beyond the fields being edited.
+#[allow(non_snake_case)]
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct TagWriteSettings {
+  pub id3Version: Id3Version,
+  pub id3v1: TrailerMode,
+  pub ape: TrailerMode,
+  /// Embedded artwork larger than this, in pixels, gets downscaled. 0 keeps its size.
+  pub artMaxDimension: u32,
+  /// Recompresses embedded artwork as a JPEG of this quality, from 1 to 100. 0 keeps it as it is.
+  pub artJpegQuality: u8,
+  pub playCounts: PlayCountMode,
+  /// Also saves synced lyrics to a .lrc file next to the song.
+  pub lrcSidecar: bool,
+}
+
+/// Moves the date frames that differ between ID3v2.3 and ID3v2.4 to the ones the version uses.
+fn convert_id3_dates(tag: &mut id3::Tag, version: id3::Version) {
+  if version == id3::Version::Id3v24 {
+    if let (Some(year), None) = (tag.year(), tag.date_recorded()) {
+      tag.set_date_recorded(id3::Timestamp { year, month: None, day: None, hour: None, minute: None, second: None });
+    }
+
+    if let (Some(year), None) = (tag.get("TORY").and_then(| frame | frame.content().text()).and_then(| year | year.trim().parse().ok()), tag.original_date_released()) {
+      tag.set_original_date_released(id3::Timestamp { year, month: None, day: None, hour: None, minute: None, second: None });
+    }
+
+    for id in ["TYER", "TDAT", "TIME", "TORY"] {
+      tag.remove(id);
+    }
   } else {
-    tag.remove_album();
+    if let (None, Some(date)) = (tag.year(), tag.date_recorded()) {
+      tag.set_year(date.year);
+    }
+
+    if let (None, Some(date)) = (tag.get("TORY"), tag.original_date_released()) {
+      tag.set_text("TORY", format!("{:04}", date.year));
+    }
+
+    for id in ["TDRC", "TDOR"] {
+      tag.remove(id);
+    }
   }
-  
-  if edited_fields.composer.is_some() {
-    let composer = edited_fields.composer.unwrap();
-    tag.add_frame(Frame::text("TCOM", composer));
-  } else {
-    tag.remove("TCOM");
+}
+
+/// Gets the ID3v2 version to save a tag as.
+fn get_id3_version(tag: &id3::Tag, settings: &TagWriteSettings) -> id3::Version {
+  return match settings.id3Version {
+    Id3Version::Keep => tag.version(),
+    Id3Version::V23 => id3::Version::Id3v23,
+    Id3Version::V24 => id3::Version::Id3v24,
+  };
+}
+
+// Writes changes to a flac file.
+fn write_flac_file(log_sender: &mut Sender<String>, file_path: String, edited_fields: SongEditFields, settings: &TagWriteSettings) -> Result<(), FileError> {
+  let tag_res = metaflac::Tag::read_from_path(&file_path);
+
+  if tag_res.is_err() {
+    let err = FileError::from(tag_res.err().unwrap());
+    let _ = log_sender.send(format!("Failed to open {}: {}", &file_path, err.message));
+    return Err(err);
   }
-  
-  if edited_fields.albumArtist.is_some() {
-    let album_artist = edited_fields.albumArtist.unwrap();
-    tag.set_album_artist(&album_artist);
-  } else {
-    tag.remove_album_artist();
+
+  let mut tag = tag_res.unwrap();
+  let apply_res = apply_flac_edits(&mut tag, edited_fields, settings);
+
+  if apply_res.is_err() {
+    let err = apply_res.err().unwrap();
+    let _ = log_sender.send(format!("Failed to write {}: {}", &file_path, err.message));
+    return Err(err);
   }
-  
-  if edited_fields.artist.is_some() {
-    let artist = edited_fields.artist.unwrap();
-    tag.set_artist(&artist);
-  } else {
-    tag.remove_artist();
+
+  let result = tag.write_to_path(&file_path);
+
+  if result.is_err() {
+    let err = FileError::from(result.err().unwrap());
+    let _ = log_sender.send(format!("Failed to save {}: {}", &file_path, err.message));
+    return Err(err);
   }
-  
-  if edited_fields.releaseYear.is_some() {
-    let release_year = edited_fields.releaseYear.unwrap();
-    tag.set_year(release_year);
-  } else {
-    tag.remove_date_recorded();
-    tag.remove_year();
+
+  return Ok(());
+}
+
+// Writes changes to a mp3 file.
+fn write_mp3_file(log_sender: &mut Sender<String>, file_path: String, edited_fields: SongEditFields, settings: &TagWriteSettings) -> Result<(), FileError> {
+  let tag_res = read_id3_tag(&file_path);
+
+  if tag_res.is_err() {
+    let err = tag_res.err().unwrap();
+    let _ = log_sender.send(format!("Failed to open {}: {}", &file_path, err.message));
+    return Err(err);
   }
-  
-  if edited_fields.genre.is_some() {
-    let genre = edited_fields.genre.unwrap();
-    tag.set_genre(&genre);
-  } else {
-    tag.remove_genre();
+
+  let mut tag = tag_res.unwrap();
+  let apply_res = apply_id3_edits(&mut tag, edited_fields, settings);
+
+  if apply_res.is_err() {
+    let err = apply_res.err().unwrap();
+    let _ = log_sender.send(format!("Failed to write {}: {}", &file_path, err.message));
+    return Err(err);
   }
-  
-  if edited_fields.trackNumber.is_some() {
-    let track_number = edited_fields.trackNumber.unwrap();
-    tag.set_track(track_number.into());
-  } else {
-    tag.remove_track();
+
+  let version = get_id3_version(&tag, settings);
+
+  if settings.id3Version != Id3Version::Keep {
+    convert_id3_dates(&mut tag, version);
   }
 
-  let result = tag.write_to_path(&file_path, tag.version());
+  let result = tag.write_to_path(&file_path, version);
 
   if result.is_err() {
-    let err = result.err().unwrap();
-    let _ = log_sender.send(format!("Failed to save {}: {}", &file_path, err.to_string()));
-    return false;
+    let err = FileError::from(result.err().unwrap());
+    let _ = log_sender.send(format!("Failed to save {}: {}", &file_path, err.message));
+    return Err(err);
+  }
+
+  let trailers_res = update_trailers(&file_path, &tag, settings.id3v1, settings.ape);
+
+  if trailers_res.is_err() {
+    let err = trailers_res.err().unwrap();
+    let _ = log_sender.send(format!("Failed to update the ID3v1/APE tags of {}: {}", &file_path, err.message));
+    return Err(err);
   }
 
-  return true;
+  return Ok(());
+}
+
+/// Saves synced lyrics to the .lrc file next to a song, or removes it if they're being cleared.
+/// The old sidecar is backed up first so undoing the edit brings it back. Returns the backup's entry if the sidecar changed.
+pub fn export_lrc_sidecar(pending: &PendingOperation, file_path: &str, edited_fields: &SongEditFields) -> Result<Option<JournalEntry>, FileError> {
+  let sidecar_path = get_sidecar_path(Path::new(file_path));
+  let edit = synced_lyrics_edit(edited_fields.syncedLyrics.clone())?;
+
+  if edit.is_keep() || (matches!(edit, FieldEdit::Clear) && !sidecar_path.exists()) {
+    return Ok(None);
+  }
+
+  let backup = pending.backup_contents(&sidecar_path.to_string_lossy())?;
+
+  let export_res = match edit {
+    FieldEdit::Set(lines) => fs::write(&sidecar_path, render_lrc(&lines) + "\n"),
+    _ => fs::remove_file(&sidecar_path),
+  };
+
+  if export_res.is_err() {
+    pending.discard(&backup);
+    return Err(FileError::from(export_res.err().unwrap()));
+  }
+
+  return Ok(Some(backup));
+}
+
+/// The tag formats that can be written.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TagFormat {
+  Id3,
+  Flac,
+}
+
+/// Gets the tag format of a music file, or an error if its tags can't be written.
+pub fn get_tag_format(file_path: &str) -> Result<TagFormat, FileError> {
+  let file_type = Path::new(file_path).extension().and_then(| extension | extension.to_str()).unwrap_or("").to_ascii_lowercase();
+
+  return match file_type.as_str() {
+    "mp3" => Ok(TagFormat::Id3),
+    "flac" => Ok(TagFormat::Flac),
+    _ => Err(FileError::new(FileErrorKind::UnsupportedFormat, format!("Writing tags to .{} files isn't supported.", file_type))),
+  };
 }
 
 // Writes changes to a music file.
-pub fn write_music_file(log_sender: &mut Sender<String>, file_path: String, edited_fields: SongEditFields) -> bool {
-  let file_path_buf = PathBuf::from(&file_path);
-  let file_type = file_path_buf.extension().expect("Couldn't get file extension for song.");
-  
-  if file_type.eq_ignore_ascii_case("mp3") {
-    return write_mp3_file(log_sender, file_path, edited_fields);
-  } else {
-    return write_flac_file(log_sender, file_path, edited_fields);
+pub fn write_music_file(log_sender: &mut Sender<String>, file_path: String, edited_fields: SongEditFields, settings: &TagWriteSettings) -> Result<(), FileError> {
+  let format_res = get_tag_format(&file_path);
+
+  if format_res.is_err() {
+    let err = format_res.err().unwrap();
+    let _ = log_sender.send(format!("Failed to write {}: {}", &file_path, err.message));
+    return Err(err);
+  }
+
+  match format_res.unwrap() {
+    TagFormat::Id3 => write_mp3_file(log_sender, file_path, edited_fields, settings)?,
+    TagFormat::Flac => write_flac_file(log_sender, file_path, edited_fields, settings)?,
   }
-}
\ No newline at end of file
+
+  return Ok(());
+}
+
+/// A field whose value would change.
+#[allow(non_snake_case)]
+#[derive(Clone, Serialize)]
+pub struct FieldChange {
+  pub field: String,
+  pub oldValue: Option<String>,
+  pub newValue: Option<String>,
+}
+
+/// The changes writing would make to a single file. Files that can't be written have an error instead.
+#[derive(Clone, Serialize)]
+pub struct FilePreview {
+  pub path: String,
+  pub changes: Vec<FieldChange>,
+  pub error: Option<FileError>,
+}
+
+/// The values of the editable fields in a tag, named after their SongEditFields keys.
+pub type TagValues = Vec<(&'static str, Option<String>)>;
+
+/// Describes the binary contents of a tag, so different contents never look the same.
+fn describe_data(data: &[u8]) -> String {
+  return format!("{} bytes, {}", data.len(), &hash_to_hex(data)[..8]);
+}
+
+/// Describes an embedded picture, so different images never look the same.
+fn describe_picture(mime_type: &str, data: &[u8]) -> String {
+  return format!("{}, {} bytes, {}", mime_type, data.len(), &hash_to_hex(data)[..8]);
+}
+
+/// Gets the values of a flac meta tag, joined into one string.
+fn get_flac_value(tag: &metaflac::Tag, keys: &[&str]) -> Option<String> {
+  return keys.iter().find_map(| key | {
+    let values: Vec<&str> = tag.get_vorbis(key)?.collect();
+    return if values.is_empty() { None } else { Some(values.join("; ")) };
+  });
+}
+
+/// Reads the values of the editable fields in a flac tag.
+fn read_flac_values(tag: &metaflac::Tag) -> TagValues {
+  let artwork = tag.pictures()
+    .find(| picture | picture.picture_type == metaflac::block::PictureType::CoverFront || picture.picture_type == metaflac::block::PictureType::Other)
+    .map(| picture | describe_picture(&picture.mime_type, &picture.data));
+
+  let rating = get_flac_value(tag, &["RATING"]).and_then(| value | parse_vorbis_rating(&value))
+    .or(get_flac_value(tag, &["FMPS_RATING"]).and_then(| value | parse_fmps_rating(&value)));
+
+  let lyrics_values: Vec<&str> = VORBIS_LYRICS_KEYS.iter().filter_map(| key | tag.get_vorbis(key)).flatten().collect();
+  let plain_lyrics = lyrics_values.iter().find(| value | !is_lrc(value)).map(| value | value.to_string());
+  let synced_lyrics = lyrics_values.iter().find(| value | is_lrc(value)).map(| value | render_lrc(&parse_lrc(value)));
+
+  return vec![
+    ("artPath", artwork),
+    ("title", get_flac_value(tag, &["TITLE"])),
+    ("album", get_flac_value(tag, &["ALBUM"])),
+    ("composers", get_flac_value(tag, &["COMPOSER"])),
+    ("albumArtist", get_flac_value(tag, &["ALBUMARTIST"])),
+    ("artists", get_flac_value(tag, &["ARTIST"])),
+    ("releaseYear", get_flac_value(tag, &["YEAR", "DATE"])),
+    ("genres", get_flac_value(tag, &["GENRE"])),
+    ("trackNumber", get_flac_value(tag, &["TRACKNUMBER"])),
+    ("trackTotal", get_flac_value(tag, &["TRACKTOTAL", "TOTALTRACKS"])),
+    ("discNumber", get_flac_value(tag, &["DISCNUMBER"])),
+    ("discTotal", get_flac_value(tag, &["DISCTOTAL", "TOTALDISCS"])),
+    ("compilation", get_flac_value(tag, &["COMPILATION"])),
+    ("sortArtist", get_flac_value(tag, &["ARTISTSORT"])),
+    ("sortAlbum", get_flac_value(tag, &["ALBUMSORT"])),
+    ("sortTitle", get_flac_value(tag, &["TITLESORT"])),
+    ("bpm", get_flac_value(tag, &["BPM"])),
+    ("comment", get_flac_value(tag, &["COMMENT"])),
+    ("rating", rating.map(| stars | stars.to_string())),
+    ("lyrics", plain_lyrics),
+    ("syncedLyrics", synced_lyrics),
+    ("playCount", get_flac_value(tag, &["FMPS_PLAYCOUNT"])),
+    ("musicBrainzTrackId", get_flac_value(tag, &["MUSICBRAINZ_TRACKID"])),
+    ("musicBrainzAlbumId", get_flac_value(tag, &["MUSICBRAINZ_ALBUMID"])),
+    ("musicBrainzArtistId", get_flac_value(tag, &["MUSICBRAINZ_ARTISTID"])),
+  ];
+}
+
+/// Gets the values of an ID3 text frame, joined into one string.
+fn get_id3_text(tag: &id3::Tag, id: &str) -> Option<String> {
+  let values: Vec<&str> = tag.get(id)?.content().text_values()?.collect();
+  return if values.is_empty() { None } else { Some(values.join("; ")) };
+}
+
+/// Reads the values of the editable fields in an ID3 tag.
+fn read_id3_values(tag: &id3::Tag) -> TagValues {
+  let artwork = tag.pictures()
+    .find(| picture | picture.picture_type == id3::frame::PictureType::CoverFront || picture.picture_type == id3::frame::PictureType::Other)
+    .map(| picture | describe_picture(&picture.mime_type, &picture.data));
+
+  let comment = tag.comments().find(| comment | comment.description.is_empty()).map(| comment | comment.text.clone());
+  let track_id = tag.unique_file_identifiers()
+    .find(| identifier | identifier.owner_identifier == MUSICBRAINZ_UFID_OWNER)
+    .map(| identifier | String::from_utf8_lossy(&identifier.identifier).into_owned());
+  let synced_lyrics = tag.synchronised_lyrics().find(| lyrics | lyrics.timestamp_format == TimestampFormat::Ms).map(| lyrics | {
+    let lines: Vec<LyricLine> = lyrics.content.iter().map(| (time_ms, text) | LyricLine { timeMs: u64::from(*time_ms), text: text.clone() }).collect();
+    return render_lrc(&lines);
+  });
+  let get_extended = | description: &str | tag.extended_texts().find(| text | text.description == description).map(| text | text.value.clone());
+
+  return vec![
+    ("artPath", artwork),
+    ("title", get_id3_text(tag, "TIT2")),
+    ("album", get_id3_text(tag, "TALB")),
+    ("composers", get_id3_text(tag, "TCOM")),
+    ("albumArtist", get_id3_text(tag, "TPE2")),
+    ("artists", get_id3_text(tag, "TPE1")),
+    ("releaseYear", tag.year().or(tag.date_recorded().map(| date | date.year)).map(| year | year.to_string())),
+    ("genres", get_id3_text(tag, "TCON")),
+    ("trackNumber", tag.track().map(| track | track.to_string())),
+    ("trackTotal", tag.total_tracks().map(| total | total.to_string())),
+    ("discNumber", tag.disc().map(| disc | disc.to_string())),
+    ("discTotal", tag.total_discs().map(| total | total.to_string())),
+    ("compilation", get_id3_text(tag, "TCMP")),
+    ("sortArtist", get_id3_text(tag, "TSOP")),
+    ("sortAlbum", get_id3_text(tag, "TSOA")),
+    ("sortTitle", get_id3_text(tag, "TSOT")),
+    ("bpm", get_id3_text(tag, "TBPM")),
+    ("comment", comment),
+    ("rating", get_id3_rating(tag).map(| stars | stars.to_string())),
+    ("lyrics", tag.lyrics().next().map(| lyrics | lyrics.text.clone())),
+    ("syncedLyrics", synced_lyrics),
+    ("playCount", get_id3_play_count(tag).map(| count | count.to_string())),
+    ("musicBrainzTrackId", track_id),
+    ("musicBrainzAlbumId", get_extended("MusicBrainz Album Id")),
+    ("musicBrainzArtistId", get_extended("MusicBrainz Artist Id")),
+  ];
+}
+
+/// Gets the fields that differ between two sets of tag values.
+fn diff_tag_values(old_values: TagValues, new_values: TagValues) -> Vec<FieldChange> {
+  return old_values.into_iter().zip(new_values).filter(| ((_, old_value), (_, new_value)) | old_value != new_value).map(| ((field, old_value), (_, new_value)) | {
+    return FieldChange {
+      field: field.to_owned(),
+      oldValue: old_value,
+      newValue: new_value,
+    };
+  }).collect();
+}
+
+/// Reads the current values of the editable fields in a music file.
+pub fn read_tag_values(file_path: &str) -> Result<TagValues, FileError> {
+  return match get_tag_format(file_path)? {
+    TagFormat::Id3 => Ok(read_id3_values(&read_id3_tag(file_path)?)),
+    TagFormat::Flac => Ok(read_flac_values(&metaflac::Tag::read_from_path(file_path)?)),
+  };
+}
+
+/// Gets the change exporting synced lyrics would make to the .lrc file next to a song, if any.
+fn get_lrc_sidecar_change(file_path: &str, edit: FieldEdit<String>) -> Result<Option<FieldChange>, FileError> {
+  let sidecar_path = get_sidecar_path(Path::new(file_path));
+
+  let new_value = match synced_lyrics_edit(edit)? {
+    FieldEdit::Keep => return Ok(None),
+    FieldEdit::Set(lines) => Some(render_lrc(&lines) + "\n"),
+    FieldEdit::Clear => None,
+  };
+  let old_value = if sidecar_path.exists() { Some(fs::read_to_string(&sidecar_path)?) } else { None };
+
+  if old_value == new_value {
+    return Ok(None);
+  }
+
+  return Ok(Some(FieldChange {
+    field: String::from("lrcSidecar"),
+    oldValue: old_value,
+    newValue: new_value,
+  }));
+}
+
+/// Applies changes to a copy of a file's tags, and diffs them against the original.
+/// The steps writing takes besides editing the tag, like converting the ID3 version, updating the ID3v1/APE tags,
+/// and exporting a .lrc file, are included as well.
+fn get_tag_changes(file_path: &str, edited_fields: SongEditFields, settings: &TagWriteSettings) -> Result<Vec<FieldChange>, FileError> {
+  let format = get_tag_format(file_path)?;
+
+  if fs::metadata(file_path)?.permissions().readonly() {
+    return Err(FileError::new(FileErrorKind::PermissionDenied, String::from("The file is read-only.")));
+  }
+
+  let synced_edit = edited_fields.syncedLyrics.clone();
+
+  let mut changes = match format {
+    TagFormat::Id3 => {
+      let tag_opt = no_tag_ok(id3::Tag::read_from_path(file_path))?;
+      let old_version = tag_opt.as_ref().map(| tag | tag.version());
+      let mut tag = tag_opt.unwrap_or_else(id3::Tag::new);
+      let old_values = read_id3_values(&tag);
+
+      apply_id3_edits(&mut tag, edited_fields, settings)?;
+
+      let version = get_id3_version(&tag, settings);
+
+      if settings.id3Version != Id3Version::Keep {
+        convert_id3_dates(&mut tag, version);
+      }
+
+      let mut changes = diff_tag_values(old_values, read_id3_values(&tag));
+
+      if old_version.is_some_and(| old_version | old_version != version) {
+        changes.push(FieldChange {
+          field: String::from("id3Version"),
+          oldValue: old_version.map(| old_version | old_version.to_string()),
+          newValue: Some(version.to_string()),
+        });
+      }
+
+      for (field, old_trailer, new_trailer) in preview_trailers(file_path, &tag, settings.id3v1, settings.ape)? {
+        changes.push(FieldChange {
+          field: field.to_owned(),
+          oldValue: Some(describe_data(&old_trailer)),
+          newValue: new_trailer.map(| new_trailer | describe_data(&new_trailer)),
+        });
+      }
+
+      changes
+    }
+    TagFormat::Flac => {
+      let mut tag = metaflac::Tag::read_from_path(file_path)?;
+      let old_values = read_flac_values(&tag);
+
+      apply_flac_edits(&mut tag, edited_fields, settings)?;
+      diff_tag_values(old_values, read_flac_values(&tag))
+    }
+  };
+
+  if settings.lrcSidecar {
+    changes.extend(get_lrc_sidecar_change(file_path, synced_edit)?);
+  }
+
+  return Ok(changes);
+}
+
+/// Previews the changes writing would make to a music file, without saving anything.
+pub fn preview_music_file(file_path: String, edited_fields: SongEditFields, settings: &TagWriteSettings) -> FilePreview {
+  return match get_tag_changes(&file_path, edited_fields, settings) {
+    Ok(changes) => FilePreview { path: file_path, changes, error: None },
+    Err(err) => FilePreview { path: file_path, changes: Vec::new(), error: Some(err) },
+  };
+}
@@ -0,0 +1,553 @@
+use std::{collections::{HashMap, HashSet}, fs::{self, create_dir_all, read_dir}, path::{Path, PathBuf}};
+
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::{cue_sheet::{references_file, retarget_cue_sheet}, file_results::{FileError, FileErrorKind}, journal::{move_file, Journal, JournalEntry, OperationKind, PendingOperation}, library_index::LibraryIndex, logger, lyrics::get_sidecar_path, music_readers::is_supported_extension, scan_rules::CompiledScanRules, song::Song, watcher::Watcher};
+
+/// The fields a path template can use, written as %field%.
+const TEMPLATE_FIELDS: [&str; 9] = ["albumartist", "artist", "album", "title", "year", "track", "disc", "genre", "composer"];
+
+/// The longest a rendered file or folder name can be, in bytes. Leaves room for collision suffixes and extensions.
+const MAX_NAME_LENGTH: usize = 200;
+
+/// Names Windows reserves for devices, which can't be used as file names.
+const RESERVED_NAMES: [&str; 22] = [
+  "CON", "PRN", "AUX", "NUL",
+  "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+  "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+#[derive(Clone, Serialize)]
+pub struct FileMove {
+  pub from: String,
+  pub to: String,
+}
+
+/// The outcome of organizing a single file. Files that can't be moved have an error instead of a new path.
+#[allow(non_snake_case)]
+#[derive(Clone, Serialize)]
+pub struct OrganizeResult {
+  pub path: String,
+  pub newPath: Option<String>,
+  pub sidecars: Vec<FileMove>,
+  pub error: Option<FileError>,
+}
+
+/// Where a rendered template is placed.
+#[derive(Clone, Copy)]
+enum TemplateBase {
+  /// The template is a path relative to the file's music folder.
+  MusicFolder,
+  /// The template is a file name in the folder the file is already in.
+  CurrentFolder,
+}
+
+/// A part of a path template.
+enum TemplatePart {
+  Text(String),
+  Field(String),
+}
+
+/// Where a file and its sidecars are going.
+struct PlannedMove {
+  from: PathBuf,
+  to: PathBuf,
+  sidecars: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Converts a path to a string.
+fn path_to_string(path: &Path) -> String {
+  return path.to_string_lossy().into_owned();
+}
+
+/// Parses a path template into its folders, each made of text and fields.
+fn parse_template(template: &str) -> Result<Vec<Vec<TemplatePart>>, FileError> {
+  let mut components = Vec::new();
+
+  for component in template.split(['/', '\\']).filter(| component | !component.trim().is_empty()) {
+    let pieces: Vec<&str> = component.split('%').collect();
+
+    if pieces.len() % 2 == 0 {
+      return Err(FileError::new(FileErrorKind::Other, format!("The template has an unclosed field in \"{}\".", component)));
+    }
+
+    let mut parts = Vec::new();
+
+    for (i, piece) in pieces.into_iter().enumerate() {
+      if i % 2 == 0 {
+        parts.push(TemplatePart::Text(piece.to_owned()));
+        continue;
+      }
+
+      let field = piece.to_ascii_lowercase();
+
+      if !TEMPLATE_FIELDS.contains(&field.as_str()) {
+        return Err(FileError::new(FileErrorKind::Other, format!("The template field %{}% doesn't exist.", piece)));
+      }
+
+      parts.push(TemplatePart::Field(field));
+    }
+
+    components.push(parts);
+  }
+
+  if components.is_empty() {
+    return Err(FileError::new(FileErrorKind::Other, String::from("The template is empty.")));
+  }
+
+  return Ok(components);
+}
+
+/// Gets the value of a template field for a file. Files split by a cue sheet use their album's values.
+fn get_field_value(songs: &[Song], file_path: &Path, field: &str) -> String {
+  let song = &songs[0];
+  let is_single_song = songs.len() == 1;
+
+  let value = match field {
+    "albumartist" => song.albumArtist.clone().or(song.artists.first().cloned()),
+    "artist" => if is_single_song { song.artists.first().cloned() } else { song.albumArtist.clone() },
+    "album" => song.album.clone(),
+    "title" => if is_single_song { song.title.clone() } else { None },
+    "year" => song.year.map(| year | year.to_string()),
+    "track" => if is_single_song { song.trackNumber.map(| track | format!("{:02}", track)) } else { None },
+    "disc" => song.discNumber.map(| disc | disc.to_string()),
+    "genre" => song.genres.first().cloned(),
+    "composer" => song.composers.first().cloned(),
+    _ => None,
+  };
+
+  return value.filter(| value | !value.trim().is_empty()).unwrap_or_else(| | {
+    return match field {
+      "albumartist" | "artist" => String::from("Unknown Artist"),
+      "album" => String::from("Unknown Album"),
+      "title" => file_path.file_stem().map(| stem | stem.to_string_lossy().into_owned()).unwrap_or_default(),
+      _ => String::new(),
+    };
+  });
+}
+
+/// Makes a name safe to use as a file or folder name on this platform.
+fn sanitize_name(name: &str) -> String {
+  let invalid_chars: &[char] = if cfg!(windows) { &['<', '>', ':', '"', '/', '\\', '|', '?', '*'] } else { &['/', '\\'] };
+
+  let replaced: String = name.chars().map(| character | {
+    return if character.is_control() || invalid_chars.contains(&character) { '_' } else { character };
+  }).collect();
+
+  let collapsed = replaced.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+  // * Separators left dangling by empty fields (like "- Album" without a year) are trimmed, along with leading dots that would hide the file.
+  let mut sanitized = collapsed.trim_matches(| character: char | character == ' ' || character == '-' || character == '.').to_owned();
+
+  if sanitized.len() > MAX_NAME_LENGTH {
+    let mut end = MAX_NAME_LENGTH;
+    while !sanitized.is_char_boundary(end) {
+      end -= 1;
+    }
+
+    sanitized.truncate(end);
+    sanitized = sanitized.trim_end_matches([' ', '.']).to_owned();
+  }
+
+  if sanitized.is_empty() {
+    sanitized = String::from("Unknown");
+  }
+
+  let base_name = sanitized.split('.').next().unwrap_or("").to_ascii_uppercase();
+  if cfg!(windows) && RESERVED_NAMES.contains(&base_name.as_str()) {
+    sanitized.push('_');
+  }
+
+  return sanitized;
+}
+
+/// Renders the path template for a file, relative to its music folder.
+fn render_template(template: &[Vec<TemplatePart>], songs: &[Song], file_path: &Path) -> PathBuf {
+  let mut relative_path = PathBuf::new();
+
+  for (i, component) in template.iter().enumerate() {
+    let rendered: String = component.iter().map(| part | {
+      return match part {
+        TemplatePart::Text(text) => text.to_owned(),
+        TemplatePart::Field(field) => get_field_value(songs, file_path, field).replace(['/', '\\'], "_"),
+      };
+    }).collect();
+
+    let mut name = sanitize_name(&rendered);
+
+    if i == template.len() - 1 {
+      if let Some(extension) = file_path.extension() {
+        name = format!("{}.{}", name, extension.to_string_lossy());
+      }
+    }
+
+    relative_path.push(name);
+  }
+
+  return relative_path;
+}
+
+/// Finds the music folder a file is in. Nested music folders resolve to the innermost one.
+fn find_music_folder(music_folders: &[String], file_path: &Path) -> Option<PathBuf> {
+  return music_folders.iter()
+    .map(PathBuf::from)
+    .filter(| folder | file_path.starts_with(folder))
+    .max_by_key(| folder | folder.components().count());
+}
+
+/// Checks if two paths point to the same file, like when only their case differs on a case insensitive file system.
+fn is_same_file(first: &Path, second: &Path) -> bool {
+  return match (fs::canonicalize(first), fs::canonicalize(second)) {
+    (Ok(first), Ok(second)) => first == second,
+    _ => false,
+  };
+}
+
+/// Finds a free path for a file, adding a number to its name if the path is taken.
+fn resolve_collision(from: &Path, target: PathBuf, taken_paths: &HashSet<PathBuf>) -> PathBuf {
+  let stem = target.file_stem().map(| stem | stem.to_string_lossy().into_owned()).unwrap_or_default();
+  let extension = target.extension().map(| extension | format!(".{}", extension.to_string_lossy())).unwrap_or_default();
+
+  let mut candidate = target.clone();
+  let mut number = 2;
+
+  while taken_paths.contains(&candidate) || (candidate.exists() && !is_same_file(from, &candidate)) {
+    candidate = target.with_file_name(format!("{} ({}){}", stem, number, extension));
+    number += 1;
+  }
+
+  return candidate;
+}
+
+/// Finds the lyrics and cue sheets that belong to a file, and where they go when it moves.
+/// Cue sheets named after the file are found first, then any other cue sheet in its folder that names it.
+fn find_sidecars(from: &Path, to: &Path) -> Vec<(PathBuf, PathBuf)> {
+  let mut from_named_cue = from.as_os_str().to_owned();
+  from_named_cue.push(".cue");
+
+  let mut to_named_cue = to.as_os_str().to_owned();
+  to_named_cue.push(".cue");
+
+  let candidates = [
+    (get_sidecar_path(from), get_sidecar_path(to)),
+    (from.with_extension("cue"), to.with_extension("cue")),
+    (PathBuf::from(from_named_cue), PathBuf::from(to_named_cue)),
+  ];
+
+  let mut sidecars: Vec<(PathBuf, PathBuf)> = candidates.into_iter().filter(| (sidecar, _) | sidecar.is_file()).collect();
+
+  let (Some(from_folder), Some(to_folder), Some(file_name)) = (from.parent(), to.parent(), from.file_name()) else { return sidecars };
+  let Ok(entries) = read_dir(from_folder) else { return sidecars };
+
+  for entry in entries.flatten() {
+    let cue_path = entry.path();
+    let is_cue = cue_path.extension().is_some_and(| extension | extension.eq_ignore_ascii_case("cue"));
+
+    if !is_cue || sidecars.iter().any(| (sidecar, _) | *sidecar == cue_path) || !references_file(&cue_path, &file_name.to_string_lossy()) {
+      continue;
+    }
+
+    // * Cue sheets with their own name keep it, they only follow the file to its new folder.
+    let target = to_folder.join(cue_path.file_name().unwrap());
+    sidecars.push((cue_path, target));
+  }
+
+  return sidecars;
+}
+
+/// Plans where a file goes.
+fn plan_move(index: &LibraryIndex, music_folders: &[String], template: &[Vec<TemplatePart>], base: TemplateBase, file_path: &str, taken_paths: &HashSet<PathBuf>) -> Result<PlannedMove, FileError> {
+  let from = PathBuf::from(file_path);
+
+  if !from.is_file() {
+    return Err(FileError::new(FileErrorKind::NotFound, String::from("The file doesn't exist.")));
+  }
+
+  let songs = index.get_songs(file_path).filter(| songs | !songs.is_empty());
+
+  if songs.is_none() {
+    return Err(FileError::new(FileErrorKind::NotFound, String::from("The file hasn't been scanned into the library.")));
+  }
+
+  let music_folder = find_music_folder(music_folders, &from);
+
+  if music_folder.is_none() {
+    return Err(FileError::new(FileErrorKind::NotFound, String::from("The file isn't in a music folder.")));
+  }
+
+  let base_folder = match base {
+    TemplateBase::MusicFolder => music_folder.unwrap(),
+    TemplateBase::CurrentFolder => from.parent().map(| parent | parent.to_owned()).unwrap_or_default(),
+  };
+
+  let target = base_folder.join(render_template(template, songs.unwrap(), &from));
+  let to = if target == from { target } else { resolve_collision(&from, target, taken_paths) };
+  let sidecars = if to == from { Vec::new() } else { find_sidecars(&from, &to) };
+
+  return Ok(PlannedMove { from, to, sidecars });
+}
+
+/// Moves the folder covers along with the tracks, when every track in their folder is going to the same new folder.
+fn plan_cover_moves(rules: &CompiledScanRules, plans: &mut [(String, Result<PlannedMove, FileError>)]) {
+  let mut folder_targets: HashMap<PathBuf, (HashSet<PathBuf>, usize)> = HashMap::new();
+
+  for (i, (_, plan)) in plans.iter().enumerate() {
+    if let Ok(plan) = plan {
+      let (Some(from_folder), Some(to_folder)) = (plan.from.parent(), plan.to.parent()) else { continue };
+      let (targets, _) = folder_targets.entry(from_folder.to_owned()).or_insert((HashSet::new(), i));
+      targets.insert(to_folder.to_owned());
+    }
+  }
+
+  let moving: HashSet<PathBuf> = plans.iter().filter_map(| (_, plan) | plan.as_ref().ok().map(| plan | plan.from.clone())).collect();
+
+  for (from_folder, (targets, first_plan)) in folder_targets {
+    if targets.len() != 1 || targets.contains(&from_folder) {
+      continue;
+    }
+
+    let to_folder = targets.into_iter().next().unwrap();
+    let Ok(entries) = read_dir(&from_folder) else { continue };
+
+    let mut covers = Vec::new();
+    let mut has_other_tracks = false;
+
+    for entry in entries.flatten() {
+      let path = entry.path();
+      let is_track = path.extension().and_then(| extension | extension.to_str()).is_some_and(is_supported_extension);
+
+      if is_track && !moving.contains(&path) {
+        has_other_tracks = true;
+        break;
+      }
+
+      if rules.is_cover_file(&path) {
+        let target = to_folder.join(path.file_name().unwrap());
+
+        if !target.exists() {
+          covers.push((path, target));
+        }
+      }
+    }
+
+    // * Tracks staying behind still use the covers, so they only move once the folder is emptied.
+    if !has_other_tracks {
+      if let (_, Ok(plan)) = &mut plans[first_plan] {
+        plan.sidecars.append(&mut covers);
+      }
+    }
+  }
+}
+
+/// Converts a planned move into the result reported for it.
+fn to_result(file_path: String, plan: Result<&PlannedMove, FileError>) -> OrganizeResult {
+  return match plan {
+    Ok(plan) => OrganizeResult {
+      path: file_path,
+      newPath: Some(path_to_string(&plan.to)),
+      sidecars: plan.sidecars.iter().map(| (from, to) | FileMove { from: path_to_string(from), to: path_to_string(to) }).collect(),
+      error: None,
+    },
+    Err(err) => OrganizeResult { path: file_path, newPath: None, sidecars: Vec::new(), error: Some(err) },
+  };
+}
+
+/// Gets every path a move touches, including the folders it creates or empties, so the watcher can ignore them.
+fn get_touched_paths(music_folders: &[String], plan: &PlannedMove) -> Vec<PathBuf> {
+  let mut paths = vec![plan.from.clone(), plan.to.clone()];
+
+  for (from, to) in &plan.sidecars {
+    paths.push(from.clone());
+    paths.push(to.clone());
+  }
+
+  for path in [&plan.from, &plan.to] {
+    let music_folder = find_music_folder(music_folders, path).unwrap_or_default();
+    paths.extend(path.ancestors().skip(1).take_while(| ancestor | ancestor.starts_with(&music_folder) && *ancestor != music_folder).map(| ancestor | ancestor.to_owned()));
+  }
+
+  return paths;
+}
+
+/// Removes the folders a move left empty, up to the music folder.
+fn remove_empty_folders(music_folders: &[String], file_path: &Path) {
+  let music_folder = find_music_folder(music_folders, file_path).unwrap_or_default();
+
+  for ancestor in file_path.ancestors().skip(1).take_while(| ancestor | ancestor.starts_with(&music_folder) && *ancestor != music_folder) {
+    // * Removing a folder that still has files fails, which is where this stops.
+    if fs::remove_dir(ancestor).is_err() {
+      break;
+    }
+  }
+}
+
+/// Moves a file and its sidecars. Cue sheets get pointed at the file's new name, so their old contents are backed up first.
+/// Returns the moves that were made and the backups of the cue sheets.
+fn run_move(app_handle: &AppHandle, pending: &PendingOperation, plan: &PlannedMove) -> Result<(Vec<(PathBuf, PathBuf)>, Vec<JournalEntry>), FileError> {
+  if let Some(parent) = plan.to.parent() {
+    create_dir_all(parent)?;
+  }
+
+  move_file(&plan.from, &plan.to)?;
+
+  let mut moved = vec![(plan.from.clone(), plan.to.clone())];
+  let mut backups = Vec::new();
+
+  for (from, to) in &plan.sidecars {
+    if let Some(parent) = to.parent() {
+      let _ = create_dir_all(parent);
+    }
+
+    let move_res = move_file(from, to);
+
+    if move_res.is_err() {
+      let err = move_res.err().unwrap();
+      logger::log(app_handle, format!("Failed to move {} along with {}: {}", path_to_string(from), path_to_string(&plan.from), err.message).as_str(), 1);
+      continue;
+    }
+
+    let is_cue = to.extension().is_some_and(| extension | extension.eq_ignore_ascii_case("cue"));
+    let old_name = plan.from.file_name().unwrap().to_string_lossy();
+    let new_name = plan.to.file_name().unwrap().to_string_lossy();
+
+    if is_cue && old_name != new_name {
+      let retarget_res = pending.backup_contents(&path_to_string(to)).and_then(| backup | {
+        let res = retarget_cue_sheet(to, &old_name, &new_name);
+
+        if res.is_err() {
+          pending.discard(&backup);
+          return res;
+        }
+
+        backups.push(backup);
+        return Ok(());
+      });
+
+      if retarget_res.is_err() {
+        let err = retarget_res.err().unwrap();
+        logger::log(app_handle, format!("Failed to update the cue sheet {}: {}", path_to_string(to), err.message).as_str(), 1);
+      }
+    }
+
+    moved.push((from.clone(), to.clone()));
+  }
+
+  return Ok((moved, backups));
+}
+
+/// Moves files to match a template built from their tags, bringing their sidecar files along.
+fn move_to_template(app_handle: &AppHandle, watcher: &Watcher, journal: &Journal, template: &str, base: TemplateBase, file_paths: Vec<String>, dry_run: bool) -> Vec<OrganizeResult> {
+  let mut seen_paths = HashSet::new();
+  let file_paths: Vec<String> = file_paths.into_iter().filter(| file_path | seen_paths.insert(file_path.clone())).collect();
+
+  let template_res = parse_template(template).and_then(| template | {
+    if matches!(base, TemplateBase::CurrentFolder) && template.len() > 1 {
+      return Err(FileError::new(FileErrorKind::Other, String::from("A file name template can't contain folders.")));
+    }
+
+    return Ok(template);
+  });
+
+  if template_res.is_err() {
+    let err = template_res.err().unwrap();
+    return file_paths.into_iter().map(| file_path | to_result(file_path, Err(err.clone()))).collect();
+  }
+
+  let template = template_res.unwrap();
+  let (music_folders, rules) = watcher.get_watched();
+  let mut index = LibraryIndex::load(app_handle);
+
+  let mut taken_paths = HashSet::new();
+  let mut plans: Vec<(String, Result<PlannedMove, FileError>)> = Vec::with_capacity(file_paths.len());
+
+  for file_path in file_paths {
+    let plan = plan_move(&index, &music_folders, &template, base, &file_path, &taken_paths);
+
+    if let Ok(plan) = &plan {
+      taken_paths.insert(plan.to.clone());
+    }
+
+    plans.push((file_path, plan));
+  }
+
+  plan_cover_moves(&rules, &mut plans);
+
+  if dry_run {
+    return plans.into_iter().map(| (file_path, plan) | to_result(file_path, plan.as_ref().map_err(| err | err.clone()))).collect();
+  }
+
+  let pending_res = journal.begin(app_handle, OperationKind::Organize);
+
+  if pending_res.is_err() {
+    let err = pending_res.err().unwrap();
+    logger::log(app_handle, format!("Failed to start the undo journal, so no files were moved: {}", err.message).as_str(), 2);
+    return plans.into_iter().map(| (file_path, _) | to_result(file_path, Err(err.clone()))).collect();
+  }
+
+  let pending = pending_res.unwrap();
+  let touched_paths: Vec<PathBuf> = plans.iter().filter_map(| (_, plan) | plan.as_ref().ok()).flat_map(| plan | get_touched_paths(&music_folders, plan)).collect();
+  watcher.suppress(&touched_paths);
+
+  let mut entries = Vec::new();
+  let mut results = Vec::new();
+
+  for (file_path, plan) in plans {
+    let plan = match plan {
+      Ok(plan) => plan,
+      Err(err) => {
+        results.push(to_result(file_path, Err(err)));
+        continue;
+      }
+    };
+
+    if plan.from == plan.to {
+      results.push(to_result(file_path, Ok(&plan)));
+      continue;
+    }
+
+    let move_res = run_move(app_handle, &pending, &plan);
+
+    if move_res.is_err() {
+      let err = move_res.err().unwrap();
+      logger::log(app_handle, format!("Failed to move {}: {}", &file_path, err.message).as_str(), 2);
+      results.push(to_result(file_path, Err(err)));
+      continue;
+    }
+
+    let (moved, backups) = move_res.unwrap();
+
+    // * Cue sheets are restored before they're moved back, while they're still at the path they were backed up from.
+    entries.extend(backups);
+
+    for (from, to) in &moved {
+      entries.push(pending.record_move(&path_to_string(from), &path_to_string(to)));
+    }
+
+    index.move_file(&file_path, &path_to_string(&plan.to));
+    remove_empty_folders(&music_folders, &plan.from);
+
+    let sidecars = moved.into_iter().skip(1).collect();
+    results.push(to_result(file_path, Ok(&PlannedMove { sidecars, ..plan })));
+  }
+
+  let moved_count = results.iter().filter(| result | result.error.is_none()).count();
+  logger::log(app_handle, format!("Organized {} of {} files.", moved_count, results.len()).as_str(), 0);
+
+  index.save(app_handle);
+  journal.record(app_handle, pending, entries);
+
+  return results;
+}
+
+/// Moves and renames files to match a path template built from their tags, bringing their sidecar files along.
+/// With dry_run set nothing is moved, and the planned moves are returned instead.
+pub fn organize_files(app_handle: &AppHandle, watcher: &Watcher, journal: &Journal, template: &str, file_paths: Vec<String>, dry_run: bool) -> Vec<OrganizeResult> {
+  return move_to_template(app_handle, watcher, journal, template, TemplateBase::MusicFolder, file_paths, dry_run);
+}
+
+/// Renames files in place to match a file name template built from their tags, bringing their sidecar files along.
+/// With dry_run set nothing is renamed, and the planned renames are returned instead.
+pub fn rename_files(app_handle: &AppHandle, watcher: &Watcher, journal: &Journal, template: &str, file_paths: Vec<String>, dry_run: bool) -> Vec<OrganizeResult> {
+  return move_to_template(app_handle, watcher, journal, template, TemplateBase::CurrentFolder, file_paths, dry_run);
+}
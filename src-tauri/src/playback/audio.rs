@@ -1,14 +1,15 @@
-use std::{fs::File, path::Path, sync::{atomic::AtomicU32, mpsc::Receiver, Arc}, thread, time::Duration};
+use std::{fs::File, path::Path, sync::{atomic::{AtomicU32, Ordering}, mpsc::Receiver, Arc}, thread, time::Duration};
 
 use atomic_wait::wake_all;
 use cpal::traits::DeviceTrait;
-use symphonia::{core::{audio::SignalSpec, codecs::{DecoderOptions, CODEC_TYPE_NULL}, errors::Error::ResetRequired, formats::{FormatOptions, SeekTo, Track}, io::MediaSourceStream, meta::MetadataOptions, probe::Hint, units::Time}, default::get_probe};
+use symphonia::{core::{audio::SignalSpec, codecs::{DecoderOptions, CODEC_TYPE_NULL}, errors::Error::ResetRequired, formats::{FormatOptions, SeekTo, Track}, io::MediaSourceStream, meta::MetadataOptions, probe::Hint, units::{Time, TimeBase}}, default::get_probe};
 use tauri::{async_runtime::Mutex, AppHandle, Emitter};
 use tokio_util::sync::CancellationToken;
 
 // use crate::logger;
+use crate::song::ReplayGain;
 
-use super::{output::{self, get_device_by_name, AudioOutput, AudioOutputError}, types::{PlayerEvent, SampleOffsetEvent, VolumeEvent, PAUSED}};
+use super::{output::{self, get_device_by_name, AudioOutput, AudioOutputError}, types::{PlaybackEvent, PlayerEvent, SampleOffsetEvent, VolumeEvent, NORMALIZATION_ALBUM, NORMALIZATION_OFF, NORMALIZATION_TRACK, PAUSED}};
 
 
 fn log(_app_handle: &AppHandle, msg: &str, _level: usize) {
@@ -18,6 +19,7 @@ fn log(_app_handle: &AppHandle, msg: &str, _level: usize) {
 /// Starts audio playback.
 pub fn start_audio(
   decoding_active: &Arc<AtomicU32>,
+  normalization_mode: &Arc<AtomicU32>,
   player_receiver: &Arc<Mutex<Receiver<PlayerEvent>>>,
   volume_receiver: &Arc<Mutex<Receiver<VolumeEvent>>>,
   app_handle: &AppHandle
@@ -27,12 +29,13 @@ pub fn start_audio(
 
   wake_all(decoding_active.as_ref());
 
-  decode_loop(&decoding_active, player_receiver, volume_receiver, app_handle);
+  decode_loop(&decoding_active, normalization_mode, player_receiver, volume_receiver, app_handle);
 }
 
 /// Handles decoding the current track.
 fn decode_loop(
   decoding_active: &Arc<AtomicU32>,
+  normalization_mode: &Arc<AtomicU32>,
   player_receiver: &Arc<Mutex<Receiver<PlayerEvent>>>,
   volume_receiver: &Arc<Mutex<Receiver<VolumeEvent>>>,
   app_handle: &AppHandle
@@ -48,15 +51,23 @@ fn decode_loop(
   let mut timestamp: f64 = 0.0;
   let mut previous_sample_rate = 44100;
   let mut previous_channels = 2;
+  let mut replay_gain: Option<ReplayGain> = None;
+  let mut applied_normalization: u32;
+  // * Songs from a cue sheet only play part of their file. Positions sent to and from the frontend are relative to the start.
+  let mut range_start: f64 = 0.0;
+  let mut range_end: Option<f64> = None;
+  let mut pending_event: Option<PlayerEvent> = None;
 
   let (playback_state_sender, playback_state_receiver) = std::sync::mpsc::channel();
   let (reset_control_sender, reset_control_receiver) = std::sync::mpsc::channel();
   let (device_change_sender, device_change_receiver) = std::sync::mpsc::channel();
   let (sender_sample_offset, receiver_sample_offset) = std::sync::mpsc::channel();
+  let (gain_sender, gain_receiver) = std::sync::mpsc::channel();
   let sample_offset_receiver = Arc::new(Mutex::new(receiver_sample_offset));
   let playback_state = Arc::new(Mutex::new(playback_state_receiver));
   let reset_control = Arc::new(Mutex::new(reset_control_receiver));
   let device_change = Arc::new(Mutex::new(device_change_receiver));
+  let gain_control = Arc::new(Mutex::new(gain_receiver));
 
   let mut audio_output: Option<Result<Arc<Mutex<dyn AudioOutput>>, AudioOutputError>> = None;
 
@@ -80,6 +91,9 @@ fn decode_loop(
             path_str.replace(event.file_path);
             seek.replace(event.position.unwrap());
             volume.replace(event.volume.unwrap());
+            replay_gain = event.replay_gain;
+            range_start = event.start.unwrap_or(0.0);
+            range_end = event.end;
           }
           PlayerEvent::SetAudioDevice(device_name) => {
             log(app_handle, "changing audio device", 0);
@@ -127,7 +141,8 @@ fn decode_loop(
       let probe_result = get_probe().format(&hint, mss, &format_opts, &metadata_opts);
 
       if probe_result.is_err() {
-        println!("probe_result err: {}", probe_result.err().unwrap());
+        log(app_handle, format!("Failed to open {}: {}", p, probe_result.err().unwrap()).as_str(), 2);
+        let _ = app_handle.emit("unplayable", Some(p.clone()));
         path_str = None;
         continue;
       }
@@ -147,7 +162,7 @@ fn decode_loop(
       // Note: This is a half-baked approach to seeking! After seeking the reader, packets should be
       // decoded and *samples* discarded up-to the exact *sample* indicated by required_ts. The
       // current approach will discard excess samples if seeking to a sample within a packet.
-      let seek_ts = if let Some(sk) = seek {
+      let seek_ts = if let Some(sk) = seek.map(| sk | sk + range_start) {
         let seek_to = SeekTo::Time {
           time: Time::from(sk),
           track_id: Some(track_id),
@@ -172,9 +187,16 @@ fn decode_loop(
       };
 
       // Create a decoder for the track.
-      let mut decoder = symphonia::default::get_codecs()
-        .make(&track.codec_params, &DecoderOptions { verify: false })
-        .unwrap();
+      // * Some formats can be read but not decoded, like Opus, so they get reported instead of played.
+      let decoder_res = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions { verify: false });
+
+      if decoder_res.is_err() {
+        log(app_handle, format!("Failed to decode {}: {}", p, decoder_res.err().unwrap()).as_str(), 2);
+        let _ = app_handle.emit("unplayable", Some(p.clone()));
+        continue;
+      }
+
+      let mut decoder = decoder_res.unwrap();
 
       let spec = SignalSpec {
         rate: decoder.codec_params().sample_rate.unwrap(),
@@ -258,6 +280,7 @@ fn decode_loop(
           playback_state.clone(),
           reset_control.clone(),
           device_change.clone(),
+          gain_control.clone(),
           volume.clone(),
         ));
       } else {
@@ -273,6 +296,9 @@ fn decode_loop(
         ),
       });
 
+      applied_normalization = normalization_mode.load(Ordering::Relaxed);
+      let _ = gain_sender.send(get_normalization_gain(&replay_gain, applied_normalization));
+
       let receiver = player_receiver.try_lock().unwrap();
 
       if let Some(ref audio) = audio_output {
@@ -290,9 +316,11 @@ fn decode_loop(
               }
             }
 
+            let mut end_ts = range_end.map(| end | get_timestamp(&time_base, end));
+
             // Decode all packets, ignoring all decode errors.
             let result = loop {
-              let event = receiver.try_recv();
+              let event = pending_event.take().map(Ok).unwrap_or_else(| | receiver.try_recv());
               
               if let Ok(result) = event {
                 match result {
@@ -302,6 +330,9 @@ fn decode_loop(
                     path_str.replace(event.file_path);
                     seek.replace(event.position.unwrap());
                     volume.replace(event.volume.unwrap());
+                    replay_gain = event.replay_gain;
+                    range_start = event.start.unwrap_or(0.0);
+                    range_end = event.end;
                     cancel_token.cancel();
                     guard.flush();
                     is_reset = true;
@@ -355,6 +386,9 @@ fn decode_loop(
                       path_str.replace(event.file_path);
                       seek.replace(event.position.unwrap());
                       volume.replace(event.volume.unwrap());
+                      replay_gain = event.replay_gain;
+                      range_start = event.start.unwrap_or(0.0);
+                      range_end = event.end;
                       cancel_token.cancel();
                       guard.flush();
                       is_reset = true;
@@ -396,6 +430,13 @@ fn decode_loop(
               let _ = playback_state_sender.send(true);
               let _ = app_handle.emit("playing", {});
 
+              // Apply normalization changes to the current track right away.
+              let current_normalization = normalization_mode.load(Ordering::Relaxed);
+              if current_normalization != applied_normalization {
+                applied_normalization = current_normalization;
+                let _ = gain_sender.send(get_normalization_gain(&replay_gain, applied_normalization));
+              }
+
               let packet = match reader.next_packet() {
                 Ok(packet) => packet,
                 Err(err) => break Err(err),
@@ -406,6 +447,59 @@ fn decode_loop(
                 continue;
               }
 
+              // The end of a cue sheet song was reached. If the next song picks up where this one ended, keep decoding so there is no gap.
+              if end_ts.is_some_and(| end_ts | packet.ts() >= end_ts) {
+                let _ = app_handle.emit("ended", Some(0.0f64));
+
+                let ended_at = range_end.unwrap();
+                let mut is_continued = false;
+
+                // * Wait for the next song while the buffered samples play out.
+                loop {
+                  match receiver.try_recv() {
+                    Ok(PlayerEvent::LoadFile(event)) if is_continuation(&event, &path_str_clone, ended_at) => {
+                      log(app_handle, "continuing into the next cue sheet track...", 0);
+
+                      replay_gain = event.replay_gain;
+                      range_start = event.start.unwrap_or(0.0);
+                      range_end = event.end;
+                      end_ts = range_end.map(| end | get_timestamp(&time_base, end));
+
+                      applied_normalization = normalization_mode.load(Ordering::Relaxed);
+                      let _ = gain_sender.send(get_normalization_gain(&replay_gain, applied_normalization));
+
+                      is_continued = true;
+                      break;
+                    }
+                    Ok(event) => {
+                      pending_event = Some(event);
+                      break;
+                    }
+                    Err(_) => {}
+                  }
+
+                  if !guard.has_remaining_samples() {
+                    break;
+                  }
+
+                  thread::sleep(Duration::from_millis(10));
+                }
+
+                if !is_continued {
+                  // Let the end of this song finish playing before handling whatever comes next.
+                  while guard.has_remaining_samples() {
+                    thread::sleep(Duration::from_millis(10));
+                  }
+
+                  if pending_event.is_none() {
+                    guard.pause();
+                    break Ok(());
+                  }
+
+                  continue;
+                }
+              }
+
               // Decode the packet into audio samples.
               match decoder.decode(&packet) {
                 Ok(mut _decoded) => {
@@ -423,7 +517,7 @@ fn decode_loop(
                       let mut ramp_down_smpls = 0;
 
                       let time = time_base.calc_time(packet.ts());
-                      let seconds = time.seconds as f64;
+                      let seconds = (time.seconds as f64 + time.frac - range_start).floor().max(0.0);
 
                       if seconds != timestamp {
                         timestamp = seconds;
@@ -476,6 +570,52 @@ fn decode_loop(
   }
 }
 
+/// Gets the linear gain to apply for the provided normalization mode.
+/// The gain is limited by the peak, if it is known, so normalized tracks don't clip.
+fn get_normalization_gain(replay_gain: &Option<ReplayGain>, mode: u32) -> f64 {
+  if mode == NORMALIZATION_OFF || replay_gain.is_none() {
+    return 1.0;
+  }
+
+  let info = replay_gain.unwrap();
+
+  // * Fall back to the other gain if the preferred one is missing.
+  let (gain_db, peak) = if mode == NORMALIZATION_ALBUM && info.albumGain.is_some() {
+    (info.albumGain, info.albumPeak.or(info.trackPeak))
+  } else if mode == NORMALIZATION_TRACK && info.trackGain.is_some() {
+    (info.trackGain, info.trackPeak)
+  } else if info.trackGain.is_some() {
+    (info.trackGain, info.trackPeak)
+  } else {
+    (info.albumGain, info.albumPeak)
+  };
+
+  if gain_db.is_none() {
+    return 1.0;
+  }
+
+  let mut gain = 10f64.powf(gain_db.unwrap() / 20.0);
+
+  if let Some(peak) = peak {
+    if peak > 0.0 {
+      gain = gain.min(1.0 / peak);
+    }
+  }
+
+  return gain;
+}
+
+/// Converts a position in seconds into a timestamp in the provided time base.
+fn get_timestamp(time_base: &TimeBase, seconds: f64) -> u64 {
+  return (seconds * time_base.denom as f64 / time_base.numer as f64).round() as u64;
+}
+
+/// Checks if a load event continues the current file right where the last cue sheet song ended.
+fn is_continuation(event: &PlaybackEvent, current_path: &Option<String>, ended_at: f64) -> bool {
+  let start = event.start.unwrap_or(0.0) + event.position.unwrap_or(0.0);
+  return current_path.as_ref() == Some(&event.file_path) && (start - ended_at).abs() < 0.05;
+}
+
 /// Gets the first supported track in the media file.
 fn first_supported_track(tracks: &[Track]) -> Option<&Track> {
   return tracks.iter().find(|t| t.codec_params.codec != CODEC_TYPE_NULL);
@@ -1,6 +1,8 @@
 use tauri::{AppHandle, State};
 
-use super::{output, player::AudioPlayer, types::{AudioDevices, PlaybackEvent, PlayerEvent, VolumeEvent}};
+use crate::song::ReplayGain;
+
+use super::{output, player::AudioPlayer, types::{AudioDevices, PlaybackEvent, PlayerEvent, VolumeEvent, NORMALIZATION_ALBUM, NORMALIZATION_OFF, NORMALIZATION_TRACK}};
 
 #[tauri::command]
 /// Gets the output devices.
@@ -15,12 +17,16 @@ pub fn set_audio_device(state: State<AudioPlayer>, _app_handle: AppHandle, devic
 }
 
 #[tauri::command]
-/// Loads the provided filepath.
-pub fn load_file(state: State<AudioPlayer>, _app_handle: AppHandle, file_path: String, position: f64) {
+/// Loads the provided filepath, along with its ReplayGain info if it has any.
+/// Songs from a cue sheet only play from their start to their end, and the position is relative to their start.
+pub fn load_file(state: State<AudioPlayer>, _app_handle: AppHandle, file_path: String, position: f64, replay_gain: Option<ReplayGain>, start_ms: Option<u64>, end_ms: Option<u64>) {
   let _ = state.player_sender.send(PlayerEvent::LoadFile(PlaybackEvent {
     file_path,
     position: Some(position),
-    volume: Some(1.0f64)
+    volume: Some(1.0f64),
+    replay_gain,
+    start: start_ms.map(| start | start as f64 / 1000.0),
+    end: end_ms.map(| end | end as f64 / 1000.0),
   }));
 }
 
@@ -36,6 +42,21 @@ pub fn set_volume(state: State<AudioPlayer>, _app_handle: AppHandle, level: f64)
   let _ = state.volume_sender.send(VolumeEvent::SetVolume(level));
 }
 
+#[tauri::command]
+/// Sets the ReplayGain normalization mode. Can be "off", "track" or "album".
+pub fn set_normalization(state: State<AudioPlayer>, _app_handle: AppHandle, mode: String) -> Result<(), String> {
+  let mode_value = match mode.as_str() {
+    "off" => NORMALIZATION_OFF,
+    "track" => NORMALIZATION_TRACK,
+    "album" => NORMALIZATION_ALBUM,
+    _ => return Err(format!("Unknown normalization mode: {}", mode)),
+  };
+
+  state.set_normalization(mode_value);
+
+  return Ok(());
+}
+
 #[tauri::command]
 /// Resumes audio playback.
 pub fn resume_playback(state: State<AudioPlayer>, _app_handle: AppHandle) {
@@ -91,6 +91,7 @@ mod cpal {
           playback_state_receiver: Arc<Mutex<Receiver<bool>>>,
           reset_control_receiver: Arc<Mutex<Receiver<bool>>>,
           device_change_receiver: Arc<Mutex<Receiver<String>>>,
+          gain_control_receiver: Arc<Mutex<Receiver<f64>>>,
           vol: Option<f64>,
       ) -> Result<Arc<Mutex<dyn AudioOutput>>> {
           let device = get_device_by_name(Some(device_name.clone())).unwrap();
@@ -145,7 +146,8 @@ mod cpal {
                   playback_state_receiver,
                   reset_control_receiver,
                   device_change_receiver,
-                  |packet, volume| ((packet as f64) * volume) as f32,
+                  gain_control_receiver,
+                  |packet, volume| ((packet as f64) * volume).clamp(-1.0, 1.0) as f32,
                   vol,
               ),
               cpal::SampleFormat::I16 => CpalAudioOutputImpl::<i16>::try_open(
@@ -157,6 +159,7 @@ mod cpal {
                   playback_state_receiver,
                   reset_control_receiver,
                   device_change_receiver,
+                  gain_control_receiver,
                   |packet, volume| ((packet as f64) * volume) as i16,
                   vol,
               ),
@@ -169,6 +172,7 @@ mod cpal {
                   playback_state_receiver,
                   reset_control_receiver,
                   device_change_receiver,
+                  gain_control_receiver,
                   |packet, volume| ((packet as f64) * volume) as u16,
                   vol,
               ),
@@ -181,7 +185,8 @@ mod cpal {
                   playback_state_receiver,
                   reset_control_receiver,
                   device_change_receiver,
-                  |packet, volume| ((packet as f64) * volume) as f32,
+                  gain_control_receiver,
+                  |packet, volume| ((packet as f64) * volume).clamp(-1.0, 1.0) as f32,
                   vol,
               ),
           }
@@ -212,6 +217,7 @@ mod cpal {
           playback_state_receiver: Arc<Mutex<Receiver<bool>>>,
           reset_control_receiver: Arc<Mutex<Receiver<bool>>>,
           device_change_receiver: Arc<Mutex<Receiver<String>>>,
+          gain_control_receiver: Arc<Mutex<Receiver<f64>>>,
           volume_change: fn(T, f64) -> T,
           vol: Option<f64>
       ) -> Result<Arc<Mutex<dyn AudioOutput>>> {
@@ -239,6 +245,7 @@ mod cpal {
           
           // States
           let volume_state = Arc::new(RwLock::new(vol.unwrap()));
+          let gain_state = Arc::new(RwLock::new(1.0f64));
           let frame_idx_state = Arc::new(RwLock::new(0));
           let playback_state = Arc::new(RwLock::new(true));
           let device_state = Arc::new(RwLock::new(
@@ -286,7 +293,16 @@ mod cpal {
                       }
                   }
 
-                  let current_volume = { *volume_state.read().unwrap() };
+                  // Get the normalization gain
+                  let gain = gain_control_receiver.try_lock();
+                  if let Ok(gain_lock) = gain {
+                      if let Ok(new_gain) = gain_lock.try_recv() {
+                          let mut current_gain = gain_state.write().unwrap();
+                          *current_gain = new_gain;
+                      }
+                  }
+
+                  let current_volume = { *volume_state.read().unwrap() * *gain_state.read().unwrap() };
                   // info!("Current volume: {:?}", current_volume);
 
                   let playing = playback_state_receiver.try_lock();
@@ -507,6 +523,7 @@ pub fn try_open(
     playback_state_receiver: Arc<Mutex<Receiver<bool>>>,
     reset_control_receiver: Arc<Mutex<Receiver<bool>>>,
     device_change_receiver: Arc<Mutex<Receiver<String>>>,
+    gain_control_receiver: Arc<Mutex<Receiver<f64>>>,
     vol: Option<f64>,
 ) -> Result<Arc<Mutex<dyn AudioOutput>>> {
     cpal::CpalAudioOutput::try_open(
@@ -518,6 +535,7 @@ pub fn try_open(
         playback_state_receiver,
         reset_control_receiver,
         device_change_receiver,
+        gain_control_receiver,
         vol,
     )
 }
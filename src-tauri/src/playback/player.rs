@@ -3,13 +3,14 @@ use std::sync::{atomic::AtomicU32, mpsc::{Receiver, Sender}, Arc};
 use atomic_wait::wake_all;
 use tauri::{async_runtime::Mutex, AppHandle};
 
-use super::{audio::start_audio, output::poll_audio_devices, types::{PlayerEvent, VolumeEvent, ACTIVE, PAUSED}};
+use super::{audio::start_audio, output::poll_audio_devices, types::{PlayerEvent, VolumeEvent, ACTIVE, NORMALIZATION_OFF, PAUSED}};
 
 #[derive(Clone)]
 pub struct AudioPlayer {
   pub player_receiver: Arc<Mutex<Receiver<PlayerEvent>>>,
   pub player_sender: Sender<PlayerEvent>,
   pub decoding_active: Arc<AtomicU32>,
+  pub normalization_mode: Arc<AtomicU32>,
   pub volume_receiver: Arc<Mutex<Receiver<VolumeEvent>>>,
   pub volume_sender: Sender<VolumeEvent>,
 }
@@ -24,6 +25,7 @@ impl AudioPlayer {
       player_receiver: Arc::new(Mutex::new(player_receiver)),
       player_sender,
       decoding_active: Arc::new(AtomicU32::new(PAUSED)),
+      normalization_mode: Arc::new(AtomicU32::new(NORMALIZATION_OFF)),
       volume_receiver: Arc::new(Mutex::new(volume_receiver)),
       volume_sender,
     }
@@ -33,12 +35,13 @@ impl AudioPlayer {
     let player_receiver = self.player_receiver.clone();
     let volume_receiver = self.volume_receiver.clone();
     let decoding_active = self.decoding_active.clone();
+    let normalization_mode = self.normalization_mode.clone();
 
     let handle_clone = app_handle.clone();
 
     // Create a thread for handling audio events and playback.
     std::thread::spawn(move || {
-      start_audio(&decoding_active, &player_receiver, &volume_receiver, &handle_clone);
+      start_audio(&decoding_active, &normalization_mode, &player_receiver, &volume_receiver, &handle_clone);
     });
     
     // Create a thread for polling for changes in audio devices.
@@ -52,6 +55,11 @@ impl AudioPlayer {
     let _ = &self.decoding_active.store(PAUSED, std::sync::atomic::Ordering::Relaxed);
   }
 
+  /// Sets how playback volume is normalized using ReplayGain.
+  pub fn set_normalization(&self, mode: u32) {
+    self.normalization_mode.store(mode, std::sync::atomic::Ordering::Relaxed);
+  }
+
   /// Resumes audio playback.
   pub fn resume(&self) {
     if (&self).decoding_active.load(std::sync::atomic::Ordering::Relaxed) == PAUSED {
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::song::ReplayGain;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AudioDevice {
   pub name: String,
@@ -16,6 +18,9 @@ pub struct PlaybackEvent {
   pub file_path: String,
   pub position: Option<f64>,
   pub volume: Option<f64>,
+  pub replay_gain: Option<ReplayGain>,
+  pub start: Option<f64>,
+  pub end: Option<f64>,
 }
 
 
@@ -35,4 +40,8 @@ pub struct SampleOffsetEvent {
 }
 
 pub const PAUSED: u32 = 0;
-pub const ACTIVE: u32 = 1;
\ No newline at end of file
+pub const ACTIVE: u32 = 1;
+
+pub const NORMALIZATION_OFF: u32 = 0;
+pub const NORMALIZATION_TRACK: u32 = 1;
+pub const NORMALIZATION_ALBUM: u32 = 2;
\ No newline at end of file
@@ -0,0 +1,190 @@
+use id3::{frame::Popularimeter, TagLike};
+use serde::Deserialize;
+
+/// The POPM owner Windows Media Player uses, which most other players read ratings from.
+pub const POPM_OWNER: &str = "Windows Media Player 9 Series";
+
+/// The POPM byte written for each half star rating, following the values MusicBee and foobar2000 use.
+/// They stay inside the ranges Windows Media Player reads as whole stars, so players without half stars round them.
+const POPM_VALUES: [(f32, u8); 10] = [
+  (0.5, 13),
+  (1.0, 1),
+  (1.5, 54),
+  (2.0, 64),
+  (2.5, 118),
+  (3.0, 128),
+  (3.5, 186),
+  (4.0, 196),
+  (4.5, 242),
+  (5.0, 255),
+];
+
+/// Where play counts get written in ID3 tags. Vorbis comments always use FMPS_PLAYCOUNT.
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PlayCountMode {
+  /// Play counts are never written.
+  #[default]
+  Off,
+  /// The PCNT frame, which counts plays for everyone.
+  Pcnt,
+  /// The counter of the POPM frame ratings are written to.
+  Popm,
+}
+
+/// Rounds a rating to the nearest half star, between 0 and 5.
+pub fn round_rating(stars: f32) -> f32 {
+  return ((stars * 2.0).round() / 2.0).clamp(0.0, 5.0);
+}
+
+/// Converts a POPM rating byte to stars. 0 means the song isn't rated.
+pub fn popm_to_stars(rating: u8) -> Option<f32> {
+  let stars = match rating {
+    0 => return None,
+    1 => 1.0,
+    2..=31 => 0.5,
+    32..=59 => 1.5,
+    60..=95 => 2.0,
+    96..=122 => 2.5,
+    123..=159 => 3.0,
+    160..=191 => 3.5,
+    192..=223 => 4.0,
+    224..=249 => 4.5,
+    250..=255 => 5.0,
+  };
+
+  return Some(stars);
+}
+
+/// Converts stars to a POPM rating byte. Less than half a star means the song isn't rated.
+pub fn stars_to_popm(stars: f32) -> u8 {
+  let rounded = round_rating(stars);
+  return POPM_VALUES.iter().find(| (value_stars, _) | *value_stars == rounded).map(| (_, value) | *value).unwrap_or(0);
+}
+
+/// Parses a Vorbis RATING value. Values up to 5 are stars, and anything larger is out of 100.
+pub fn parse_vorbis_rating(value: &str) -> Option<f32> {
+  let rating = value.trim().parse::<f32>().ok()?;
+
+  if !rating.is_finite() || rating <= 0.0 || rating > 100.0 {
+    return None;
+  }
+
+  let stars = if rating <= 5.0 { rating } else { rating / 20.0 };
+  return Some(round_rating(stars)).filter(| stars | *stars > 0.0);
+}
+
+/// Parses a FMPS_RATING value, which goes from 0 to 1.
+pub fn parse_fmps_rating(value: &str) -> Option<f32> {
+  let rating = value.trim().parse::<f32>().ok()?;
+
+  if !rating.is_finite() || rating <= 0.0 || rating > 1.0 {
+    return None;
+  }
+
+  return Some(round_rating(rating * 5.0)).filter(| stars | *stars > 0.0);
+}
+
+/// Parses the counter of a PCNT frame, which is a big endian number of at least 4 bytes.
+pub fn parse_pcnt(data: &[u8]) -> Option<u64> {
+  if data.is_empty() || data.len() > 8 {
+    return None;
+  }
+
+  let mut bytes = [0; 8];
+  bytes[8 - data.len()..].copy_from_slice(data);
+
+  return Some(u64::from_be_bytes(bytes));
+}
+
+/// Renders the counter of a PCNT frame.
+pub fn render_pcnt(count: u64) -> Vec<u8> {
+  let bytes = count.to_be_bytes();
+  let first = bytes.iter().position(| byte | *byte != 0).unwrap_or(bytes.len()).min(4);
+
+  return bytes[first..].to_vec();
+}
+
+/// Reads the rating of an ID3 tag. Every player writes its own POPM frame,
+/// so the one Windows Media Player uses is preferred since it's the most widely read.
+pub fn get_id3_rating(tag: &id3::Tag) -> Option<f32> {
+  let mut popularimeters: Vec<&Popularimeter> = tag.frames().filter_map(| frame | frame.content().popularimeter()).collect();
+  popularimeters.sort_by_key(| popularimeter | popularimeter.user != POPM_OWNER);
+
+  return popularimeters.iter().find_map(| popularimeter | popm_to_stars(popularimeter.rating));
+}
+
+/// Reads the play count of an ID3 tag, which is the highest of the PCNT frame and the POPM counters.
+pub fn get_id3_play_count(tag: &id3::Tag) -> Option<u64> {
+  let play_count = tag.get("PCNT").and_then(| frame | frame.content().to_unknown().ok()).and_then(| unknown | parse_pcnt(&unknown.data));
+  let counters = tag.frames().filter_map(| frame | frame.content().popularimeter()).map(| popularimeter | popularimeter.counter);
+
+  return play_count.into_iter().chain(counters).filter(| count | *count > 0).max();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn half_stars_round_trip_through_popm() {
+    for (stars, value) in POPM_VALUES {
+      assert_eq!(stars_to_popm(stars), value);
+      assert_eq!(popm_to_stars(value), Some(stars));
+    }
+  }
+
+  #[test]
+  fn reads_whole_star_popm_values() {
+    // * The values Windows Media Player writes for one to five stars.
+    assert_eq!([1, 64, 128, 196, 255].map(popm_to_stars), [Some(1.0), Some(2.0), Some(3.0), Some(4.0), Some(5.0)]);
+    assert_eq!(popm_to_stars(0), None);
+  }
+
+  #[test]
+  fn rounds_stars_before_writing() {
+    assert_eq!(stars_to_popm(4.3), 242);
+    assert_eq!(stars_to_popm(0.2), 0);
+    assert_eq!(stars_to_popm(7.0), 255);
+  }
+
+  #[test]
+  fn parses_vorbis_ratings() {
+    assert_eq!(parse_vorbis_rating("3"), Some(3.0));
+    assert_eq!(parse_vorbis_rating("2.7"), Some(2.5));
+    assert_eq!(parse_vorbis_rating("80"), Some(4.0));
+    assert_eq!(parse_vorbis_rating("100"), Some(5.0));
+    assert_eq!(parse_vorbis_rating("0"), None);
+    assert_eq!(parse_vorbis_rating("101"), None);
+    assert_eq!(parse_vorbis_rating("five"), None);
+  }
+
+  #[test]
+  fn parses_fmps_ratings() {
+    assert_eq!(parse_fmps_rating("0.8"), Some(4.0));
+    assert_eq!(parse_fmps_rating("1"), Some(5.0));
+    assert_eq!(parse_fmps_rating("0.3"), Some(1.5));
+    assert_eq!(parse_fmps_rating("0"), None);
+    assert_eq!(parse_fmps_rating("1.5"), None);
+  }
+
+  #[test]
+  fn pcnt_counters_round_trip() {
+    assert_eq!(render_pcnt(5), vec![0, 0, 0, 5]);
+    assert_eq!(render_pcnt(1 << 40), vec![1, 0, 0, 0, 0, 0]);
+    assert_eq!(parse_pcnt(&render_pcnt(1 << 40)), Some(1 << 40));
+    assert_eq!(parse_pcnt(&[0, 0, 1, 2]), Some(258));
+    assert_eq!(parse_pcnt(&[]), None);
+    assert_eq!(parse_pcnt(&[0; 9]), None);
+  }
+
+  #[test]
+  fn prefers_the_windows_media_player_popm() {
+    let mut tag = id3::Tag::new();
+    tag.add_frame(Popularimeter { user: String::from("other@example.com"), rating: 64, counter: 3 });
+    tag.add_frame(Popularimeter { user: String::from(POPM_OWNER), rating: 242, counter: 9 });
+
+    assert_eq!(get_id3_rating(&tag), Some(4.5));
+    assert_eq!(get_id3_play_count(&tag), Some(9));
+  }
+}
@@ -0,0 +1,114 @@
+use std::{path::PathBuf, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
+
+use tauri::{AppHandle, Emitter};
+
+use crate::music_readers::ScanError;
+
+/// The minimum time between two progress events.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, serde::Serialize)]
+#[allow(non_snake_case)]
+pub struct ScanProgress {
+  filesDiscovered: usize,
+  filesProcessed: usize,
+  currentPath: String,
+}
+
+/// Tracks the progress of a single library scan, and whether it has been cancelled.
+pub struct ScanTracker {
+  app_handle: AppHandle,
+  cancelled: Arc<AtomicBool>,
+  files_discovered: AtomicUsize,
+  files_processed: AtomicUsize,
+  last_emit: Mutex<Instant>,
+  errors: Mutex<Vec<ScanError>>,
+}
+
+impl ScanTracker {
+  /// Checks if the scan has been cancelled.
+  pub fn is_cancelled(&self) -> bool {
+    return self.cancelled.load(Ordering::Relaxed);
+  }
+
+  /// Records that a music file was found.
+  pub fn file_discovered(&self, file_path: &PathBuf) {
+    self.files_discovered.fetch_add(1, Ordering::Relaxed);
+    self.emit_if_due(file_path, false);
+  }
+
+  /// Records that a music file was processed.
+  pub fn file_processed(&self, file_path: &PathBuf) {
+    self.files_processed.fetch_add(1, Ordering::Relaxed);
+    self.emit_if_due(file_path, false);
+  }
+
+  /// Records an error that isn't tied to a single music file, like a folder that couldn't be read.
+  pub fn report_error(&self, error: ScanError) {
+    self.errors.lock().unwrap().push(error);
+  }
+
+  /// Takes the errors recorded during the scan.
+  pub fn take_errors(&self) -> Vec<ScanError> {
+    return std::mem::take(&mut *self.errors.lock().unwrap());
+  }
+
+  /// Emits the final progress of the scan.
+  pub fn finish(&self) {
+    self.emit_if_due(&PathBuf::new(), true);
+  }
+
+  /// Emits a progress event if enough time has passed since the last one.
+  fn emit_if_due(&self, file_path: &PathBuf, force: bool) {
+    // * If another worker is emitting right now, there's no need to wait on it.
+    let last_emit_res = if force { self.last_emit.lock().ok() } else { self.last_emit.try_lock().ok() };
+
+    if let Some(mut last_emit) = last_emit_res {
+      if force || last_emit.elapsed() >= PROGRESS_INTERVAL {
+        *last_emit = Instant::now();
+
+        let _ = self.app_handle.emit("scan_progress", ScanProgress {
+          filesDiscovered: self.files_discovered.load(Ordering::Relaxed),
+          filesProcessed: self.files_processed.load(Ordering::Relaxed),
+          currentPath: file_path.as_os_str().to_str().unwrap_or("").to_owned(),
+        });
+      }
+    }
+  }
+}
+
+pub struct ScanState {
+  current: Mutex<Arc<AtomicBool>>,
+}
+
+impl ScanState {
+  /// Creates a new ScanState.
+  pub fn new() -> ScanState {
+    return ScanState {
+      current: Mutex::new(Arc::new(AtomicBool::new(false))),
+    };
+  }
+
+  /// Starts tracking a new scan, cancelling any scan that is still running.
+  pub fn start(&self, app_handle: AppHandle) -> ScanTracker {
+    let cancelled = Arc::new(AtomicBool::new(false));
+
+    let mut current = self.current.lock().unwrap();
+    current.store(true, Ordering::Relaxed);
+    *current = cancelled.clone();
+
+    return ScanTracker {
+      app_handle,
+      cancelled,
+      files_discovered: AtomicUsize::new(0),
+      files_processed: AtomicUsize::new(0),
+      last_emit: Mutex::new(Instant::now().checked_sub(PROGRESS_INTERVAL).unwrap_or(Instant::now())),
+      errors: Mutex::new(vec![]),
+    };
+  }
+
+  /// Cancels the running scan, if there is one.
+  pub fn cancel(&self) {
+    self.current.lock().unwrap().store(true, Ordering::Relaxed);
+  }
+}
@@ -0,0 +1,234 @@
+use std::path::Path;
+
+use glob::{MatchOptions, Pattern};
+use serde::{Deserialize, Serialize};
+
+use crate::music_readers::is_supported_extension;
+
+/// How glob patterns are matched against paths.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+  case_sensitive: false,
+  require_literal_separator: true,
+  require_literal_leading_dot: false,
+};
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[allow(non_snake_case)]
+pub struct ScanRules {
+  #[serde(default)]
+  pub include: Vec<String>,
+  #[serde(default)]
+  pub exclude: Vec<String>,
+  #[serde(default)]
+  pub skipHidden: bool,
+  #[serde(default)]
+  pub maxDepth: Option<usize>,
+  #[serde(default)]
+  pub coverNames: Option<Vec<String>>,
+}
+
+/// The cover file names looked for in album folders when none are configured.
+const DEFAULT_COVER_NAMES: [&str; 5] = ["cover.*", "folder.*", "front.*", "album.*", "albumart.*"];
+
+/// Image extensions that folder covers can have.
+const COVER_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "webp", "bmp"];
+
+/// Extensions of the files next to songs that the scanner reads, like cue sheets and lyrics.
+const SIDECAR_EXTENSIONS: [&str; 2] = ["cue", "lrc"];
+
+/// A glob pattern, and whether it should only be matched against the file or folder name.
+#[derive(Clone)]
+struct Rule {
+  pattern: Pattern,
+  name_only: bool,
+}
+
+impl Rule {
+  /// Checks if the rule matches the provided path.
+  fn matches(&self, path_str: &str, name: &str, is_dir: bool) -> bool {
+    if self.name_only {
+      return self.pattern.matches_with(name, MATCH_OPTIONS);
+    }
+
+    // * Folders also get checked with a trailing separator, so "**/Samples/**" excludes the folder itself.
+    return self.pattern.matches_with(path_str, MATCH_OPTIONS) || (is_dir && self.pattern.matches_with(&format!("{}/", path_str), MATCH_OPTIONS));
+  }
+}
+
+/// The compiled rules used by the scanner and the watcher to decide which files belong in the library.
+#[derive(Clone, Default)]
+pub struct CompiledScanRules {
+  include: Vec<Rule>,
+  exclude: Vec<Rule>,
+  blacklist: Vec<String>,
+  skip_hidden: bool,
+  max_depth: Option<usize>,
+  cover_names: Vec<Pattern>,
+}
+
+/// Normalizes a path to forward slashes so the same patterns work on every platform.
+fn normalize_path(path: &Path) -> String {
+  return path.to_string_lossy().replace('\\', "/");
+}
+
+/// Gets the file or folder name of a path.
+fn get_name(path: &Path) -> String {
+  return path.file_name().map(| name | name.to_string_lossy().to_string()).unwrap_or_default();
+}
+
+/// Compiles a list of glob patterns, returning the ones that were invalid separately.
+fn compile_rules(patterns: &[String], invalid: &mut Vec<String>) -> Vec<Rule> {
+  let mut rules: Vec<Rule> = vec![];
+
+  for pattern_str in patterns {
+    let normalized = pattern_str.trim().replace('\\', "/");
+
+    if normalized.is_empty() {
+      continue;
+    }
+
+    match Pattern::new(&normalized) {
+      Ok(pattern) => rules.push(Rule {
+        pattern,
+        name_only: !normalized.contains('/'),
+      }),
+      Err(err) => invalid.push(format!("{}: {}", pattern_str, err.msg)),
+    }
+  }
+
+  return rules;
+}
+
+impl CompiledScanRules {
+  /// Compiles the provided rules along with the exact folder blacklist.
+  /// Returns the rules and any patterns that couldn't be parsed.
+  pub fn new(rules: &ScanRules, blacklist: &[String]) -> (CompiledScanRules, Vec<String>) {
+    let mut invalid: Vec<String> = vec![];
+
+    let cover_names: Vec<String> = rules.coverNames.clone().unwrap_or(DEFAULT_COVER_NAMES.iter().map(| name | name.to_string()).collect());
+
+    let compiled = CompiledScanRules {
+      include: compile_rules(&rules.include, &mut invalid),
+      exclude: compile_rules(&rules.exclude, &mut invalid),
+      blacklist: blacklist.to_vec(),
+      skip_hidden: rules.skipHidden,
+      max_depth: rules.maxDepth,
+      cover_names: compile_rules(&cover_names, &mut invalid).into_iter().map(| rule | rule.pattern).collect(),
+    };
+
+    return (compiled, invalid);
+  }
+
+  /// Checks if a path is hidden or excluded by the blacklist or an exclude pattern.
+  fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+    let path_str = normalize_path(path);
+    let name = get_name(path);
+
+    if self.skip_hidden && name.starts_with('.') {
+      return true;
+    }
+
+    if is_dir && self.blacklist.iter().any(| blacklisted | Path::new(blacklisted) == path) {
+      return true;
+    }
+
+    return self.exclude.iter().any(| rule | rule.matches(&path_str, &name, is_dir));
+  }
+
+  /// Checks if the scanner should descend into a folder at the provided depth below its music folder.
+  pub fn allows_dir(&self, path: &Path, depth: usize) -> bool {
+    if self.max_depth.is_some_and(| max_depth | depth > max_depth) {
+      return false;
+    }
+
+    return !self.is_excluded(path, true);
+  }
+
+  /// Checks if a file should be included in the library.
+  pub fn allows_file(&self, path: &Path) -> bool {
+    if self.is_excluded(path, false) {
+      return false;
+    }
+
+    if self.include.is_empty() {
+      return true;
+    }
+
+    let path_str = normalize_path(path);
+    let name = get_name(path);
+
+    return self.include.iter().any(| rule | rule.matches(&path_str, &name, false));
+  }
+
+  /// Checks if a path under one of the music folders is allowed, including all of its parent folders.
+  /// Used when the path may no longer exist, like for watcher events. Only music files, the sidecars the scanner reads,
+  /// and folders are allowed.
+  pub fn allows_path(&self, music_folders: &[String], path: &Path) -> bool {
+    let root_res = music_folders.iter().map(| folder | Path::new(folder)).find(| folder | path.starts_with(folder));
+
+    if root_res.is_none() {
+      return false;
+    }
+
+    let root = root_res.unwrap();
+    let mut current = root.to_path_buf();
+    let relative = path.strip_prefix(root).unwrap();
+    let num_components = relative.components().count();
+
+    for (index, component) in relative.components().enumerate() {
+      current.push(component);
+
+      if index + 1 < num_components && !self.allows_dir(&current, index + 1) {
+        return false;
+      }
+    }
+
+    if path.is_dir() {
+      return self.allows_dir(path, num_components);
+    }
+
+    let extension = path.extension().and_then(| extension | extension.to_str()).unwrap_or("");
+
+    if is_supported_extension(extension) {
+      return self.allows_file(path);
+    }
+
+    // * Sidecars aren't music files, so include patterns don't apply to them.
+    if self.is_sidecar_file(path) {
+      return !self.is_excluded(path, false);
+    }
+
+    // * Removed paths can't be checked, so anything that isn't a music file may have been a folder.
+    if !path.exists() {
+      return self.allows_dir(path, num_components);
+    }
+
+    return false;
+  }
+
+  /// Checks if a file is one the scanner reads next to songs, like a cue sheet, lyrics, or a folder cover.
+  pub fn is_sidecar_file(&self, path: &Path) -> bool {
+    let extension = path.extension().and_then(| extension | extension.to_str()).unwrap_or("");
+    return SIDECAR_EXTENSIONS.iter().any(| sidecar_extension | sidecar_extension.eq_ignore_ascii_case(extension)) || self.is_cover_file(path);
+  }
+
+  /// Gets the cover names the rules look for, so changing them can be detected.
+  pub fn get_cover_names(&self) -> Vec<&str> {
+    return self.cover_names.iter().map(| pattern | pattern.as_str()).collect();
+  }
+
+  /// Checks if a file is an image with one of the folder cover names.
+  pub fn is_cover_file(&self, path: &Path) -> bool {
+    let is_image = path.extension().and_then(| extension | extension.to_str()).is_some_and(| extension | {
+      return COVER_EXTENSIONS.iter().any(| cover_extension | cover_extension.eq_ignore_ascii_case(extension));
+    });
+
+    if !is_image {
+      return false;
+    }
+
+    let name = get_name(path);
+
+    return self.cover_names.iter().any(| pattern | pattern.matches_with(&name, MATCH_OPTIONS));
+  }
+}
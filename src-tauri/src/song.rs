@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use symphonia::core::meta::{StandardTagKey, Tag};
+
+use crate::ratings::{parse_fmps_rating, parse_vorbis_rating};
+
+/// ID3 frames that symphonia maps to `Date` but only hold the day or time.
+const NON_YEAR_DATE_FRAMES: [&str; 3] = ["TDAT", "TIME", "TRDA"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BitrateMode {
+  Cbr,
+  Vbr,
+  Abr,
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+#[allow(non_snake_case)]
+pub struct ReplayGain {
+  pub trackGain: Option<f64>,
+  pub trackPeak: Option<f64>,
+  pub albumGain: Option<f64>,
+  pub albumPeak: Option<f64>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[allow(non_snake_case)]
+pub struct Song {
+  pub filePath: String,
+  pub title: Option<String>,
+  pub artists: Vec<String>,
+  pub album: Option<String>,
+  pub albumArtist: Option<String>,
+  pub trackNumber: Option<u16>,
+  pub trackTotal: Option<u16>,
+  pub discNumber: Option<u16>,
+  pub discTotal: Option<u16>,
+  pub compilation: bool,
+  pub year: Option<i32>,
+  pub genres: Vec<String>,
+  pub composers: Vec<String>,
+  pub sortArtist: Option<String>,
+  pub sortAlbum: Option<String>,
+  pub sortTitle: Option<String>,
+  pub bpm: Option<u16>,
+  pub comment: Option<String>,
+  /// The rating in stars, from 0.5 to 5 in half steps.
+  pub rating: Option<f32>,
+  pub playCount: Option<u64>,
+  pub musicBrainzTrackId: Option<String>,
+  pub musicBrainzAlbumId: Option<String>,
+  pub musicBrainzArtistId: Option<String>,
+  pub replayGain: ReplayGain,
+  pub duration: u64,
+  pub codec: String,
+  pub bitrate: u64,
+  pub bitrateMode: Option<BitrateMode>,
+  pub encoder: Option<String>,
+  pub sampleRate: u32,
+  pub channels: Option<usize>,
+  pub bitsPerSample: Option<u32>,
+  pub size: u64,
+  pub artPath: Option<String>,
+  pub hasLyrics: bool,
+  pub cueTrack: Option<u16>,
+  pub startMs: Option<u64>,
+  pub endMs: Option<u64>,
+  pub rawTags: Map<String, Value>,
+}
+
+/// Parses a "number/total" style tag value, like "3/12".
+fn parse_number_pair(value: &str) -> (Option<u16>, Option<u16>) {
+  let mut parts = value.split('/');
+
+  let number = parts.next().and_then(| part | part.trim().parse::<u16>().ok());
+  let total = parts.next().and_then(| part | part.trim().parse::<u16>().ok());
+
+  return (number, total);
+}
+
+/// Parses the year out of a date tag value, like "2003" or "2003-04-15".
+fn parse_year(value: &str) -> Option<i32> {
+  let trimmed = value.trim();
+
+  if trimmed.len() < 4 || !trimmed.is_char_boundary(4) {
+    return None;
+  }
+
+  return trimmed[..4].parse::<i32>().ok();
+}
+
+/// Parses a boolean flag tag value, like "1" or "true".
+pub fn parse_flag(value: &str) -> bool {
+  let trimmed = value.trim();
+  return trimmed == "1" || trimmed.eq_ignore_ascii_case("true") || trimmed.eq_ignore_ascii_case("yes");
+}
+
+/// Parses a BPM tag value, which may have a fractional part.
+fn parse_bpm(value: &str) -> Option<u16> {
+  let bpm = value.trim().parse::<f64>().ok()?;
+
+  if !bpm.is_finite() || bpm <= 0.0 || bpm > u16::MAX as f64 {
+    return None;
+  }
+
+  return Some(bpm.round() as u16);
+}
+
+/// Parses a ReplayGain gain or peak value, like "-6.50 dB" or "0.988".
+pub fn parse_replay_gain(value: &str) -> Option<f64> {
+  let trimmed = value.trim();
+  // * Slicing with get keeps malformed values ending in a multibyte character from panicking.
+  let has_unit = trimmed.len() > 2 && trimmed.get(trimmed.len() - 2..).is_some_and(| unit | unit.eq_ignore_ascii_case("db"));
+  let number = if has_unit { &trimmed[..trimmed.len() - 2] } else { trimmed };
+  let parsed = number.trim().parse::<f64>().ok()?;
+
+  if !parsed.is_finite() {
+    return None;
+  }
+
+  return Some(parsed);
+}
+
+/// Sets a ReplayGain field if it hasn't been set yet.
+fn set_gain_if_missing(field: &mut Option<f64>, value: &str) {
+  if field.is_none() {
+    *field = parse_replay_gain(value);
+  }
+}
+
+/// Sets a single valued field if it hasn't been set yet.
+fn set_if_missing(field: &mut Option<String>, value: &str) {
+  if field.is_none() {
+    *field = Some(value.to_owned());
+  }
+}
+
+/// Adds a value to a multi-valued field, skipping duplicates.
+fn push_unique(values: &mut Vec<String>, value: &str) {
+  if !values.iter().any(| existing | existing == value) {
+    values.push(value.to_owned());
+  }
+}
+
+impl Song {
+  /// Creates a new Song for the provided file.
+  pub fn new(file_path: String) -> Song {
+    return Song {
+      filePath: file_path,
+      ..Default::default()
+    };
+  }
+
+  /// Applies a tag to the song's canonical fields, and keeps the raw value.
+  pub fn apply_tag(&mut self, raw_key: String, tag: &Tag) {
+    let value = tag.value.to_string().trim().to_owned();
+
+    if value.is_empty() {
+      return;
+    }
+
+    match tag.std_key {
+      Some(StandardTagKey::TrackTitle) => {
+        if self.title.is_none() {
+          self.title = Some(value.clone());
+        }
+      }
+      Some(StandardTagKey::Artist) => push_unique(&mut self.artists, &value),
+      Some(StandardTagKey::Album) => {
+        if self.album.is_none() {
+          self.album = Some(value.clone());
+        }
+      }
+      Some(StandardTagKey::AlbumArtist) => {
+        if self.albumArtist.is_none() {
+          self.albumArtist = Some(value.clone());
+        }
+      }
+      Some(StandardTagKey::TrackNumber) => {
+        let (number, total) = parse_number_pair(&value);
+        self.trackNumber = number.or(self.trackNumber);
+        self.trackTotal = total.or(self.trackTotal);
+      }
+      Some(StandardTagKey::TrackTotal) => {
+        let (total, _) = parse_number_pair(&value);
+        self.trackTotal = total.or(self.trackTotal);
+      }
+      Some(StandardTagKey::DiscNumber) => {
+        let (number, total) = parse_number_pair(&value);
+        self.discNumber = number.or(self.discNumber);
+        self.discTotal = total.or(self.discTotal);
+      }
+      Some(StandardTagKey::DiscTotal) => {
+        let (total, _) = parse_number_pair(&value);
+        self.discTotal = total.or(self.discTotal);
+      }
+      Some(StandardTagKey::Compilation) => {
+        self.compilation = self.compilation || parse_flag(&value);
+      }
+      Some(StandardTagKey::SortArtist) => set_if_missing(&mut self.sortArtist, &value),
+      Some(StandardTagKey::SortAlbum) => set_if_missing(&mut self.sortAlbum, &value),
+      Some(StandardTagKey::SortTrackTitle) => set_if_missing(&mut self.sortTitle, &value),
+      Some(StandardTagKey::Bpm) => {
+        if self.bpm.is_none() {
+          self.bpm = parse_bpm(&value);
+        }
+      }
+      Some(StandardTagKey::Comment) => {
+        // * ID3 comments are read separately, since symphonia drops their descriptions.
+        if !tag.key.starts_with("COMM") {
+          set_if_missing(&mut self.comment, &value);
+        }
+      }
+      Some(StandardTagKey::MusicBrainzTrackId) | Some(StandardTagKey::MusicBrainzRecordingId) => set_if_missing(&mut self.musicBrainzTrackId, &value),
+      Some(StandardTagKey::MusicBrainzAlbumId) => set_if_missing(&mut self.musicBrainzAlbumId, &value),
+      Some(StandardTagKey::MusicBrainzArtistId) => set_if_missing(&mut self.musicBrainzArtistId, &value),
+      Some(StandardTagKey::Encoder) => set_if_missing(&mut self.encoder, &value),
+      Some(StandardTagKey::ReplayGainTrackGain) => set_gain_if_missing(&mut self.replayGain.trackGain, &value),
+      Some(StandardTagKey::ReplayGainTrackPeak) => set_gain_if_missing(&mut self.replayGain.trackPeak, &value),
+      Some(StandardTagKey::ReplayGainAlbumGain) => set_gain_if_missing(&mut self.replayGain.albumGain, &value),
+      Some(StandardTagKey::ReplayGainAlbumPeak) => set_gain_if_missing(&mut self.replayGain.albumPeak, &value),
+      Some(StandardTagKey::Rating) => {
+        // * ID3 POPM frames are read separately, since symphonia only keeps their text.
+        if self.rating.is_none() && !tag.key.starts_with("POPM") {
+          self.rating = parse_vorbis_rating(&value);
+        }
+      }
+      None if tag.key.eq_ignore_ascii_case("fmps_rating") => {
+        if self.rating.is_none() {
+          self.rating = parse_fmps_rating(&value);
+        }
+      }
+      None if tag.key.eq_ignore_ascii_case("fmps_playcount") => {
+        if self.playCount.is_none() {
+          self.playCount = value.parse::<f64>().ok().filter(| count | count.is_finite() && *count >= 0.0).map(| count | count as u64);
+        }
+      }
+      None if tag.key.eq_ignore_ascii_case("compilation") => {
+        // * Symphonia doesn't map the Vorbis COMPILATION comment to a standard key.
+        self.compilation = self.compilation || parse_flag(&value);
+      }
+      Some(StandardTagKey::Date) => {
+        if self.year.is_none() && !NON_YEAR_DATE_FRAMES.contains(&tag.key.as_str()) {
+          self.year = parse_year(&value);
+        }
+      }
+      Some(StandardTagKey::Genre) => push_unique(&mut self.genres, &value),
+      Some(StandardTagKey::Composer) => push_unique(&mut self.composers, &value),
+      _ => {}
+    }
+
+    // * Keys that show up more than once keep all of their values as an array.
+    match self.rawTags.get_mut(&raw_key) {
+      Some(Value::Array(values)) => values.push(Value::String(value)),
+      Some(existing) => {
+        let first = existing.take();
+        *existing = Value::Array(vec![first, Value::String(value)]);
+      }
+      None => {
+        self.rawTags.insert(raw_key, Value::String(value));
+      }
+    }
+  }
+}
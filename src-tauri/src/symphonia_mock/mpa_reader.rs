@@ -15,14 +15,17 @@ use symphonia::core::codecs::CodecParameters;
 use symphonia::core::errors::{seek_error, Result, SeekErrorKind};
 use symphonia::core::formats::prelude::*;
 use symphonia::core::io::*;
-use symphonia::core::meta::{Metadata, MetadataLog};
+use symphonia::core::meta::{Metadata, MetadataBuilder, MetadataLog, StandardTagKey, Tag, Value};
 use symphonia::core::probe::{Descriptor, Instantiate, QueryDescriptor};
 use symphonia::core::io::BufReader;
 
+use crate::song::BitrateMode;
 use crate::symphonia_mock::common::{FrameHeader, MpegLayer};
 use crate::symphonia_mock::header::{self, MAX_MPEG_FRAME_SIZE, MPEG_HEADER_LEN};
 
+use std::fs::File;
 use std::io::{Seek, SeekFrom};
+use std::path::Path;
 
 /// MPEG1 and MPEG2 audio elementary stream reader.
 ///
@@ -103,17 +106,23 @@ impl FormatReader for MpaReader {
 
     params
       .for_codec(header.codec())
-      .with_bits_per_sample(header.bitrate)
       .with_sample_rate(header.sample_rate)
       .with_time_base(TimeBase::new(1, header.sample_rate))
       .with_channels(header.channel_mode.channels());
 
+    let mut metadata = MetadataLog::default();
+
     // Check if there is a Xing/Info tag contained in the first frame.
     if let Some(info_tag) = try_read_info_tag(&packet, &header) {
       // The LAME tag contains ReplayGain and padding information.
       let (delay, padding) = if let Some(lame_tag) = info_tag.lame {
         params.with_delay(lame_tag.enc_delay).with_padding(lame_tag.enc_padding);
 
+        // Expose the ReplayGain values as regular tags, so they are read the same way as ID3 or Vorbis ones.
+        let mut builder = MetadataBuilder::new();
+        lame_tag.add_replaygain_tags(&mut builder);
+        metadata.push(builder.metadata());
+
         (lame_tag.enc_delay, lame_tag.enc_padding)
       } else {
         (0, 0)
@@ -155,7 +164,7 @@ impl FormatReader for MpaReader {
       reader: source,
       tracks: vec![Track::new(0, params)],
       cues: Vec::new(),
-      metadata: Default::default(),
+      metadata,
       options: *options,
       first_packet_pos,
       next_packet_ts: 0,
@@ -566,9 +575,9 @@ const XING_TAG_ID: [u8; 4] = *b"Xing";
 const INFO_TAG_ID: [u8; 4] = *b"Info";
 
 /// The LAME tag is an extension to the Xing/Info tag.
-#[allow(dead_code)]
 struct LameTag {
   encoder: String,
+  vbr_method: u8,
   replaygain_peak: Option<f32>,
   replaygain_radio: Option<f32>,
   replaygain_audiophile: Option<f32>,
@@ -576,6 +585,33 @@ struct LameTag {
   enc_padding: u32,
 }
 
+impl LameTag {
+  /// Gets the bitrate mode from the LAME VBR method, if it is one of the known values.
+  fn bitrate_mode(&self) -> Option<BitrateMode> {
+    return match self.vbr_method {
+      1 | 8 => Some(BitrateMode::Cbr),
+      2 | 9 => Some(BitrateMode::Abr),
+      3..=7 => Some(BitrateMode::Vbr),
+      _ => None,
+    };
+  }
+
+  /// Adds the ReplayGain values of the tag. Radio gain is the track gain, and audiophile gain is the album gain.
+  fn add_replaygain_tags(&self, builder: &mut MetadataBuilder) {
+    if let Some(gain) = self.replaygain_radio {
+      builder.add_tag(Tag::new(Some(StandardTagKey::ReplayGainTrackGain), "REPLAYGAIN_TRACK_GAIN", Value::from(format!("{:.2} dB", gain))));
+    }
+
+    if let Some(peak) = self.replaygain_peak {
+      builder.add_tag(Tag::new(Some(StandardTagKey::ReplayGainTrackPeak), "REPLAYGAIN_TRACK_PEAK", Value::from(format!("{:.6}", peak))));
+    }
+
+    if let Some(gain) = self.replaygain_audiophile {
+      builder.add_tag(Tag::new(Some(StandardTagKey::ReplayGainAlbumGain), "REPLAYGAIN_ALBUM_GAIN", Value::from(format!("{:.2} dB", gain))));
+    }
+  }
+}
+
 /// The Xing/Info time additional information for regarding a MP3 file.
 #[allow(dead_code)]
 struct XingInfoTag {
@@ -656,10 +692,10 @@ fn try_read_info_tag_inner(buf: &[u8], header: &FrameHeader) -> Result<Option<Xi
     // Lowpass filter value.
     let _lowpass = reader.read_u8()?;
 
-    // Replay gain peak in 9.23 (bit) fixed-point format.
+    // Replay gain peak in 9.23 (bit) fixed-point format, where 1.0 is full scale.
     let replaygain_peak = match reader.read_be_u32()? {
       0 => None,
-      peak => Some(32767.0 * (peak as f32 / 2.0f32.powi(23))),
+      peak => Some(peak as f32 / 2.0f32.powi(23)),
     };
 
     // Radio replay gain.
@@ -668,8 +704,8 @@ fn try_read_info_tag_inner(buf: &[u8], header: &FrameHeader) -> Result<Option<Xi
     // Audiophile replay gain.
     let replaygain_audiophile = parse_lame_tag_replaygain(reader.read_be_u16()?, 2);
 
-    // Encoding flags & ATH type.
-    let _encoding_flags = reader.read_u8()?;
+    // Encoding flags & ATH type. The lower 4 bits are the VBR method.
+    let encoding_flags = reader.read_u8()?;
 
     // Arbitrary bitrate.
     let _abr = reader.read_u8()?;
@@ -726,6 +762,7 @@ fn try_read_info_tag_inner(buf: &[u8], header: &FrameHeader) -> Result<Option<Xi
       // The CRC matched or is not present.
       Some(LameTag {
         encoder: String::from_utf8_lossy(&encoder).into(),
+        vbr_method: encoding_flags & 0x0f,
         replaygain_peak,
         replaygain_radio,
         replaygain_audiophile,
@@ -789,7 +826,6 @@ fn is_maybe_info_tag(buf: &[u8], header: &FrameHeader) -> bool {
 const VBRI_TAG_ID: [u8; 4] = *b"VBRI";
 
 /// The contents of a VBRI tag.
-#[allow(dead_code)]
 struct VbriTag {
   num_bytes: u32,
   num_mpeg_frames: u32,
@@ -862,4 +898,75 @@ fn is_maybe_vbri_tag(buf: &[u8], header: &FrameHeader) -> bool {
 
   // The bytes preceeding the VBRI tag (mostly the side information) should be all 0.
   !buf[MPEG_HEADER_LEN..VBRI_TAG_OFFSET].iter().any(|&b| b != 0)
-}
\ No newline at end of file
+}
+/// The stream level information of a MP3 file that isn't exposed through the codec parameters.
+pub struct Mp3StreamInfo {
+  pub bitrate_mode: BitrateMode,
+  pub encoder: Option<String>,
+  pub audio_bytes: Option<u64>,
+  pub header_bitrate: u32,
+}
+
+/// Gets the size of the ID3v2 tag at the start of a file, so the first MPEG frame can be found without scanning through it.
+fn get_id3v2_tag_len(file: &mut File) -> std::io::Result<u64> {
+  use std::io::Read;
+
+  let mut tag_header = [0u8; 10];
+
+  if file.read_exact(&mut tag_header).is_err() || &tag_header[..3] != b"ID3" {
+    return Ok(0);
+  }
+
+  // The tag size is a 28 bit sync-safe integer, and doesn't include the header or footer.
+  let size = tag_header[6..10].iter().fold(0u64, |size, byte| (size << 7) | u64::from(byte & 0x7f));
+  let footer_len = if tag_header[5] & 0x10 != 0 { 10 } else { 0 };
+
+  return Ok(10 + size + footer_len);
+}
+
+/// Reads the Xing/Info, LAME, or VBRI tag in the first frame of a MP3 file.
+pub fn read_mp3_stream_info(file_path: &Path) -> Result<Mp3StreamInfo> {
+  let mut file = File::open(file_path)?;
+
+  let tag_len = get_id3v2_tag_len(&mut file)?;
+  file.seek(SeekFrom::Start(tag_len))?;
+
+  let mut source = MediaSourceStream::new(Box::new(file), Default::default());
+  let (header, packet) = read_mpeg_frame_strict(&mut source)?;
+
+  if let Some(info_tag) = try_read_info_tag(&packet, &header) {
+    let default_mode = if info_tag.is_cbr { BitrateMode::Cbr } else { BitrateMode::Vbr };
+
+    let (bitrate_mode, encoder) = match &info_tag.lame {
+      Some(lame_tag) => {
+        let encoder = lame_tag.encoder.trim_end_matches(['\0', ' ']).to_owned();
+        (lame_tag.bitrate_mode().unwrap_or(default_mode), if encoder.is_empty() { None } else { Some(encoder) })
+      }
+      None => (default_mode, None),
+    };
+
+    return Ok(Mp3StreamInfo {
+      bitrate_mode,
+      encoder,
+      audio_bytes: info_tag.num_bytes.map(u64::from),
+      header_bitrate: header.bitrate,
+    });
+  }
+
+  if let Some(vbri_tag) = try_read_vbri_tag(&packet, &header) {
+    return Ok(Mp3StreamInfo {
+      bitrate_mode: BitrateMode::Vbr,
+      encoder: None,
+      audio_bytes: Some(u64::from(vbri_tag.num_bytes)),
+      header_bitrate: header.bitrate,
+    });
+  }
+
+  // * Files without a Xing/Info or VBRI tag are almost always CBR, so the first frame's bitrate is the bitrate of the file.
+  return Ok(Mp3StreamInfo {
+    bitrate_mode: BitrateMode::Cbr,
+    encoder: None,
+    audio_bytes: None,
+    header_bitrate: header.bitrate,
+  });
+}
@@ -0,0 +1,557 @@
+use std::{fs::{File, OpenOptions}, io::{Read, Seek, SeekFrom, Write}};
+
+use id3::TagLike;
+use serde::Deserialize;
+
+use crate::file_results::FileError;
+
+/// The size of an ID3v1 tag.
+const ID3V1_SIZE: u64 = 128;
+/// The size of the extended "TAG+" block that can come right before an ID3v1 tag.
+const ID3V1_EXTENDED_SIZE: u64 = 227;
+
+/// The size of an APE tag's header and footer.
+const APE_FOOTER_SIZE: u64 = 32;
+/// The APE version written, which is APEv2.
+const APE_VERSION: u32 = 2000;
+/// Set on both the header and footer when the tag has a header.
+const APE_FLAG_HAS_HEADER: u32 = 1 << 31;
+/// Set on the header, to tell it apart from the footer.
+const APE_FLAG_IS_HEADER: u32 = 1 << 29;
+
+/// The bits of an APE item's flags that hold the type of its value.
+const APE_ITEM_TYPE_MASK: u32 = 0b110;
+/// The item type of binary values, like cover art.
+const APE_ITEM_TYPE_BINARY: u32 = 0b010;
+
+/// The APE item keys that mirror the ID3v2 tag when syncing.
+const APE_SYNCED_KEYS: [&str; 10] = ["Title", "Artist", "Album", "Album Artist", "Composer", "Genre", "Year", "Track", "Disc", "Comment"];
+
+/// What to do with a tag at the end of an MP3 file when its ID3v2 tag is written.
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TrailerMode {
+  /// Leave the tag as it is.
+  #[default]
+  Keep,
+  /// Remove the tag.
+  Remove,
+  /// Update the tag to match the ID3v2 tag, if the file has one.
+  Sync,
+}
+
+/// Where the tags at the end of a file are.
+struct TrailerLayout {
+  ape: Option<(u64, u64)>,
+  id3v1: Option<(u64, u64)>,
+  length: u64,
+}
+
+impl TrailerLayout {
+  /// Gets where the first tag at the end of the file starts, or the end of the file if it has none.
+  fn start(&self) -> u64 {
+    return self.ape.or(self.id3v1).map(| (start, _) | start).unwrap_or(self.length);
+  }
+}
+
+/// An item in an APE tag.
+struct ApeItem {
+  key: String,
+  flags: u32,
+  value: Vec<u8>,
+}
+
+/// The value of an item in an APE tag, as read by read_ape_tag.
+pub enum ApeValue {
+  /// Text values, which are separated by null bytes when an item has more than one.
+  Text(Vec<String>),
+  Binary(Vec<u8>),
+}
+
+/// Reads the bytes at a position in a file.
+fn read_at(file: &mut File, position: u64, length: u64) -> Result<Vec<u8>, FileError> {
+  let mut buffer = vec![0; length as usize];
+
+  file.seek(SeekFrom::Start(position))?;
+  file.read_exact(&mut buffer)?;
+
+  return Ok(buffer);
+}
+
+/// Reads a little endian u32 out of a buffer.
+fn read_u32(buffer: &[u8], position: usize) -> u32 {
+  return u32::from_le_bytes(buffer[position..position + 4].try_into().unwrap());
+}
+
+/// Finds the ID3v1 and APE tags at the end of a file.
+fn find_trailers(file: &mut File) -> Result<TrailerLayout, FileError> {
+  let length = file.seek(SeekFrom::End(0))?;
+  let mut id3v1 = None;
+
+  if length >= ID3V1_SIZE && read_at(file, length - ID3V1_SIZE, 3)? == b"TAG" {
+    let mut start = length - ID3V1_SIZE;
+
+    if start >= ID3V1_EXTENDED_SIZE && read_at(file, start - ID3V1_EXTENDED_SIZE, 4)? == b"TAG+" {
+      start -= ID3V1_EXTENDED_SIZE;
+    }
+
+    id3v1 = Some((start, length));
+  }
+
+  // * The APE tag sits right before the ID3v1 tag when a file has both.
+  let ape_end = id3v1.map(| (start, _) | start).unwrap_or(length);
+  let mut ape = None;
+
+  if ape_end >= APE_FOOTER_SIZE {
+    let footer = read_at(file, ape_end - APE_FOOTER_SIZE, APE_FOOTER_SIZE)?;
+
+    if &footer[..8] == b"APETAGEX" {
+      let size = read_u32(&footer, 12) as u64;
+      let header_size = if read_u32(&footer, 20) & APE_FLAG_HAS_HEADER != 0 { APE_FOOTER_SIZE } else { 0 };
+
+      // * A size that points before the start of the file means the footer is garbage, so it's left alone.
+      if let Some(start) = ape_end.checked_sub(size + header_size) {
+        if size >= APE_FOOTER_SIZE {
+          ape = Some((start, ape_end));
+        }
+      }
+    }
+  }
+
+  return Ok(TrailerLayout { ape, id3v1, length });
+}
+
+/// Reads the items of an APE tag.
+fn parse_ape_items(tag: &[u8]) -> Vec<ApeItem> {
+  let footer = &tag[tag.len() - APE_FOOTER_SIZE as usize..];
+  let item_count = read_u32(footer, 16);
+  let has_header = read_u32(footer, 20) & APE_FLAG_HAS_HEADER != 0;
+
+  let mut position = if has_header { APE_FOOTER_SIZE as usize } else { 0 };
+  let items_end = tag.len() - APE_FOOTER_SIZE as usize;
+  let mut items = Vec::new();
+
+  for _ in 0..item_count {
+    if position + 8 > items_end {
+      break;
+    }
+
+    let size = read_u32(tag, position) as usize;
+    let flags = read_u32(tag, position + 4);
+    let key_start = position + 8;
+    let key_length = tag[key_start..items_end].iter().position(| byte | *byte == 0);
+
+    if key_length.is_none() {
+      break;
+    }
+
+    let value_start = key_start + key_length.unwrap() + 1;
+
+    if value_start + size > items_end {
+      break;
+    }
+
+    items.push(ApeItem {
+      key: String::from_utf8_lossy(&tag[key_start..value_start - 1]).into_owned(),
+      flags,
+      value: tag[value_start..value_start + size].to_vec(),
+    });
+
+    position = value_start + size;
+  }
+
+  return items;
+}
+
+/// Builds the header or footer of an APE tag.
+fn render_ape_footer(size: u32, item_count: u32, flags: u32) -> Vec<u8> {
+  let mut footer = Vec::with_capacity(APE_FOOTER_SIZE as usize);
+
+  footer.extend_from_slice(b"APETAGEX");
+  footer.extend_from_slice(&APE_VERSION.to_le_bytes());
+  footer.extend_from_slice(&size.to_le_bytes());
+  footer.extend_from_slice(&item_count.to_le_bytes());
+  footer.extend_from_slice(&flags.to_le_bytes());
+  footer.extend_from_slice(&[0; 8]);
+
+  return footer;
+}
+
+/// Builds an APEv2 tag, with a header and footer, out of its items.
+fn render_ape_tag(items: &[ApeItem]) -> Vec<u8> {
+  let mut body = Vec::new();
+
+  for item in items {
+    body.extend_from_slice(&(item.value.len() as u32).to_le_bytes());
+    body.extend_from_slice(&item.flags.to_le_bytes());
+    body.extend_from_slice(item.key.as_bytes());
+    body.push(0);
+    body.extend_from_slice(&item.value);
+  }
+
+  let size = body.len() as u32 + APE_FOOTER_SIZE as u32;
+  let item_count = items.len() as u32;
+
+  let mut tag = render_ape_footer(size, item_count, APE_FLAG_HAS_HEADER | APE_FLAG_IS_HEADER);
+  tag.append(&mut body);
+  tag.append(&mut render_ape_footer(size, item_count, APE_FLAG_HAS_HEADER));
+
+  return tag;
+}
+
+/// Gets the values of an ID3v2 frame.
+fn get_id3_values(tag: &id3::Tag, id: &str) -> Vec<String> {
+  return tag.get(id).and_then(| frame | frame.content().text_values()).map(| values | values.map(| value | value.to_owned()).collect()).unwrap_or_default();
+}
+
+/// Formats a number with its total, like 3/12.
+fn format_position(number: Option<u32>, total: Option<u32>) -> Option<String> {
+  return match (number, total) {
+    (Some(number), Some(total)) => Some(format!("{}/{}", number, total)),
+    (Some(number), None) => Some(number.to_string()),
+    _ => None,
+  };
+}
+
+/// Gets the values the synced APE items should have, from an ID3v2 tag.
+fn get_synced_ape_values(tag: &id3::Tag) -> Vec<(&'static str, Vec<String>)> {
+  let year = tag.year().or(tag.date_recorded().map(| date | date.year)).map(| year | year.to_string());
+  let comment = tag.comments().find(| comment | comment.description.is_empty()).map(| comment | comment.text.clone());
+
+  return vec![
+    ("Title", get_id3_values(tag, "TIT2")),
+    ("Artist", get_id3_values(tag, "TPE1")),
+    ("Album", get_id3_values(tag, "TALB")),
+    ("Album Artist", get_id3_values(tag, "TPE2")),
+    ("Composer", get_id3_values(tag, "TCOM")),
+    ("Genre", get_id3_values(tag, "TCON")),
+    ("Year", year.into_iter().collect()),
+    ("Track", format_position(tag.track(), tag.total_tracks()).into_iter().collect()),
+    ("Disc", format_position(tag.disc(), tag.total_discs()).into_iter().collect()),
+    ("Comment", comment.into_iter().collect()),
+  ];
+}
+
+/// Rebuilds an APE tag so its common items match an ID3v2 tag. Items it doesn't know about, like ReplayGain values, are kept.
+fn sync_ape_tag(ape_tag: &[u8], tag: &id3::Tag) -> Vec<u8> {
+  let synced_values = get_synced_ape_values(tag);
+  let mut items: Vec<ApeItem> = parse_ape_items(ape_tag).into_iter().filter(| item | {
+    return !APE_SYNCED_KEYS.iter().any(| key | key.eq_ignore_ascii_case(&item.key));
+  }).collect();
+
+  for (key, values) in synced_values {
+    if values.is_empty() {
+      continue;
+    }
+
+    // * APE text items hold multiple values separated by null bytes.
+    items.push(ApeItem {
+      key: key.to_owned(),
+      flags: 0,
+      value: values.join("\0").into_bytes(),
+    });
+  }
+
+  return render_ape_tag(&items);
+}
+
+/// Writes text into an ID3v1 field, which only holds Latin-1 text of a fixed length.
+fn write_id3v1_text(field: &mut [u8], text: &str) {
+  let encoded = text.chars().map(| character | if (character as u32) < 256 { character as u8 } else { b'?' });
+
+  for (byte, encoded_byte) in field.iter_mut().zip(encoded) {
+    *byte = encoded_byte;
+  }
+}
+
+/// Finds the ID3v1 genre number for a genre name, or 255 if it isn't one of the predefined genres.
+fn find_id3v1_genre(genre: Option<&String>) -> u8 {
+  if genre.is_none() {
+    return u8::MAX;
+  }
+
+  let genre = genre.unwrap();
+
+  return (0..u8::MAX).find(| genre_id | {
+    let v1_tag = id3::v1::Tag { genre_id: *genre_id, ..Default::default() };
+    return v1_tag.genre().is_some_and(| name | name.eq_ignore_ascii_case(genre));
+  }).unwrap_or(u8::MAX);
+}
+
+/// Builds an ID3v1 tag that matches an ID3v2 tag, as far as ID3v1 can hold it.
+fn render_id3v1_tag(tag: &id3::Tag) -> Vec<u8> {
+  let mut v1_tag = vec![0; ID3V1_SIZE as usize];
+  let year = tag.year().or(tag.date_recorded().map(| date | date.year)).map(| year | year.to_string()).unwrap_or_default();
+  let comment = tag.comments().find(| comment | comment.description.is_empty()).map(| comment | comment.text.clone()).unwrap_or_default();
+
+  v1_tag[..3].copy_from_slice(b"TAG");
+  write_id3v1_text(&mut v1_tag[3..33], tag.title().unwrap_or_default());
+  write_id3v1_text(&mut v1_tag[33..63], &get_id3_values(tag, "TPE1").join(", "));
+  write_id3v1_text(&mut v1_tag[63..93], tag.album().unwrap_or_default());
+  write_id3v1_text(&mut v1_tag[93..97], &year);
+
+  // * ID3v1.1 gives up the last two bytes of the comment to hold the track number.
+  write_id3v1_text(&mut v1_tag[97..125], &comment);
+  v1_tag[126] = tag.track().and_then(| track | u8::try_from(track).ok()).unwrap_or(0);
+  v1_tag[127] = find_id3v1_genre(get_id3_values(tag, "TCON").first());
+
+  return v1_tag;
+}
+
+/// Removes or syncs the ID3v1 and APE tags at the end of an MP3 file, after its ID3v2 tag was written.
+/// Files without one of the tags don't get one added when syncing.
+pub fn update_trailers(file_path: &str, tag: &id3::Tag, id3v1_mode: TrailerMode, ape_mode: TrailerMode) -> Result<(), FileError> {
+  if id3v1_mode == TrailerMode::Keep && ape_mode == TrailerMode::Keep {
+    return Ok(());
+  }
+
+  let mut file = OpenOptions::new().read(true).write(true).open(file_path)?;
+  let layout = find_trailers(&mut file)?;
+
+  if layout.ape.is_none() && layout.id3v1.is_none() {
+    return Ok(());
+  }
+
+  let mut new_trailers = Vec::new();
+
+  if let Some((start, end)) = layout.ape {
+    let ape_tag = read_at(&mut file, start, end - start)?;
+
+    match ape_mode {
+      TrailerMode::Keep => new_trailers.extend(ape_tag),
+      TrailerMode::Remove => {}
+      TrailerMode::Sync => new_trailers.extend(sync_ape_tag(&ape_tag, tag)),
+    }
+  }
+
+  if let Some((start, end)) = layout.id3v1 {
+    match id3v1_mode {
+      TrailerMode::Keep => new_trailers.extend(read_at(&mut file, start, end - start)?),
+      TrailerMode::Remove => {}
+      TrailerMode::Sync => new_trailers.extend(render_id3v1_tag(tag)),
+    }
+  }
+
+  let start = layout.start();
+
+  file.set_len(start)?;
+  file.seek(SeekFrom::Start(start))?;
+  file.write_all(&new_trailers)?;
+
+  return Ok(());
+}
+
+/// A change to one of the tags at the end of a file: the name of the tag, its current contents,
+/// and its new contents, or None if it would be removed.
+pub type TrailerChange = (&'static str, Vec<u8>, Option<Vec<u8>>);
+
+/// Reads the items of the APE tag at the end of a file, along with where the tags at the end of the file start.
+/// Files without an APE tag have no items.
+pub fn read_ape_tag(file_path: &str) -> Result<(Vec<(String, ApeValue)>, u64), FileError> {
+  let mut file = File::open(file_path)?;
+  let layout = find_trailers(&mut file)?;
+
+  if layout.ape.is_none() {
+    return Ok((Vec::new(), layout.start()));
+  }
+
+  let (start, end) = layout.ape.unwrap();
+  let items = parse_ape_items(&read_at(&mut file, start, end - start)?).into_iter().map(| item | {
+    let value = if item.flags & APE_ITEM_TYPE_MASK == APE_ITEM_TYPE_BINARY {
+      ApeValue::Binary(item.value)
+    } else {
+      ApeValue::Text(String::from_utf8_lossy(&item.value).split('\0').map(| value | value.to_owned()).collect())
+    };
+
+    return (item.key, value);
+  }).collect();
+
+  return Ok((items, layout.start()));
+}
+
+/// Gets the ID3v1 and APE tags update_trailers would change, without writing anything.
+pub fn preview_trailers(file_path: &str, tag: &id3::Tag, id3v1_mode: TrailerMode, ape_mode: TrailerMode) -> Result<Vec<TrailerChange>, FileError> {
+  if id3v1_mode == TrailerMode::Keep && ape_mode == TrailerMode::Keep {
+    return Ok(Vec::new());
+  }
+
+  let mut file = File::open(file_path)?;
+  let layout = find_trailers(&mut file)?;
+  let mut changes = Vec::new();
+
+  if let (Some((start, end)), TrailerMode::Remove | TrailerMode::Sync) = (layout.ape, ape_mode) {
+    let ape_tag = read_at(&mut file, start, end - start)?;
+    let new_tag = if ape_mode == TrailerMode::Sync { Some(sync_ape_tag(&ape_tag, tag)) } else { None };
+    changes.push(("ape", ape_tag, new_tag));
+  }
+
+  if let (Some((start, end)), TrailerMode::Remove | TrailerMode::Sync) = (layout.id3v1, id3v1_mode) {
+    let v1_tag = read_at(&mut file, start, end - start)?;
+    let new_tag = if id3v1_mode == TrailerMode::Sync { Some(render_id3v1_tag(tag)) } else { None };
+    changes.push(("id3v1", v1_tag, new_tag));
+  }
+
+  return Ok(changes.into_iter().filter(| (_, old_tag, new_tag) | new_tag.as_ref() != Some(old_tag)).collect());
+}
+
+/// Reads the ID3v1 and APE tags at the end of a file, so they can be put back later.
+/// Returns None if the file doesn't have either.
+pub fn read_trailers(file_path: &str) -> Result<Option<Vec<u8>>, FileError> {
+  let mut file = File::open(file_path)?;
+  let layout = find_trailers(&mut file)?;
+  let start = layout.start();
+
+  if start == layout.length {
+    return Ok(None);
+  }
+
+  return Ok(Some(read_at(&mut file, start, layout.length - start)?));
+}
+
+/// Replaces the ID3v1 and APE tags at the end of a file with ones read by read_trailers.
+pub fn restore_trailers(file_path: &str, trailers: &[u8]) -> Result<(), FileError> {
+  let mut file = OpenOptions::new().read(true).write(true).open(file_path)?;
+  let start = find_trailers(&mut file)?.start();
+
+  file.set_len(start)?;
+  file.seek(SeekFrom::Start(start))?;
+  file.write_all(trailers)?;
+
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{fs, path::PathBuf};
+
+  use super::*;
+
+  /// The stand in for a file's audio, which comes before its tags.
+  const AUDIO: [u8; 500] = [0xff; 500];
+
+  /// Writes a file made of the provided parts into the temp folder, returning its path.
+  fn write_temp_file(name: &str, parts: &[&[u8]]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("svunes-trailers-{}-{}", std::process::id(), name));
+    fs::write(&path, parts.concat()).unwrap();
+
+    return path;
+  }
+
+  /// Builds an ID3v1 tag with a title.
+  fn id3v1_tag() -> Vec<u8> {
+    let mut v1_tag = vec![0; ID3V1_SIZE as usize];
+    v1_tag[..3].copy_from_slice(b"TAG");
+    write_id3v1_text(&mut v1_tag[3..33], "Title");
+
+    return v1_tag;
+  }
+
+  /// Builds the extended block that can come before an ID3v1 tag.
+  fn id3v1_extended_block() -> Vec<u8> {
+    let mut block = vec![0; ID3V1_EXTENDED_SIZE as usize];
+    block[..4].copy_from_slice(b"TAG+");
+
+    return block;
+  }
+
+  /// Builds an APE tag with a title and a ReplayGain item.
+  fn ape_tag() -> Vec<u8> {
+    return render_ape_tag(&[
+      ApeItem { key: String::from("Title"), flags: 0, value: b"Old".to_vec() },
+      ApeItem { key: String::from("REPLAYGAIN_TRACK_GAIN"), flags: 0, value: b"-6.00 dB".to_vec() },
+    ]);
+  }
+
+  /// Finds the trailers of a file made of the provided parts.
+  fn find_layout(name: &str, parts: &[&[u8]]) -> TrailerLayout {
+    let path = write_temp_file(name, parts);
+    let layout = find_trailers(&mut File::open(&path).unwrap()).unwrap();
+    let _ = fs::remove_file(&path);
+
+    return layout;
+  }
+
+  #[test]
+  fn finds_nothing_without_trailers() {
+    let layout = find_layout("none", &[&AUDIO]);
+
+    assert_eq!((layout.ape, layout.id3v1), (None, None));
+    assert_eq!(layout.start(), 500);
+  }
+
+  #[test]
+  fn finds_id3v1_and_its_extended_block() {
+    let layout = find_layout("id3v1", &[&AUDIO, &id3v1_tag()]);
+    assert_eq!(layout.id3v1, Some((500, 628)));
+
+    let layout = find_layout("id3v1-extended", &[&AUDIO, &id3v1_extended_block(), &id3v1_tag()]);
+    assert_eq!(layout.id3v1, Some((500, 855)));
+    assert_eq!(layout.start(), 500);
+  }
+
+  #[test]
+  fn finds_ape_before_id3v1() {
+    let ape = ape_tag();
+    let ape_end = 500 + ape.len() as u64;
+    let layout = find_layout("ape-id3v1", &[&AUDIO, &ape, &id3v1_tag()]);
+
+    assert_eq!(layout.ape, Some((500, ape_end)));
+    assert_eq!(layout.id3v1, Some((ape_end, ape_end + ID3V1_SIZE)));
+    assert_eq!(layout.start(), 500);
+  }
+
+  #[test]
+  fn finds_ape_tags_without_a_header() {
+    let ape = ape_tag();
+    let without_header = &ape[APE_FOOTER_SIZE as usize..];
+    let mut footer = without_header[without_header.len() - APE_FOOTER_SIZE as usize..].to_vec();
+    footer[20..24].copy_from_slice(&0u32.to_le_bytes());
+
+    let body = &without_header[..without_header.len() - APE_FOOTER_SIZE as usize];
+    let layout = find_layout("ape-no-header", &[&AUDIO, body, &footer]);
+
+    assert_eq!(layout.ape, Some((500, 500 + (body.len() + footer.len()) as u64)));
+  }
+
+  #[test]
+  fn ignores_ape_footers_that_point_before_the_file() {
+    let footer = render_ape_footer(10_000, 0, 0);
+
+    assert_eq!(find_layout("ape-garbage", &[&AUDIO[..10], &footer]).ape, None);
+  }
+
+  #[test]
+  fn ape_items_round_trip() {
+    let items = parse_ape_items(&ape_tag());
+
+    assert_eq!(items.iter().map(| item | (item.key.as_str(), item.value.as_slice())).collect::<Vec<(&str, &[u8])>>(), vec![("Title", b"Old".as_slice()), ("REPLAYGAIN_TRACK_GAIN", b"-6.00 dB".as_slice())]);
+  }
+
+  #[test]
+  fn syncing_keeps_unknown_ape_items() {
+    let mut tag = id3::Tag::new();
+    tag.set_title("New");
+
+    let synced = parse_ape_items(&sync_ape_tag(&ape_tag(), &tag));
+
+    assert_eq!(synced.iter().map(| item | (item.key.as_str(), item.value.as_slice())).collect::<Vec<(&str, &[u8])>>(), vec![("REPLAYGAIN_TRACK_GAIN", b"-6.00 dB".as_slice()), ("Title", b"New".as_slice())]);
+  }
+
+  #[test]
+  fn removes_and_restores_trailers() {
+    let trailers = [ape_tag(), id3v1_extended_block(), id3v1_tag()].concat();
+    let path = write_temp_file("remove-restore", &[&AUDIO, &trailers]);
+    let file_path = path.to_string_lossy().into_owned();
+
+    assert_eq!(read_trailers(&file_path).unwrap(), Some(trailers.clone()));
+
+    update_trailers(&file_path, &id3::Tag::new(), TrailerMode::Remove, TrailerMode::Remove).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), AUDIO.to_vec());
+    assert_eq!(read_trailers(&file_path).unwrap(), None);
+
+    restore_trailers(&file_path, &trailers).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), [AUDIO.as_slice(), &trailers].concat());
+
+    let _ = fs::remove_file(&path);
+  }
+}
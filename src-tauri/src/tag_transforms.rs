@@ -0,0 +1,215 @@
+use rayon::prelude::*;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{file_results::{FileError, FileErrorKind}, music_writers::{read_tag_values, SongEditFields, TagValues}};
+
+/// Words that stay lowercase in title case, unless they start or end the text.
+const SMALL_WORDS: [&str; 17] = ["a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "on", "or", "the", "to", "vs", "with"];
+
+/// A single step of a batch transform. Steps run in order, each seeing the values left by the last.
+#[allow(non_snake_case)]
+#[derive(Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TagTransform {
+  /// Replaces matches of a regex. The replacement can use capture groups like $1.
+  RegexReplace { fields: Vec<String>, pattern: String, replacement: String },
+  TitleCase { fields: Vec<String> },
+  SentenceCase { fields: Vec<String> },
+  /// Trims the ends of the values and collapses the whitespace inside them.
+  TrimWhitespace { fields: Vec<String> },
+  /// Numbers the tracks in the order the files were provided.
+  AutoNumber { start: Option<u16>, setTotal: bool },
+  CopyField { from: String, to: String },
+}
+
+/// A transform that's been checked, with its regex compiled.
+enum CompiledTransform {
+  RegexReplace { fields: Vec<String>, regex: Regex, replacement: String },
+  TitleCase { fields: Vec<String> },
+  SentenceCase { fields: Vec<String> },
+  TrimWhitespace { fields: Vec<String> },
+  AutoNumber { start: u16, set_total: bool },
+  CopyField { from: String, to: String },
+}
+
+/// Checks that a field can be transformed. The artwork isn't text, so it can't be.
+fn check_field(field: &str) -> Result<(), FileError> {
+  let is_known = SongEditFields::default().set_from_text(field, None).is_ok();
+
+  if !is_known {
+    return Err(FileError::new(FileErrorKind::Other, format!("The field {} can't be transformed.", field)));
+  }
+
+  return Ok(());
+}
+
+/// Checks the fields of each transform and compiles their regexes.
+fn compile_transforms(transforms: &[TagTransform]) -> Result<Vec<CompiledTransform>, FileError> {
+  return transforms.iter().map(| transform | {
+    let compiled = match transform.clone() {
+      TagTransform::RegexReplace { fields, pattern, replacement } => {
+        let regex = Regex::new(&pattern).map_err(| err | FileError::new(FileErrorKind::Other, format!("Invalid pattern {}: {}", pattern, err)))?;
+        CompiledTransform::RegexReplace { fields, regex, replacement }
+      }
+      TagTransform::TitleCase { fields } => CompiledTransform::TitleCase { fields },
+      TagTransform::SentenceCase { fields } => CompiledTransform::SentenceCase { fields },
+      TagTransform::TrimWhitespace { fields } => CompiledTransform::TrimWhitespace { fields },
+      TagTransform::AutoNumber { start, setTotal } => CompiledTransform::AutoNumber { start: start.unwrap_or(1), set_total: setTotal },
+      TagTransform::CopyField { from, to } => CompiledTransform::CopyField { from, to },
+    };
+
+    let fields: Vec<&String> = match &compiled {
+      CompiledTransform::RegexReplace { fields, .. } | CompiledTransform::TitleCase { fields } | CompiledTransform::SentenceCase { fields } | CompiledTransform::TrimWhitespace { fields } => fields.iter().collect(),
+      CompiledTransform::CopyField { from, to } => vec![from, to],
+      CompiledTransform::AutoNumber { .. } => vec![],
+    };
+
+    for field in fields {
+      check_field(field)?;
+    }
+
+    return Ok(compiled);
+  }).collect();
+}
+
+/// Checks if a word has capitals after its first letter, like "AC/DC", "II" or "McCartney".
+fn has_inner_capitals(word: &str) -> bool {
+  return word.chars().filter(| character | character.is_alphabetic()).skip(1).any(| character | character.is_uppercase());
+}
+
+/// Uppercases the first letter of some text. The rest is kept as it is, so names and acronyms aren't mangled.
+fn capitalize(text: &str) -> String {
+  let mut capitalized = String::with_capacity(text.len());
+  let mut found_letter = false;
+
+  for character in text.chars() {
+    if !found_letter && character.is_alphabetic() {
+      capitalized.extend(character.to_uppercase());
+      found_letter = true;
+    } else {
+      capitalized.push(character);
+    }
+  }
+
+  return capitalized;
+}
+
+/// Converts text to title case, keeping small words like "of" lowercase.
+/// Words with capitals after their first letter are left alone.
+fn to_title_case(text: &str) -> String {
+  let words: Vec<&str> = text.split(' ').collect();
+  let last = words.len().saturating_sub(1);
+
+  return words.iter().enumerate().map(| (i, word) | {
+    if has_inner_capitals(word) {
+      return word.to_string();
+    }
+
+    let bare_word: String = word.chars().filter(| character | character.is_alphanumeric()).collect::<String>().to_lowercase();
+
+    if i != 0 && i != last && SMALL_WORDS.contains(&bare_word.as_str()) {
+      return word.to_lowercase();
+    }
+
+    return capitalize(word);
+  }).collect::<Vec<String>>().join(" ");
+}
+
+/// Converts text to sentence case by uppercasing its first letter, and "I" wherever it's written lowercase.
+/// The rest isn't lowercased, since that would also lowercase proper nouns.
+fn to_sentence_case(text: &str) -> String {
+  let words: Vec<String> = text.split(' ').map(| word | {
+    let is_pronoun = word == "i" || word.starts_with("i'") || word.starts_with("i\u{2019}");
+    return if is_pronoun { capitalize(word) } else { word.to_string() };
+  }).collect();
+
+  return capitalize(&words.join(" "));
+}
+
+/// Trims text and collapses the whitespace inside it.
+fn trim_whitespace(text: &str) -> String {
+  return text.split_whitespace().collect::<Vec<&str>>().join(" ");
+}
+
+/// Gets the value of a field.
+fn get_value<'a>(values: &'a TagValues, field: &str) -> Option<&'a String> {
+  return values.iter().find(| (name, _) | *name == field).and_then(| (_, value) | value.as_ref());
+}
+
+/// Sets the value of a field. Empty values clear it.
+fn set_value(values: &mut TagValues, field: &str, value: Option<String>) {
+  if let Some((_, current)) = values.iter_mut().find(| (name, _) | *name == field) {
+    *current = value.filter(| text | !text.is_empty());
+  }
+}
+
+/// Runs a text transform over the values of the provided fields.
+fn map_values(values: &mut TagValues, fields: &[String], transform: impl Fn(&str) -> String) {
+  for field in fields {
+    let new_value = get_value(values, field).map(| value | transform(value));
+
+    if new_value.is_some() {
+      set_value(values, field, new_value);
+    }
+  }
+}
+
+/// Runs the transforms over a file's values.
+fn apply_transforms(values: &mut TagValues, transforms: &[CompiledTransform], position: usize, file_count: usize) {
+  for transform in transforms {
+    match transform {
+      CompiledTransform::RegexReplace { fields, regex, replacement } => map_values(values, fields, | value | regex.replace_all(value, replacement.as_str()).into_owned()),
+      CompiledTransform::TitleCase { fields } => map_values(values, fields, to_title_case),
+      CompiledTransform::SentenceCase { fields } => map_values(values, fields, to_sentence_case),
+      CompiledTransform::TrimWhitespace { fields } => map_values(values, fields, trim_whitespace),
+      CompiledTransform::AutoNumber { start, set_total } => {
+        set_value(values, "trackNumber", Some((*start as usize + position).to_string()));
+
+        if *set_total {
+          set_value(values, "trackTotal", Some((*start as usize + file_count - 1).to_string()));
+        }
+      }
+      CompiledTransform::CopyField { from, to } => {
+        let value = get_value(values, from).cloned();
+        set_value(values, to, value);
+      }
+    }
+  }
+}
+
+/// Runs the transforms over a file, and gets the edits that would make its tags match.
+fn get_transform_edits(file_path: &str, transforms: &[CompiledTransform], position: usize, file_count: usize) -> Result<SongEditFields, FileError> {
+  let old_values = read_tag_values(file_path)?;
+  let mut new_values = old_values.clone();
+
+  apply_transforms(&mut new_values, transforms, position, file_count);
+
+  let mut edited_fields = SongEditFields::default();
+
+  // * Only the fields that changed are edited, so values the transforms didn't touch are written back exactly as they were.
+  for ((field, old_value), (_, new_value)) in old_values.into_iter().zip(new_values) {
+    if old_value != new_value {
+      edited_fields.set_from_text(field, new_value)?;
+    }
+  }
+
+  return Ok(edited_fields);
+}
+
+/// Runs batch transforms over a list of files, and gets the edits for each one.
+/// Files are numbered in the order they are provided.
+pub fn get_transform_changes(file_paths: &[String], transforms: &[TagTransform]) -> Vec<(String, Result<SongEditFields, FileError>)> {
+  let compiled_res = compile_transforms(transforms);
+
+  if compiled_res.is_err() {
+    let err = compiled_res.err().unwrap();
+    return file_paths.iter().map(| file_path | (file_path.to_owned(), Err(err.clone()))).collect();
+  }
+
+  let compiled = compiled_res.unwrap();
+
+  return file_paths.par_iter().enumerate().map(| (position, file_path) | {
+    return (file_path.to_owned(), get_transform_edits(file_path, &compiled, position, file_paths.len()));
+  }).collect();
+}
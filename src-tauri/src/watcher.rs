@@ -1,16 +1,52 @@
-use std::{path::Path, sync::{mpsc::{Receiver, Sender}, Arc}};
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::{mpsc::{Receiver, Sender}, Arc, RwLock}, time::{Duration, Instant}};
 
 use notify::{Config, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
 use tauri::{async_runtime::Mutex, AppHandle, Emitter};
 
+use crate::scan_rules::CompiledScanRules;
+
+/// How long changes the app made itself are ignored for.
+const SUPPRESS_DURATION: Duration = Duration::from_secs(5);
+
+/// The folders being watched and the scan rules, shared so events can be filtered the same way the scanner does.
+type WatchedFolders = Arc<RwLock<(Vec<String>, CompiledScanRules)>>;
+
+/// Paths the app changed itself, mapped to when they stop being ignored.
+type SuppressedPaths = Arc<RwLock<HashMap<PathBuf, Instant>>>;
+
 pub enum WatcherEvent {
-  Update(Vec<String>, Vec<String>),
+  Update(Vec<String>, CompiledScanRules),
+}
+
+/// Checks if a path was changed by the app itself recently.
+fn is_suppressed(suppressed: &SuppressedPaths, path: &PathBuf) -> bool {
+  if let Ok(suppressed_guard) = suppressed.read() {
+    return suppressed_guard.get(path).is_some_and(| until | *until > Instant::now());
+  }
+
+  return false;
+}
+
+/// Checks if any of the paths in a watcher event are part of the library, ignoring changes the app made itself.
+fn affects_library(watched: &WatchedFolders, suppressed: &SuppressedPaths, paths: &[PathBuf]) -> bool {
+  let paths: Vec<&PathBuf> = paths.iter().filter(| path | !is_suppressed(suppressed, path)).collect();
+
+  let watched_res = watched.read();
+
+  if let Ok(watched_guard) = watched_res {
+    let (folders, rules) = &*watched_guard;
+    return paths.iter().any(| path | rules.allows_path(folders, path));
+  }
+
+  return !paths.is_empty();
 }
 
 #[derive(Clone)]
 pub struct Watcher {
   receiver: Arc<Mutex<Receiver<WatcherEvent>>>,
-  sender: Sender<WatcherEvent>
+  sender: Sender<WatcherEvent>,
+  watched: WatchedFolders,
+  suppressed: SuppressedPaths,
 }
 
 impl Watcher {
@@ -20,7 +56,9 @@ impl Watcher {
     
     return Watcher {
       receiver: Arc::new(Mutex::new(receiver)),
-      sender
+      sender,
+      watched: Arc::new(RwLock::new((vec![], CompiledScanRules::default()))),
+      suppressed: Arc::new(RwLock::new(HashMap::new())),
     };
   }
 
@@ -33,6 +71,10 @@ impl Watcher {
     let (sender, receiver) = std::sync::mpsc::channel();
 
     let reciever_mutex = Arc::new(Mutex::new(receiver));
+
+    let watched = self.watched.clone();
+    let watched_clone = self.watched.clone();
+    let suppressed = self.suppressed.clone();
     
     // Create a thread for handling the folder watching.
     std::thread::spawn(move || {
@@ -50,7 +92,11 @@ impl Watcher {
 
         if let Ok(result) = event {
           match result {
-            WatcherEvent::Update(folders, _blacklisted_folders) => {
+            WatcherEvent::Update(folders, rules) => {
+              if let Ok(mut watched_guard) = watched_clone.write() {
+                *watched_guard = (folders.clone(), rules);
+              }
+
               // * Remove watchers from any paths that were removed.
               let folders_watching_loop = folders_watching.clone();
               for current_folder in folders_watching_loop {
@@ -87,10 +133,14 @@ impl Watcher {
           match res {
             Ok(event) => match event.kind {
                 EventKind::Create(_path) => {
-                  let _ = app.emit("music_folder_update", None::<String>);
+                  if affects_library(&watched, &suppressed, &event.paths) {
+                    let _ = app.emit("music_folder_update", None::<String>);
+                  }
                 },
                 EventKind::Remove(_path) => {
-                  let _ = app.emit("music_folder_update", None::<String>);
+                  if affects_library(&watched, &suppressed, &event.paths) {
+                    let _ = app.emit("music_folder_update", None::<String>);
+                  }
                 },
                 _ => {
                   
@@ -105,8 +155,25 @@ impl Watcher {
     });
   }
 
+  /// Gets the folders being watched and the scan rules they use.
+  pub fn get_watched(&self) -> (Vec<String>, CompiledScanRules) {
+    return self.watched.read().map(| watched_guard | watched_guard.clone()).unwrap_or_default();
+  }
+
+  /// Ignores changes to the provided paths for a short while, since the app made them itself.
+  pub fn suppress(&self, paths: &[PathBuf]) {
+    if let Ok(mut suppressed_guard) = self.suppressed.write() {
+      let now = Instant::now();
+      suppressed_guard.retain(| _, until | *until > now);
+
+      for path in paths {
+        suppressed_guard.insert(path.to_owned(), now + SUPPRESS_DURATION);
+      }
+    }
+  }
+
   /// Updates the paths being watched by the watcher.
-  pub fn update(&self, paths: Vec<String>, blacklist: Vec<String>) {
-    let _ = self.sender.send(WatcherEvent::Update(paths, blacklist));
+  pub fn update(&self, paths: Vec<String>, rules: CompiledScanRules) {
+    let _ = self.sender.send(WatcherEvent::Update(paths, rules));
   }
 }
\ No newline at end of file
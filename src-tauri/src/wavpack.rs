@@ -0,0 +1,196 @@
+use std::{fs::File, io::Read, path::Path};
+
+use symphonia::core::meta::{MetadataBuilder, MetadataRevision, StandardTagKey, StandardVisualKey, Tag, Value, Visual};
+
+use crate::{file_results::{FileError, FileErrorKind}, tag_trailers::ApeValue};
+
+/// How much of the start of a file is searched for the first block, to skip past any tag before it.
+const HEADER_SEARCH_SIZE: u64 = 1 << 20;
+/// The size of a block header.
+const BLOCK_HEADER_SIZE: usize = 32;
+/// The total sample count used when the length of the file isn't known.
+const UNKNOWN_SAMPLES: u32 = u32::MAX;
+
+/// Set when the block holds a single channel.
+const FLAG_MONO: u32 = 1 << 2;
+/// Set when the samples are 32 bit floats.
+const FLAG_FLOAT: u32 = 1 << 7;
+/// The bits of the flags that hold the sample rate index.
+const FLAG_SAMPLE_RATE_SHIFT: u32 = 23;
+const FLAG_SAMPLE_RATE_MASK: u32 = 0xf << FLAG_SAMPLE_RATE_SHIFT;
+
+/// The sample rates the flags can point to. Other rates are stored in a metadata sub-block.
+const SAMPLE_RATES: [u32; 15] = [6000, 8000, 9600, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000, 64000, 88200, 96000, 192000];
+
+/// The metadata sub-block ids read here.
+const ID_CHANNEL_INFO: u8 = 0x0d;
+const ID_SAMPLE_RATE: u8 = 0x27;
+/// The bits of a sub-block id that hold the id itself.
+const ID_MASK: u8 = 0x3f;
+/// Set when the sub-block's size is one byte less than its word count says.
+const ID_ODD_SIZE: u8 = 0x40;
+/// Set when the sub-block's size takes three bytes instead of one.
+const ID_LARGE: u8 = 0x80;
+
+/// The stream info in a WavPack file's first block.
+pub struct WavPackInfo {
+  pub sample_rate: u32,
+  pub channels: usize,
+  pub bits_per_sample: u32,
+  /// The number of samples per channel, if the encoder knew it.
+  pub total_samples: Option<u64>,
+}
+
+/// Reads a little endian u32 out of a buffer.
+fn read_u32(buffer: &[u8], position: usize) -> u32 {
+  return u32::from_le_bytes(buffer[position..position + 4].try_into().unwrap());
+}
+
+/// Reads the metadata sub-blocks after a block header, returning each one's id and data.
+fn read_sub_blocks(block: &[u8]) -> Vec<(u8, &[u8])> {
+  let mut sub_blocks = Vec::new();
+  let mut position = BLOCK_HEADER_SIZE;
+
+  while position + 2 <= block.len() {
+    let id = block[position];
+    let mut size = block[position + 1] as usize * 2;
+    let mut data_start = position + 2;
+
+    if id & ID_LARGE != 0 {
+      if position + 4 > block.len() {
+        break;
+      }
+
+      size = (block[position + 1] as usize | (block[position + 2] as usize) << 8 | (block[position + 3] as usize) << 16) * 2;
+      data_start = position + 4;
+    }
+
+    let data_size = if id & ID_ODD_SIZE != 0 { size.saturating_sub(1) } else { size };
+
+    if data_start + data_size > block.len() {
+      break;
+    }
+
+    sub_blocks.push((id & ID_MASK, &block[data_start..data_start + data_size]));
+    position = data_start + size;
+  }
+
+  return sub_blocks;
+}
+
+/// Reads the stream info out of the first block of a WavPack file.
+pub fn read_wavpack_info(file_path: &Path) -> Result<WavPackInfo, FileError> {
+  let mut buffer = Vec::new();
+  File::open(file_path)?.take(HEADER_SEARCH_SIZE).read_to_end(&mut buffer)?;
+
+  let start_res = buffer.windows(4).position(| window | window == b"wvpk");
+
+  if start_res.is_none() || start_res.unwrap() + BLOCK_HEADER_SIZE > buffer.len() {
+    return Err(FileError::new(FileErrorKind::UnsupportedFormat, String::from("The file doesn't have a WavPack block.")));
+  }
+
+  let start = start_res.unwrap();
+  let header = &buffer[start..start + BLOCK_HEADER_SIZE];
+
+  // * The block size doesn't count the id and size fields themselves.
+  let block_end = (start + 8 + read_u32(header, 4) as usize).min(buffer.len());
+  let block = &buffer[start..block_end];
+
+  let samples_low = read_u32(header, 12);
+  let total_samples = if samples_low == UNKNOWN_SAMPLES { None } else { Some(u64::from(header[11]) << 32 | u64::from(samples_low)) };
+  let flags = read_u32(header, 24);
+
+  let mut sample_rate = SAMPLE_RATES.get(((flags & FLAG_SAMPLE_RATE_MASK) >> FLAG_SAMPLE_RATE_SHIFT) as usize).copied().unwrap_or(0);
+  let mut channels = if flags & FLAG_MONO != 0 { 1 } else { 2 };
+  let bits_per_sample = if flags & FLAG_FLOAT != 0 { 32 } else { ((flags & 0b11) + 1) * 8 };
+
+  // * Multichannel files and uncommon sample rates store the real values in metadata sub-blocks.
+  for (id, data) in read_sub_blocks(block) {
+    match id {
+      ID_CHANNEL_INFO if !data.is_empty() => channels = data[0] as usize,
+      ID_SAMPLE_RATE if data.len() >= 3 => sample_rate = u32::from(data[0]) | u32::from(data[1]) << 8 | u32::from(data[2]) << 16,
+      _ => {}
+    }
+  }
+
+  return Ok(WavPackInfo {
+    sample_rate,
+    channels,
+    bits_per_sample,
+    total_samples,
+  });
+}
+
+/// Gets the standard key of an APE item. APE keys aren't case sensitive.
+fn get_ape_std_key(key: &str) -> Option<StandardTagKey> {
+  let std_key = match key.to_ascii_lowercase().as_str() {
+    "title" => StandardTagKey::TrackTitle,
+    "artist" => StandardTagKey::Artist,
+    "album" => StandardTagKey::Album,
+    "album artist" | "albumartist" => StandardTagKey::AlbumArtist,
+    "composer" => StandardTagKey::Composer,
+    "genre" => StandardTagKey::Genre,
+    "year" => StandardTagKey::Date,
+    "track" => StandardTagKey::TrackNumber,
+    "disc" => StandardTagKey::DiscNumber,
+    "comment" => StandardTagKey::Comment,
+    "compilation" => StandardTagKey::Compilation,
+    "artistsort" => StandardTagKey::SortArtist,
+    "albumsort" => StandardTagKey::SortAlbum,
+    "titlesort" => StandardTagKey::SortTrackTitle,
+    "bpm" => StandardTagKey::Bpm,
+    "lyrics" => StandardTagKey::Lyrics,
+    "rating" => StandardTagKey::Rating,
+    "musicbrainz_trackid" => StandardTagKey::MusicBrainzTrackId,
+    "musicbrainz_albumid" => StandardTagKey::MusicBrainzAlbumId,
+    "musicbrainz_artistid" => StandardTagKey::MusicBrainzArtistId,
+    "replaygain_track_gain" => StandardTagKey::ReplayGainTrackGain,
+    "replaygain_track_peak" => StandardTagKey::ReplayGainTrackPeak,
+    "replaygain_album_gain" => StandardTagKey::ReplayGainAlbumGain,
+    "replaygain_album_peak" => StandardTagKey::ReplayGainAlbumPeak,
+    _ => return None,
+  };
+
+  return Some(std_key);
+}
+
+/// Converts the items of an APE tag into symphonia metadata, so they get read the same way as every other format.
+/// Only the front cover is kept, and its item holds a file name and a null byte before the image.
+pub fn get_ape_metadata(items: Vec<(String, ApeValue)>) -> MetadataRevision {
+  let mut builder = MetadataBuilder::new();
+
+  for (key, value) in items {
+    match value {
+      ApeValue::Text(values) => {
+        for value in values {
+          builder.add_tag(Tag::new(get_ape_std_key(&key), &key, Value::from(value)));
+        }
+      }
+      ApeValue::Binary(data) => {
+        if !key.eq_ignore_ascii_case("Cover Art (Front)") {
+          continue;
+        }
+
+        let image_start = data.iter().position(| byte | *byte == 0).map(| position | position + 1).unwrap_or(0);
+        let image = &data[image_start..];
+        let format_res = image::guess_format(image);
+
+        if format_res.is_err() {
+          continue;
+        }
+
+        builder.add_visual(Visual {
+          media_type: format_res.unwrap().to_mime_type().to_owned(),
+          dimensions: None,
+          bits_per_pixel: None,
+          color_mode: None,
+          usage: Some(StandardVisualKey::FrontCover),
+          tags: vec![],
+          data: image.into(),
+        });
+      }
+    }
+  }
+
+  return builder.metadata();
+}
@@ -18,6 +18,7 @@ export { default as ProgressIndicator } from "./ProgressIndicator.svelte";
 export { default as SearchField } from "./SearchField.svelte";
 export { default as Select } from "./select/Select.svelte";
 export { default as Slider } from "./Slider.svelte";
+export { default as TextAreaField } from "./TextAreaField.svelte";
 export { default as TextField } from "./TextField.svelte";
 export { default as Toggle } from "./Toggle.svelte";
 export { default as ToggleShuffleButton } from "./ToggleShuffleButton.svelte";
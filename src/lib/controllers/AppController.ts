@@ -7,7 +7,7 @@ import { window } from "@tauri-apps/api";
 import type { UnlistenFn } from "@tauri-apps/api/event";
 import * as fs from "@tauri-apps/plugin-fs";
 import type { AlbumMetadata, ArtistMetadata, SongMetadata } from "@types";
-import { debounce, getAllArtistNames } from "@utils";
+import { debounce, getAllArtistNames, splitTagValues } from "@utils";
 import { get, type Unsubscriber } from "svelte/store";
 import { PlaybackController } from "./PlaybackController";
 import { DialogController } from "./utils/DialogController";
@@ -138,7 +138,7 @@ export class AppController {
             artist.songIds.push(song.id);
             
             if (song.album && song.albumArtist?.includes(artistName)) artist.albumNames.add(song.album);
-            if (song.genre) artist.genres.add(song.genre);
+            for (const genre of splitTagValues(song.genre)) artist.genres.add(genre);
     
             artistMap.set(artistName, artist);
           } else {
@@ -146,7 +146,7 @@ export class AppController {
             artist.songIds.push(song.id);
   
             if (song.album && song.albumArtist?.includes(artistName)) artist.albumNames.add(song.album);
-            if (song.genre) artist.genres.add(song.genre);
+            for (const genre of splitTagValues(song.genre)) artist.genres.add(genre);
             if (!artist.imagePath) artist.imagePath = song.artPath;
           }
         }
@@ -178,27 +178,29 @@ export class AppController {
     const genreMap = new Map<string, Genre>();
 
     for (const song of songs) {
-      const songGenre = song.genre ?? "Other";
+      const songGenres = song.genre ? splitTagValues(song.genre) : [ "Other" ];
 
-      if (!genreMap.get(songGenre)) {
-        const genre = new Genre(songGenre, song.artPath);
-        genre.songIds.push(song.id);
+      for (const songGenre of songGenres) {
+        if (!genreMap.get(songGenre)) {
+          const genre = new Genre(songGenre, song.artPath);
+          genre.songIds.push(song.id);
 
-        if (song.artist) {
-          for (const artist of getAllArtistNames(song.artist)) {
-            genre.artists.add(artist);
+          if (song.artist) {
+            for (const artist of getAllArtistNames(song.artist)) {
+              genre.artists.add(artist);
+            }
           }
-        }
 
-        genreMap.set(songGenre, genre);
-      } else {
-        const genre = genreMap.get(songGenre)!;
-        genre.songIds.push(song.id);
+          genreMap.set(songGenre, genre);
+        } else {
+          const genre = genreMap.get(songGenre)!;
+          genre.songIds.push(song.id);
 
-        if (!genre.imagePreviewPath) genre.imagePreviewPath = song.artPath;
-        if (song.artist) {
-          for (const artist of getAllArtistNames(song.artist)) {
-            genre.artists.add(artist);
+          if (!genre.imagePreviewPath) genre.imagePreviewPath = song.artPath;
+          if (song.artist) {
+            for (const artist of getAllArtistNames(song.artist)) {
+              genre.artists.add(artist);
+            }
           }
         }
       }
@@ -222,15 +224,16 @@ export class AppController {
     if (musicFolders.length === 0) {
       showEditMusicFolders.set(true);
     } else {
-      const songsJson = await RustInterop.readMusicFolders(musicFolders, blacklistedFolders, maxLength);
-      const loadedSongs: Song[] = songsJson.map((json) => {
+      const { entries } = await RustInterop.readMusicFolders(musicFolders, blacklistedFolders, maxLength);
+      const loadedSongs: Song[] = entries.map((json) => {
         const song = Song.fromJSON(json);
         const metadata = songsMetadataMap[song.id];
         
         if (metadata) {
           song.dateAdded = metadata.dateAdded ?? (new Date()).toISOString();
           song.lastPlayedOn = metadata.lastPlayedOn ?? "Never";
-          song.numTimesPlayed = metadata.numTimesPlayed ?? 0;
+          // * Play counts saved in the file by other players are kept if they're higher.
+          song.numTimesPlayed = Math.max(metadata.numTimesPlayed ?? 0, song.numTimesPlayed);
         }
 
         return song;
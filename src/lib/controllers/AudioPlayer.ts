@@ -1,7 +1,8 @@
 // playback::ipc::get_audio_devices,
 // playback::ipc::set_audio_device,
 
-import { autoPlayOnConnect, connectedDevices, isPaused, playingSongId, selectedDevice, shouldPauseOnEnd, songProgress, songsMap, volumeLevel } from "@stores/State";
+import { t } from "@stores/Locale";
+import { autoPlayOnConnect, connectedDevices, isPaused, playingSongId, selectedDevice, shouldPauseOnEnd, showErrorSnackbar, songProgress, songsMap, volumeLevel } from "@stores/State";
 import { window } from "@tauri-apps/api";
 import { invoke } from "@tauri-apps/api/core";
 import type { UnlistenFn } from "@tauri-apps/api/event";
@@ -19,6 +20,7 @@ export class AudioPlayer {
   private static backendPlayingUnsub: Promise<UnlistenFn>;
   private static backendPausedUnsub: Promise<UnlistenFn>;
   private static deviceChangesUnsub: Promise<UnlistenFn>;
+  private static unplayableUnsub: Promise<UnlistenFn>;
 
   private static playingSongIdUnsub: Unsubscriber;
   private static isPausedUnsub: Unsubscriber;
@@ -49,10 +51,17 @@ export class AudioPlayer {
       isPaused.set(true);
     });
 
+    // * Some formats that get scanned, like Opus and WavPack, can't be decoded yet.
+    this.unplayableUnsub = currentWindow.listen("unplayable", (event) => {
+      console.log("couldn't play:", event.payload);
+      isPaused.set(true);
+      get(showErrorSnackbar)({ message: get(t)("UNPLAYABLE_FORMAT_MESSAGE") });
+    });
+
     this.playingSongIdUnsub = playingSongId.subscribe((id) => {
       if (id !== "") {
         const song = get(songsMap)[id];
-        const loadPromise = AudioPlayer.loadFile(song.filePath, get(songProgress));
+        const loadPromise = AudioPlayer.loadFile(song.filePath, get(songProgress), song.replayGain, song.startMs, song.endMs);
 
         if (get(shouldPauseOnEnd)) {
           isPaused.set(true);
@@ -108,14 +117,25 @@ export class AudioPlayer {
     const deviceChangeUnlisten = await this.deviceChangesUnsub;
     if (deviceChangeUnlisten) deviceChangeUnlisten();
 
+    const unplayableUnlisten = await this.unplayableUnsub;
+    if (unplayableUnlisten) unplayableUnlisten();
+
     if (this.playingSongIdUnsub) this.playingSongIdUnsub();
     if (this.isPausedUnsub) this.isPausedUnsub();
     if (this.volumeLevelUnsub) this.volumeLevelUnsub();
     if (this.selectedDeviceUnsub) this.selectedDeviceUnsub();
   }
 
-  static async loadFile(filePath: string, position?: number) {
-    return invoke<void>("load_file", { filePath: filePath, position: position ?? 0 });
+  static async loadFile(filePath: string, position?: number, replayGain?: ReplayGain, startMs?: number, endMs?: number) {
+    return invoke<void>("load_file", { filePath: filePath, position: position ?? 0, replayGain: replayGain ?? null, startMs: startMs ?? null, endMs: endMs ?? null });
+  }
+
+  /**
+   * Sets how playback volume is normalized using the songs' ReplayGain info.
+   * @param mode The normalization mode.
+   */
+  static async setNormalization(mode: NormalizationMode) {
+    return invoke<void>("set_normalization", { mode: mode });
   }
 
   static play() {
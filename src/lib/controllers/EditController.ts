@@ -1,30 +1,55 @@
 import { Album, Song } from "@models";
 import { desktopSidePanel, sidePanelProps, SidePanels } from "@stores/Layout";
 import { t as translate } from "@stores/Locale";
-import { albums, history, nowPlayingList, playingSongId, playlists, queue, showErrorSnackbar, showInfoSnackbar, songs, songsMap } from "@stores/State";
+import { albums, blacklistedFolders, history, musicDirectories, nowPlayingList, playingSongId, playlists, queue, showErrorSnackbar, showInfoSnackbar, songs, songsMap } from "@stores/State";
 import { get } from "svelte/store";
 import { bulkEditSongIds } from "../../stores/Select";
-import { backFromSidePanel } from "../utils";
+import { backFromSidePanel, splitTagValues } from "../utils";
 import { AppController } from "./AppController";
 import { QueueController } from "./QueueController";
+import { SettingsController } from "./utils/SettingsController";
 import { DialogController } from "./utils/DialogController";
 import { LogController } from "./utils/LogController";
 import { RustInterop } from "./utils/RustInterop";
 
+/**
+ * Maps the edit fields to the song properties that display them, when their names differ.
+ */
+const EDIT_FIELD_SONG_KEYS: Record<string, string> = {
+  "artists": "artist",
+  "composers": "composer",
+  "genres": "genre",
+  "trackTotal": "totalTracks"
+};
+
+/**
+ * Edit fields the song doesn't keep. Play counts are written from numTimesPlayed, and lyrics are read on demand.
+ */
+const UNTRACKED_EDIT_FIELDS = ["playCount", "lyrics", "syncedLyrics"];
+
+/**
+ * Edit fields that describe a single track. Cue sheet tracks share their file, so these can't be written for them.
+ */
+const CUE_TRACK_EDIT_FIELDS: TransformField[] = ["title", "artists", "composers", "trackNumber", "trackTotal", "sortTitle", "bpm", "rating", "lyrics", "syncedLyrics", "playCount", "musicBrainzTrackId"];
+
 /**
  * The controller for editing music, albums and artists.
  */
 export class EditController {
   /**
-   * Updates the provided song based on the edited fields.
+   * Updates the provided song based on the edited fields. Fields that are undefined are kept, and null ones are cleared.
    * @param original The original song.
    * @param editFields The edited fields.
    */
   private static editSongFields(original: Song, editFields: SongEditFields): void {
     for (const key of Object.keys(editFields)) {
-      const songKey = key as keyof Song;
+      const songKey = (EDIT_FIELD_SONG_KEYS[key] ?? key) as keyof Song;
       let newValue = editFields[key as keyof SongEditFields];
+
+      if (newValue === undefined || UNTRACKED_EDIT_FIELDS.includes(key)) continue;
+      if (newValue === null) newValue = undefined;
       
+      if (Array.isArray(newValue)) newValue = newValue.length ? newValue.join("; ") : undefined;
       if (key === "releaseYear" && !newValue) newValue = -1;
       if (key === "title" && !newValue) newValue = original.fileName;
 
@@ -33,6 +58,25 @@ export class EditController {
     }
   }
 
+  /**
+   * Logs the files that failed, and gets the paths of the ones that succeeded.
+   * @param results The outcome of each file.
+   * @returns The paths of the files that succeeded.
+   */
+  private static getSucceededPaths(results: (FileResult | OrganizeResult)[]): Set<string> {
+    const succeeded = new Set<string>();
+
+    for (const result of results) {
+      if (result.error) {
+        LogController.error(`Failed to update ${result.path} (${result.error.kind}): ${result.error.message}`);
+      } else {
+        succeeded.add(result.path);
+      }
+    }
+
+    return succeeded;
+  }
+
   /**
    * Updates the provided album based on the edited fields.
    * @param original The original album.
@@ -47,6 +91,37 @@ export class EditController {
     }
   }
 
+  /**
+   * Checks if edits would change a single track's fields for any cue sheet track, and shows an error if so.
+   * @param editedSongs The songs being edited.
+   * @param fields The fields being edited.
+   * @returns Whether the edits touch a cue sheet track's own fields.
+   */
+  private static rejectCueTrackEdits(editedSongs: Song[], fields: string[]): boolean {
+    const editsTrackFields = fields.some((field) => CUE_TRACK_EDIT_FIELDS.includes(field as TransformField));
+    if (!editsTrackFields || !editedSongs.some((song) => song.cueTrack !== undefined)) return false;
+
+    get(showErrorSnackbar)({ message: get(translate)("CUE_TRACK_FIELDS_MESSAGE") });
+    return true;
+  }
+
+  /**
+   * Gets the fields a list of transforms would change.
+   * @param transforms The transforms to run.
+   */
+  private static getTransformedFields(transforms: TagTransform[]): TransformField[] {
+    return transforms.flatMap((transform): TransformField[] => {
+      switch (transform.type) {
+        case "autoNumber":
+          return transform.setTotal ? ["trackNumber", "trackTotal"] : ["trackNumber"];
+        case "copyField":
+          return [transform.to];
+        default:
+          return transform.fields;
+      }
+    });
+  }
+
   /**
    * Applies the changes made to a song, and updates the artist/album (or creates/deletes them) as needed.
    * @param original The original song.
@@ -54,11 +129,14 @@ export class EditController {
    */
   static async editSong(original: Song, editedFields: SongEditFields) {
     const t = get(translate);
+    const editedKeys = Object.keys(editedFields).filter((key) => editedFields[key as keyof SongEditFields] !== undefined);
+    if (this.rejectCueTrackEdits([original], editedKeys)) return;
+
     const changes: Record<string, SongEditFields> = {};
     changes[original.filePath] = editedFields;
-    const success = await RustInterop.writeMusicFiles(changes);
+    const results = await RustInterop.writeMusicFiles(changes, this.getWriteSettings());
     
-    if (success) {
+    if (this.getSucceededPaths(results).has(original.filePath)) {
       this.editSongFields(original, editedFields);
       
       const songsList = get(songs);
@@ -85,11 +163,18 @@ export class EditController {
     const songIds = Object.keys(songPaths);
 
     const songMap = get(songsMap);
-    const success = await RustInterop.writeMusicFiles(changes);
+    const editedKeys = Object.values(changes).flatMap((change) => Object.keys(change).filter((key) => change[key as keyof SongEditFields] !== undefined));
+    if (this.rejectCueTrackEdits(songIds.map((id) => songMap[id]), editedKeys)) return;
 
-    if (success) {
+    const results = await RustInterop.writeMusicFiles(changes, this.getWriteSettings());
+    const succeededPaths = this.getSucceededPaths(results);
+
+    if (succeededPaths.size > 0) {
+      // * Only the songs that were written get updated, so failed ones still show what's on disk.
       for (const id of songIds) {
         const song = songMap[id];
+        if (!succeededPaths.has(song.filePath)) continue;
+
         const change = changes[song.filePath];
 
         this.editSongFields(song, change);
@@ -102,10 +187,15 @@ export class EditController {
       AppController.loadArtistsFromSongs(songsList);
       AppController.loadGenresFromSongs(songsList);
 
+      LogController.log(`Finished writing edits to ${succeededPaths.size} songs`);
+    }
+
+    const failedCount = results.length - succeededPaths.size;
+
+    if (failedCount === 0) {
       get(showInfoSnackbar)({ message: t("FINISHED_WRITING_CHANGES_MESSAGE") });
-      LogController.log(`Finished writing edits to ${songIds.length} songs`);
     } else {
-      get(showErrorSnackbar)({ message: t("FAILED_WRITING_CHANGES_MESSAGE") });
+      get(showErrorSnackbar)({ message: `${t("FAILED_WRITING_CHANGES_MESSAGE")} (${failedCount}/${results.length})` });
     }
   }
 
@@ -122,8 +212,8 @@ export class EditController {
       let albumToEdit = original;
       const albumsList = get(albums);
 
-      if (changedAlbumFields.artPath) {
-        const copiedPath = await this.copyAlbumImage(changedAlbumFields.artPath, original.name);
+      if (changedAlbumFields.artPath && changedAlbumFields.artPath !== original.artPath) {
+        const copiedPath = await this.copyAlbumImage(changedAlbumFields.artPath, original.name, original.albumArtist);
         changedAlbumFields.artPath = copiedPath;
       }
 
@@ -139,21 +229,18 @@ export class EditController {
 
       for (const id of original.songIds) {
         const song = songMap[id];
+        // * The song's own fields are left out, so they're kept as they are.
         changes[song.filePath] = {
-          "artPath": changedAlbumFields.artPath,
-          "title": song.title,
+          "artPath": changedAlbumFields.artPath !== original.artPath ? changedAlbumFields.artPath : undefined,
           "album": changedAlbumFields.name,
-          "composer": song.composer,
-          "albumArtist": changedAlbumFields.albumArtist,
-          "artist": song.artist,
-          "releaseYear": changedAlbumFields.releaseYear,
-          "genre": changedAlbumFields.genre,
-          "trackNumber": song.trackNumber
+          "albumArtist": changedAlbumFields.albumArtist ?? null,
+          "releaseYear": changedAlbumFields.releaseYear ?? null,
+          "genres": splitTagValues(changedAlbumFields.genre)
         };
       }
       
-      const success = await RustInterop.writeMusicFiles(changes);
-      if (success) {
+      const results = await RustInterop.writeMusicFiles(changes, this.getWriteSettings());
+      if (this.getSucceededPaths(results).size === results.length) {
         this.editAlbumFields(albumToEdit, changedAlbumFields);
         await albumToEdit.setBackgroundFromImage();
 
@@ -161,11 +248,11 @@ export class EditController {
           const song = songMap[id];
           const change = changes[song.filePath];
 
-          song.album = change.album;
-          song.artPath = change.artPath;
-          song.albumArtist = change.albumArtist;
+          song.album = change.album ?? undefined;
+          if (change.artPath) song.artPath = change.artPath;
+          song.albumArtist = change.albumArtist ?? undefined;
           song.releaseYear = change.releaseYear ?? -1;
-          song.genre = change.genre;
+          song.genre = change.genres?.length ? change.genres.join("; ") : undefined;
         }
 
         const songsList = get(songs);
@@ -194,14 +281,20 @@ export class EditController {
   }
 
   /**
-   * Deletes the provided songs from the device.
-   * @param songIds The ids of the songs to delete.
+   * Deletes the provided songs from the device, along with any cue sheet tracks that share their files.
+   * @param selectedIds The ids of the songs to delete.
    */
-  static async deleteSongsFromDevice(songIds: string[]) {
+  static async deleteSongsFromDevice(selectedIds: string[]) {
     const t = get(translate);
+
+    // * Cue sheet tracks share their file, so deleting one deletes every track in it.
+    const selectedPaths = new Set(selectedIds.map((id) => get(songsMap)[id].filePath));
+    const songIds = get(songs).filter((song) => selectedPaths.has(song.filePath)).map((song) => song.id);
+    
     const numSongsMessage = `${songIds.length} ${songIds.length === 1 ? t("SONG_SINGULAR_VALUE") : t("SONG_PLURAL_VALUE")}`;
+    const confirmMessage = songIds.length > selectedIds.length ? t("CONFIRM_DELETE_CUE_TRACKS_MESSAGE") : t("CONFIRM_DELETE_MESSAGE");
 
-    DialogController.ask(t("CANT_BE_UNDONE_TITLE"), `${t("CONFIRM_DELETE_MESSAGE")} ${numSongsMessage}?`, t("YES_ACTION"), t("NO_ACTION")).then(async (shouldContinue) => {
+    DialogController.ask(t("CAN_BE_UNDONE_TITLE"), `${confirmMessage} ${numSongsMessage}?`, t("YES_ACTION"), t("NO_ACTION")).then(async (shouldContinue) => {
       if (shouldContinue) {
         const panel = get(desktopSidePanel);
         const panelProps = get(sidePanelProps);
@@ -248,23 +341,259 @@ export class EditController {
         queue.set(songQueue);
         history.set(songHistory);
         
-        const successPromise = RustInterop.deleteSongs(filePaths);
+        const resultsPromise = RustInterop.deleteSongs(filePaths);
 
         AppController.loadAlbumsFromSongs(songList);
         AppController.loadArtistsFromSongs(songList);
         AppController.loadGenresFromSongs(songList);
         
-        const success = await successPromise;
+        const results = await resultsPromise;
+        const failedCount = results.length - this.getSucceededPaths(results).size;
 
-        if (success) {
+        if (failedCount === 0) {
           get(showInfoSnackbar)({ message: numSongsMessage + " " + t("DELETED_VALUE") });
         } else {
-          get(showErrorSnackbar)({ message: t("FAILED_DELETE_SELECTED_MESSAGE") + " " + t("SONG_PLURAL_VALUE") })
+          get(showErrorSnackbar)({ message: `${t("FAILED_DELETE_SELECTED_MESSAGE")} ${t("SONG_PLURAL_VALUE")} (${failedCount}/${results.length})` });
         }
       }
     });
   }
 
+  /**
+   * Gets how tags should be saved, from the user's settings.
+   */
+  private static getWriteSettings(): TagWriteSettings {
+    return SettingsController.getSetting<TagWriteSettings>("tagWriting");
+  }
+
+  /**
+   * Gets the file paths of the provided songs, in order, without duplicates from cue sheet tracks.
+   * @param songIds The ids of the songs.
+   */
+  private static getUniqueFilePaths(songIds: string[]): string[] {
+    const songMap = get(songsMap);
+    return [...new Set(songIds.map((id) => songMap[id].filePath))];
+  }
+
+  /**
+   * Points the songs whose files moved at their new paths, keeping their stats and playlist spots.
+   * @param results The outcome of each move.
+   * @returns The number of files that moved.
+   */
+  private static applyFileMoves(results: OrganizeResult[]): number {
+    const newPaths: Record<string, string> = {};
+    for (const result of results) {
+      if (!result.error && result.newPath && result.newPath !== result.path) newPaths[result.path] = result.newPath;
+    }
+
+    // * Song ids come from their file names, so everything referencing a moved song needs its new id.
+    const newIds: Record<string, string> = {};
+    const songList = get(songs);
+
+    for (const song of songList) {
+      const newPath = newPaths[song.filePath];
+      if (!newPath) continue;
+
+      const oldId = song.id;
+      song.setFilePath(newPath);
+      newIds[oldId] = song.id;
+    }
+
+    const playlistList = get(playlists);
+    for (const playlist of playlistList) {
+      playlist.songIds = playlist.songIds.map((id) => newIds[id] ?? id);
+    }
+
+    const nowPlayingSongId = get(playingSongId);
+    if (newIds[nowPlayingSongId]) playingSongId.set(newIds[nowPlayingSongId]);
+
+    songs.set(songList);
+    playlists.set(playlistList);
+    queue.set(get(queue).map((id) => newIds[id] ?? id));
+    history.set(get(history).map((id) => newIds[id] ?? id));
+
+    AppController.loadAlbumsFromSongs(songList);
+    AppController.loadArtistsFromSongs(songList);
+    AppController.loadGenresFromSongs(songList);
+
+    return Object.keys(newPaths).length;
+  }
+
+  /**
+   * Shows how moving files went.
+   * @param results The outcome of each move.
+   */
+  private static showFileMoveResults(results: OrganizeResult[]) {
+    const t = get(translate);
+    const failedCount = results.length - this.getSucceededPaths(results).size;
+
+    if (failedCount === 0) {
+      get(showInfoSnackbar)({ message: t("FINISHED_ORGANIZING_FILES_MESSAGE") });
+    } else {
+      get(showErrorSnackbar)({ message: `${t("FAILED_ORGANIZING_FILES_MESSAGE")} (${failedCount}/${results.length})` });
+    }
+  }
+
+  /**
+   * Moves and renames the files of the provided songs to match a path template, keeping their stats and playlist spots.
+   * @param template The path template, relative to the music folder, using fields like %albumartist% and %title%.
+   * @param songIds The ids of the songs to organize.
+   * @param dryRun Whether to only plan the moves without making them.
+   * @returns The outcome of each file.
+   */
+  static async organizeFiles(template: string, songIds: string[], dryRun: boolean): Promise<OrganizeResult[]> {
+    const results = await RustInterop.organizeFiles(template, this.getUniqueFilePaths(songIds), dryRun);
+    if (dryRun) return results;
+
+    const movedCount = this.applyFileMoves(results);
+    LogController.log(`Organized ${movedCount} files.`);
+    this.showFileMoveResults(results);
+
+    return results;
+  }
+
+  /**
+   * Renames the files of the provided songs in place to match a file name template, keeping their stats and playlist spots.
+   * @param template The file name template, using fields like %track% and %title%.
+   * @param songIds The ids of the songs to rename.
+   * @param dryRun Whether to only plan the renames without making them.
+   * @returns The outcome of each file.
+   */
+  static async renameFiles(template: string, songIds: string[], dryRun: boolean): Promise<OrganizeResult[]> {
+    const results = await RustInterop.renameFiles(template, this.getUniqueFilePaths(songIds), dryRun);
+    if (dryRun) return results;
+
+    const renamedCount = this.applyFileMoves(results);
+    LogController.log(`Renamed ${renamedCount} files.`);
+    this.showFileMoveResults(results);
+
+    return results;
+  }
+
+  /**
+   * Previews the tags parsing the provided songs' file paths would set, without saving anything.
+   * @param pattern The pattern to match, like "%track% - %artist% - %title%". Folders in it match the folders the files are in.
+   * @param songIds The ids of the songs to tag.
+   * @returns What would change in each file.
+   */
+  static async previewTagsFromNames(pattern: string, songIds: string[]): Promise<FilePreview[]> {
+    return await RustInterop.previewTagsFromNames(pattern, this.getUniqueFilePaths(songIds), this.getWriteSettings());
+  }
+
+  /**
+   * Tags the provided songs with values parsed from their file paths, and reloads the library to pick up the new tags.
+   * @param pattern The pattern to match, like "%track% - %artist% - %title%". Folders in it match the folders the files are in.
+   * @param songIds The ids of the songs to tag.
+   * @returns The outcome of each file.
+   */
+  static async tagSongsFromNames(pattern: string, songIds: string[]): Promise<FileResult[]> {
+    const t = get(translate);
+    const results = await RustInterop.tagFilesFromNames(pattern, this.getUniqueFilePaths(songIds), this.getWriteSettings());
+    const failedCount = results.length - this.getSucceededPaths(results).size;
+
+    LogController.log(`Tagged ${results.length - failedCount} files from their names.`);
+    await AppController.loadSongs(get(musicDirectories), get(blacklistedFolders));
+
+    if (failedCount === 0) {
+      get(showInfoSnackbar)({ message: t("FINISHED_TAGGING_FROM_NAMES_MESSAGE") });
+    } else {
+      get(showErrorSnackbar)({ message: `${t("FAILED_TAGGING_FROM_NAMES_MESSAGE")} (${failedCount}/${results.length})` });
+    }
+
+    return results;
+  }
+
+  /**
+   * Previews the changes batch tag transforms would make to the provided songs, without saving anything.
+   * @param songIds The ids of the songs to transform. Tracks are numbered in this order.
+   * @param transforms The transforms to run, in order.
+   * @returns What would change in each file.
+   */
+  static async previewSongTransforms(songIds: string[], transforms: TagTransform[]): Promise<FilePreview[]> {
+    return await RustInterop.previewMusicFileTransforms(transforms, this.getUniqueFilePaths(songIds), this.getWriteSettings());
+  }
+
+  /**
+   * Runs batch tag transforms over the provided songs, and reloads the library to pick up the new tags.
+   * @param songIds The ids of the songs to transform. Tracks are numbered in this order.
+   * @param transforms The transforms to run, in order.
+   * @returns The outcome of each file.
+   */
+  static async transformSongs(songIds: string[], transforms: TagTransform[]): Promise<FileResult[]> {
+    const t = get(translate);
+    const songMap = get(songsMap);
+    if (this.rejectCueTrackEdits(songIds.map((id) => songMap[id]), this.getTransformedFields(transforms))) return [];
+
+    const results = await RustInterop.transformMusicFiles(transforms, this.getUniqueFilePaths(songIds), this.getWriteSettings());
+    const failedCount = results.length - this.getSucceededPaths(results).size;
+
+    LogController.log(`Transformed the tags of ${results.length - failedCount} files.`);
+    await AppController.loadSongs(get(musicDirectories), get(blacklistedFolders));
+
+    if (failedCount === 0) {
+      get(showInfoSnackbar)({ message: t("FINISHED_TRANSFORMING_TAGS_MESSAGE") });
+    } else {
+      get(showErrorSnackbar)({ message: `${t("FAILED_TRANSFORMING_TAGS_MESSAGE")} (${failedCount}/${results.length})` });
+    }
+
+    return results;
+  }
+
+  /**
+   * Saves how many times the provided songs have been played into their files, so the counts carry over to other players.
+   * Nothing is saved if play counts are turned off in the tag writing settings.
+   * @param songIds The ids of the songs to save play counts for.
+   * @returns The outcome of each file.
+   */
+  static async syncPlayCounts(songIds: string[]): Promise<FileResult[]> {
+    const t = get(translate);
+    const settings = this.getWriteSettings();
+
+    if (settings.playCounts === "off") return [];
+
+    const songMap = get(songsMap);
+    const changes: Record<string, SongEditFields> = {};
+
+    // * Cue sheet tracks share a file, so a single play count can't describe them.
+    for (const id of songIds) {
+      const song = songMap[id];
+      if (song.cueTrack !== undefined) continue;
+
+      changes[song.filePath] = { playCount: song.numTimesPlayed };
+    }
+
+    const results = await RustInterop.writeMusicFiles(changes, settings);
+    const failedCount = results.length - this.getSucceededPaths(results).size;
+
+    LogController.log(`Saved the play counts of ${results.length - failedCount} files.`);
+
+    if (failedCount === 0) {
+      get(showInfoSnackbar)({ message: t("FINISHED_SYNCING_PLAY_COUNTS_MESSAGE") });
+    } else {
+      get(showErrorSnackbar)({ message: `${t("FAILED_SYNCING_PLAY_COUNTS_MESSAGE")} (${failedCount}/${results.length})` });
+    }
+
+    return results;
+  }
+
+  /**
+   * Undoes the last tag edit or song deletion, and reloads the library to pick up the restored files.
+   */
+  static async undoLastOperation() {
+    const t = get(translate);
+    const operation = await RustInterop.undoLastOperation();
+
+    if (!operation) {
+      get(showInfoSnackbar)({ message: t("NOTHING_TO_UNDO_MESSAGE") });
+      return;
+    }
+
+    LogController.log(`Undid ${operation.kind} of ${operation.entries.length} files.`);
+    await AppController.loadSongs(get(musicDirectories), get(blacklistedFolders));
+
+    get(showInfoSnackbar)({ message: t("UNDID_LAST_CHANGE_MESSAGE") });
+  }
+
   /**
    * Deletes the provided albums from the device.
    * @param albumNames The names of the albums to delete.
@@ -273,7 +602,7 @@ export class EditController {
     const t = get(translate);
     const numSongsMessage = `${albumNames.length} ${albumNames.length === 1 ? t("ALBUM_SINGULAR_VALUE") : t("ALBUM_PLURAL_VALUE")}`;
 
-    DialogController.ask(t("CANT_BE_UNDONE_TITLE"), `${t("CONFIRM_DELETE_MESSAGE")} ${numSongsMessage}?`, t("YES_ACTION"), t("NO_ACTION")).then(async (shouldContinue) => {
+    DialogController.ask(t("CAN_BE_UNDONE_TITLE"), `${t("CONFIRM_DELETE_MESSAGE")} ${numSongsMessage}?`, t("YES_ACTION"), t("NO_ACTION")).then(async (shouldContinue) => {
       if (shouldContinue) {
         if (get(desktopSidePanel) === SidePanels.ALBUM_EDIT && albumNames.includes(get(sidePanelProps).key)) backFromSidePanel();
 
@@ -324,18 +653,19 @@ export class EditController {
         queue.set(songQueue);
         history.set(songHistory);
         
-        const successPromise = RustInterop.deleteSongs(filePaths);
+        const resultsPromise = RustInterop.deleteSongs(filePaths);
 
         AppController.loadAlbumsFromSongs(songList);
         AppController.loadArtistsFromSongs(songList);
         AppController.loadGenresFromSongs(songList);
         
-        const success = await successPromise;
+        const results = await resultsPromise;
+        const failedCount = results.length - this.getSucceededPaths(results).size;
 
-        if (success) {
+        if (failedCount === 0) {
           get(showInfoSnackbar)({ message: numSongsMessage + " " + t("DELETED_VALUE") });
         } else {
-          get(showErrorSnackbar)({ message: t("FAILED_DELETE_SELECTED_MESSAGE") + " " + t("ALBUM_PLURAL_VALUE") });
+          get(showErrorSnackbar)({ message: `${t("FAILED_DELETE_SELECTED_MESSAGE")} ${t("SONG_PLURAL_VALUE")} (${failedCount}/${results.length})` });
         }
       }
     });
@@ -377,13 +707,14 @@ export class EditController {
    * Copies the provided image to the "albums" directory.
    * @param imagePath The image path to copy.
    * @param albumName The name of the album.
+   * @param albumArtist The album's artist, if it has one.
    * @returns The image path, or undefined if it was undefined.
    */
-  static async copyAlbumImage(imagePath: string | undefined, albumName: string): Promise<string | undefined> {
+  static async copyAlbumImage(imagePath: string | undefined, albumName: string, albumArtist?: string): Promise<string | undefined> {
     const t = get(translate);
     if (!imagePath) return undefined;
     
-    const result = await RustInterop.copyAlbumsImage(imagePath, albumName);
+    const result = await RustInterop.copyAlbumsImage(imagePath, albumName, albumArtist);
     if (result === "") {
       get(showErrorSnackbar)({ message: t("INVALID_IMAGE_MESSAGE"), faster: true })
       return undefined;
@@ -66,13 +66,31 @@ export class RustInterop {
    * @param folders The folders to read.
    * @param blacklist The blacklisted folders.
    * @param maxLength The max length of songs to include.
-   * @returns The list of songs found.
+   * @param scanRules The optional include/exclude rules to scan with.
+   * @returns The list of songs found, and the files that changed since the last scan.
    */
-  static async readMusicFolders(folders: string[], blacklist: string[], maxLength: number): Promise<any[]> {
-    const results = await invoke<string>("read_music_folders", { musicFolderPathsStr: JSON.stringify(folders), blacklistFolderPathsStr: JSON.stringify(blacklist), maxLength: maxLength });
+  static async readMusicFolders(folders: string[], blacklist: string[], maxLength: number, scanRules?: ScanRules): Promise<LibraryScan> {
+    const results = await invoke<string>("read_music_folders", { musicFolderPathsStr: JSON.stringify(folders), blacklistFolderPathsStr: JSON.stringify(blacklist), maxLength: maxLength, scanRulesStr: scanRules ? JSON.stringify(scanRules) : null });
     return JSON.parse(results);
   }
 
+  /**
+   * Cancels the running library scan.
+   * The running `readMusicFolders` call will resolve with the songs read so far.
+   */
+  static async cancelLibraryScan(): Promise<void> {
+    await invoke("cancel_library_scan", {});
+  }
+
+  /**
+   * Gets the embedded or sidecar lyrics of a song.
+   * @param filePath The path of the song.
+   * @returns The lyrics, or null if the song doesn't have any.
+   */
+  static async getLyrics(filePath: string): Promise<Lyrics | null> {
+    return await invoke<Lyrics | null>("get_lyrics", { filePath: filePath });
+  }
+
   /**
    * Gets the two primary colors from an image.
    * @param imagePath The path to the image.
@@ -87,10 +105,11 @@ export class RustInterop {
    * Copies the provided image to the "albums" cache directory.
    * @param imagePath The image path to copy.
    * @param albumName The name of the album.
+   * @param albumArtist The album's artist, if it has one.
    * @returns The resulting path.
    */
-  static async copyAlbumsImage(imagePath: string, albumName: string): Promise<string> {
-    return await invoke<string>("copy_album_image", { imagePath: imagePath, albumName: albumName });
+  static async copyAlbumsImage(imagePath: string, albumName: string, albumArtist?: string): Promise<string> {
+    return await invoke<string>("copy_album_image", { imagePath: imagePath, albumName: albumName, albumArtist: albumArtist ?? null });
   }
 
   /**
@@ -114,17 +133,112 @@ export class RustInterop {
   /**
    * Writes the provided changes to the music files.
    * @param changes The changes to write.
+   * @param settings How the tags get saved.
+   * @returns The outcome of each file.
+   */
+  static async writeMusicFiles(changes: Record<string, SongEditFields>, settings: TagWriteSettings): Promise<FileResult[]> {
+    return await invoke<FileResult[]>("write_music_files", { changesStr: JSON.stringify(changes), writeSettingsStr: JSON.stringify(settings) });
+  }
+
+  /**
+   * Previews the changes writing would make to the music files, without saving anything.
+   * @param changes The changes to preview.
+   * @param settings How the tags would get saved.
+   * @returns What would change in each file.
+   */
+  static async previewMusicFileChanges(changes: Record<string, SongEditFields>, settings: TagWriteSettings): Promise<FilePreview[]> {
+    return await invoke<FilePreview[]>("preview_music_file_changes", { changesStr: JSON.stringify(changes), writeSettingsStr: JSON.stringify(settings) });
+  }
+
+  /**
+   * Runs batch tag transforms over the provided music files, in order, and writes the results.
+   * @param transforms The transforms to run.
+   * @param paths The paths of the files to transform. Tracks are numbered in this order.
+   * @param settings How the tags get saved.
+   * @returns The outcome of each file.
+   */
+  static async transformMusicFiles(transforms: TagTransform[], paths: string[], settings: TagWriteSettings): Promise<FileResult[]> {
+    return await invoke<FileResult[]>("transform_music_files", { transformsStr: JSON.stringify(transforms), filePathsStr: JSON.stringify(paths), writeSettingsStr: JSON.stringify(settings) });
+  }
+
+  /**
+   * Previews the changes batch tag transforms would make to the provided music files, without saving anything.
+   * @param transforms The transforms to preview.
+   * @param paths The paths of the files to transform. Tracks are numbered in this order.
+   * @param settings How the tags would get saved.
+   * @returns What would change in each file.
+   */
+  static async previewMusicFileTransforms(transforms: TagTransform[], paths: string[], settings: TagWriteSettings): Promise<FilePreview[]> {
+    return await invoke<FilePreview[]>("preview_music_file_transforms", { transformsStr: JSON.stringify(transforms), filePathsStr: JSON.stringify(paths), writeSettingsStr: JSON.stringify(settings) });
+  }
+
+  /**
+   * Tags the provided music files with values parsed from their paths.
+   * @param pattern The pattern to match, like "%track% - %artist% - %title%". Folders in it match the folders the files are in.
+   * @param paths The paths of the files to tag.
+   * @param settings How the tags get saved.
+   * @returns The outcome of each file.
+   */
+  static async tagFilesFromNames(pattern: string, paths: string[], settings: TagWriteSettings): Promise<FileResult[]> {
+    return await invoke<FileResult[]>("tag_files_from_names", { pattern: pattern, filePathsStr: JSON.stringify(paths), writeSettingsStr: JSON.stringify(settings) });
+  }
+
+  /**
+   * Previews the tags parsing the provided music files' paths would set, without saving anything.
+   * @param pattern The pattern to match, like "%track% - %artist% - %title%". Folders in it match the folders the files are in.
+   * @param paths The paths of the files to tag.
+   * @param settings How the tags would get saved.
+   * @returns What would change in each file.
    */
-  static async writeMusicFiles(changes: Record<string, SongEditFields>): Promise<boolean> {
-    return await invoke<boolean>("write_music_files", { changesStr: JSON.stringify(changes) });
+  static async previewTagsFromNames(pattern: string, paths: string[], settings: TagWriteSettings): Promise<FilePreview[]> {
+    return await invoke<FilePreview[]>("preview_tags_from_names", { pattern: pattern, filePathsStr: JSON.stringify(paths), writeSettingsStr: JSON.stringify(settings) });
   }
 
   /**
    * Deletes the provided songs from the device.
    * @param paths The paths of the songs to delete.
+   * @returns The outcome of each file.
+   */
+  static async deleteSongs(paths: string[]): Promise<FileResult[]> {
+    return await invoke<FileResult[]>("delete_songs", { filePathsStr: JSON.stringify(paths) });
+  }
+
+  /**
+   * Moves and renames music files to match a path template built from their tags, along with their sidecar files.
+   * @param template The path template, relative to the music folder, using fields like %albumartist% and %title%.
+   * @param paths The paths of the files to organize.
+   * @param dryRun Whether to only plan the moves without making them.
+   * @returns The outcome of each file.
+   */
+  static async organizeFiles(template: string, paths: string[], dryRun: boolean): Promise<OrganizeResult[]> {
+    return await invoke<OrganizeResult[]>("organize_files", { template: template, filePathsStr: JSON.stringify(paths), dryRun: dryRun });
+  }
+
+  /**
+   * Renames music files in place to match a file name template built from their tags, along with their sidecar files.
+   * @param template The file name template, using fields like %track% and %title%.
+   * @param paths The paths of the files to rename.
+   * @param dryRun Whether to only plan the renames without making them.
+   * @returns The outcome of each file.
+   */
+  static async renameFiles(template: string, paths: string[], dryRun: boolean): Promise<OrganizeResult[]> {
+    return await invoke<OrganizeResult[]>("rename_files", { template: template, filePathsStr: JSON.stringify(paths), dryRun: dryRun });
+  }
+
+  /**
+   * Undoes the last tag edit or song deletion.
+   * @returns The operation that was undone, or null if there was nothing to undo.
+   */
+  static async undoLastOperation(): Promise<Operation | null> {
+    return await invoke<Operation | null>("undo_last_operation", {});
+  }
+
+  /**
+   * Lists the tag edits and song deletions that can be undone.
+   * @returns The operations, newest first.
    */
-  static async deleteSongs(paths: string[]): Promise<boolean> {
-    return await invoke<boolean>("delete_songs", { filePathsStr: JSON.stringify(paths) });
+  static async listOperations(): Promise<Operation[]> {
+    return await invoke<Operation[]>("list_operations", {});
   }
 
   /**
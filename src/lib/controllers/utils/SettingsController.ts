@@ -16,7 +16,7 @@
  */
 import { Playlist, Song, type Album, type Artist } from "@models";
 import { hasShownHelpTranslate, selectedLanguage, t, t as translate } from "@stores/Locale";
-import { albumGridSize, albums, albumSortOrder, artistGridSize, artistGridStyle, artists, artistSortOrder, autoPlayOnConnect, blacklistedFolders, currentProfile, debugModeEnabled, dismissMiniPlayerWithSwipe, extraControl, filterSongDuration, musicDirectories, nowPlayingBackgroundType, nowPlayingList, nowPlayingTheme, nowPlayingType, palette, playingSongId, playlistGridSize, playlists, playlistSortOrder, profiles, queue, repeatPlayed, selectedView, showErrorSnackbar, showExtraSongInfo, showInfoSnackbar, showVolumeControls, shuffle, songGridSize, songProgress, songs, songSortOrder, themePrimaryColor, useAlbumColors, useArtistColors, useOledPalette, viewIndices, viewsToRender, volumeLevel } from "@stores/State";
+import { albumGridSize, albums, albumSortOrder, apeMode, artistGridSize, artistGridStyle, artists, artistSortOrder, artJpegQuality, artMaxDimension, autoPlayOnConnect, blacklistedFolders, currentProfile, debugModeEnabled, dismissMiniPlayerWithSwipe, extraControl, filterSongDuration, id3v1Mode, id3Version, lrcSidecar, musicDirectories, nowPlayingBackgroundType, nowPlayingList, nowPlayingTheme, nowPlayingType, palette, playingSongId, playlistGridSize, playCountMode, playlists, playlistSortOrder, profiles, queue, repeatPlayed, selectedView, showErrorSnackbar, showExtraSongInfo, showInfoSnackbar, showVolumeControls, shuffle, songGridSize, songProgress, songs, songSortOrder, themePrimaryColor, useAlbumColors, useArtistColors, useOledPalette, viewIndices, viewsToRender, volumeLevel } from "@stores/State";
 import { exists, readTextFile, writeTextFile } from "@tauri-apps/plugin-fs";
 import * as process from "@tauri-apps/plugin-process";
 import { load as loadStore, Store } from '@tauri-apps/plugin-store';
@@ -95,6 +95,13 @@ export class SettingsController {
 
   private static blacklistedFoldersUnsub: Unsubscriber;
   private static filterSongDurationUnsub: Unsubscriber;
+  private static id3VersionUnsub: Unsubscriber;
+  private static id3v1ModeUnsub: Unsubscriber;
+  private static apeModeUnsub: Unsubscriber;
+  private static artMaxDimensionUnsub: Unsubscriber;
+  private static artJpegQualityUnsub: Unsubscriber;
+  private static playCountModeUnsub: Unsubscriber;
+  private static lrcSidecarUnsub: Unsubscriber;
   private static selectedLanguageUnsub: Unsubscriber;
 
   private static albumsUnsub: Unsubscriber;
@@ -213,6 +220,7 @@ export class SettingsController {
 
         blacklistedFolders:old.blacklistedFolders,
         filterSongDuration: old.filterSongDuration,
+        tagWriting: old.tagWriting,
         selectedLanguage: old.selectedLanguage,
 
         cache: old.cache,
@@ -420,6 +428,13 @@ export class SettingsController {
 
     blacklistedFolders.set(this.profile.blacklistedFolders);
     filterSongDuration.set(this.profile.filterSongDuration);
+    id3Version.set(this.profile.tagWriting.id3Version);
+    id3v1Mode.set(this.profile.tagWriting.id3v1);
+    apeMode.set(this.profile.tagWriting.ape);
+    artMaxDimension.set(this.profile.tagWriting.artMaxDimension);
+    artJpegQuality.set(this.profile.tagWriting.artJpegQuality);
+    playCountMode.set(this.profile.tagWriting.playCounts);
+    lrcSidecar.set(this.profile.tagWriting.lrcSidecar);
     selectedLanguage.set(this.profile.selectedLanguage);
 
 
@@ -520,6 +535,13 @@ export class SettingsController {
     
     this.blacklistedFoldersUnsub = blacklistedFolders.subscribe(this.updateStoreIfChanged<string[]>("blacklistedFolders"));
     this.filterSongDurationUnsub = filterSongDuration.subscribe(this.updateStoreIfChanged<number>("filterSongDuration"));
+    this.id3VersionUnsub = id3Version.subscribe(this.updateStoreIfChanged<Id3Version>("tagWriting.id3Version"));
+    this.id3v1ModeUnsub = id3v1Mode.subscribe(this.updateStoreIfChanged<TrailerMode>("tagWriting.id3v1"));
+    this.apeModeUnsub = apeMode.subscribe(this.updateStoreIfChanged<TrailerMode>("tagWriting.ape"));
+    this.artMaxDimensionUnsub = artMaxDimension.subscribe(this.updateStoreIfChanged<number>("tagWriting.artMaxDimension"));
+    this.artJpegQualityUnsub = artJpegQuality.subscribe(this.updateStoreIfChanged<number>("tagWriting.artJpegQuality"));
+    this.playCountModeUnsub = playCountMode.subscribe(this.updateStoreIfChanged<PlayCountMode>("tagWriting.playCounts"));
+    this.lrcSidecarUnsub = lrcSidecar.subscribe(this.updateStoreIfChanged<boolean>("tagWriting.lrcSidecar"));
     this.selectedLanguageUnsub = selectedLanguage.subscribe(this.updateStoreIfChanged<string>("selectedLanguage"));
 
     this.albumsUnsub = albums.subscribe((newAlbums) => {
@@ -713,6 +735,13 @@ export class SettingsController {
     
     if (this.blacklistedFoldersUnsub) this.blacklistedFoldersUnsub();
     if (this.filterSongDurationUnsub) this.filterSongDurationUnsub();
+    if (this.id3VersionUnsub) this.id3VersionUnsub();
+    if (this.id3v1ModeUnsub) this.id3v1ModeUnsub();
+    if (this.apeModeUnsub) this.apeModeUnsub();
+    if (this.artMaxDimensionUnsub) this.artMaxDimensionUnsub();
+    if (this.artJpegQualityUnsub) this.artJpegQualityUnsub();
+    if (this.playCountModeUnsub) this.playCountModeUnsub();
+    if (this.lrcSidecarUnsub) this.lrcSidecarUnsub();
     if (this.selectedLanguageUnsub) this.selectedLanguageUnsub();
 
     if (this.albumsUnsub) this.albumsUnsub();
@@ -1,7 +1,7 @@
 import { t } from "@stores/Locale";
 import { albumsMap } from "@stores/State";
 import { path } from "@tauri-apps/api";
-import { artistIsSingular, formatTime, getGenre, hash64, normalizeString } from "@utils";
+import { artistIsSingular, formatTime, getGenre, hash64, normalizeString, splitTagValues } from "@utils";
 import { get } from "svelte/store";
 
 /**
@@ -17,6 +17,8 @@ export class Song {
   releaseYear: number;
   length: number;
   bitRate: number;
+  bitrateMode?: BitrateMode;
+  encoder?: string;
   sampleRate: number;
   size: number;
   filePath: string;
@@ -29,6 +31,22 @@ export class Song {
   genre?: string;
   trackNumber?: number;
   totalTracks?: number;
+  discNumber?: number;
+  discTotal?: number;
+  compilation = false;
+  sortArtist?: string;
+  sortAlbum?: string;
+  sortTitle?: string;
+  bpm?: number;
+  comment?: string;
+  rating?: number;
+  musicBrainzTrackId?: string;
+  musicBrainzAlbumId?: string;
+  musicBrainzArtistId?: string;
+  replayGain?: ReplayGain;
+  cueTrack?: number;
+  startMs?: number;
+  endMs?: number;
 
   /**
    * Creates a new Song.
@@ -53,7 +71,7 @@ export class Song {
     this.artPath = artPath;
     this.lastPlayedOn = lastPlayedOn;
     this.numTimesPlayed = numTimesPlayed;
-    this.genre = genre ? getGenre(genre) : undefined;
+    this.genre = genre ? splitTagValues(genre).map(getGenre).join("; ") : undefined;
     this.trackNumber = trackNumber;
     this.totalTracks = totalTracks;
 
@@ -61,13 +79,38 @@ export class Song {
     this.fileName = this.filePath.substring(fileNameStart+1);
     this.folderPath = this.filePath.substring(0, fileNameStart);
 
-    this._id = hash64(this.fileName);
+    this._id = Song.getId(this.fileName);
   }
 
   get id() {
     return this._id;
   }
 
+  /**
+   * Gets the id of a song from its file name.
+   * Songs from a cue sheet share their file, so they need the track number to have unique ids.
+   * @param fileName The name of the song's file.
+   * @param cueTrack The song's track in its cue sheet, if it has one.
+   */
+  private static getId(fileName: string, cueTrack?: number): string {
+    return cueTrack !== undefined ? hash64(fileName + "#" + cueTrack) : hash64(fileName);
+  }
+
+  /**
+   * Points the song at its file's new location.
+   * ! The id follows the file name, so anything referencing the old id needs to be updated.
+   * @param filePath The new path of the song's file.
+   */
+  setFilePath(filePath: string): void {
+    this.filePath = filePath;
+
+    const fileNameStart = this.filePath.lastIndexOf(path.sep());
+    this.fileName = this.filePath.substring(fileNameStart+1);
+    this.folderPath = this.filePath.substring(0, fileNameStart);
+
+    this._id = Song.getId(this.fileName, this.cueTrack);
+  }
+
   /**
    * Sets the last played date to now.
    */
@@ -117,57 +160,65 @@ export class Song {
    * Displays the song's bitrate and sample rate
    */
   displayFrequency(): string {
-    return this.bitRate / 1000 + " kb/s • " + this.sampleRate / 1000 + " Hz";
+    const mode = this.bitrateMode ? " " + this.bitrateMode.toUpperCase() : "";
+    return Math.round(this.bitRate / 1000) + " kb/s" + mode + " • " + this.sampleRate / 1000 + " Hz";
   }
 
   /**
    * Gets a song object from a json object.
    * @param json The song json object.
    * @param lastPlayedOn The date the song was last played.
-   * @param numTimesPlayed The number of times this song has been played.
+   * @param numTimesPlayed The number of times this song has been played. The play count saved in the file is used if it's higher, unless the song is a cue sheet track.
    * @returns The song object.
    */
   static fromJSON(json: any, lastPlayedOn = "Never", numTimesPlayed = 0): Song {
-    let title, album, artist, composer, albumArtist, releaseYear, length, bitRate, sampleRate, size, filePath, artPath, genre, trackNumber, totalTracks;
-
-    if (json.title && json.filename.endsWith("flac")) {
-      title = json.title;
-      album = json.album;
-      artist = json.artist;
-      albumArtist = json.albumartist;
-      genre = json.genre;
-      composer = json.composer;
-      releaseYear = json.date;
-
-      trackNumber = json.tracknumber ? parseInt(json.tracknumber) : undefined;
-      const stringTotalTracks = json.totaltracks ?? json.tracktotal;
-      totalTracks = stringTotalTracks ? parseInt(stringTotalTracks) : undefined;
-    } else {
-      title = json.tit2 ?? json.tt2;
-
-      album = json.talb ?? json.tal;
-      artist = json.tpe1 ?? json.tp1;
-      composer = json.tcom ?? json.tcm;
-      albumArtist = json.tpe2 ?? json.tp2;
-      releaseYear = json.tyer ?? json.tye;
-      genre = json.tcon ?? json.tco;
-
-      if (json.trk) {
-        const parts = json.trk.split("/");
-        trackNumber = parseInt(parts[0]);
-        totalTracks = parseInt(parts[1]);
-      } else {
-        trackNumber = parseInt(json.trck);
-      }
+    const artist = json.artists.length ? json.artists.join("; ") : undefined;
+    const composer = json.composers.length ? json.composers.join("; ") : undefined;
+    const genre = json.genres.length ? json.genres.join("; ") : undefined;
+
+    const song = new Song(
+      json.title ?? undefined,
+      json.album ?? undefined,
+      artist,
+      composer,
+      json.albumArtist ?? undefined,
+      json.year ?? -1,
+      json.duration,
+      json.bitrate,
+      json.sampleRate,
+      json.size,
+      json.filePath,
+      json.artPath ?? undefined,
+      lastPlayedOn,
+      json.cueTrack === null ? Math.max(numTimesPlayed, json.playCount ?? 0) : numTimesPlayed,
+      genre,
+      json.trackNumber ?? undefined,
+      json.trackTotal ?? undefined
+    );
+
+    song.discNumber = json.discNumber ?? undefined;
+    song.discTotal = json.discTotal ?? undefined;
+    song.compilation = json.compilation;
+    song.sortArtist = json.sortArtist ?? undefined;
+    song.sortAlbum = json.sortAlbum ?? undefined;
+    song.sortTitle = json.sortTitle ?? undefined;
+    song.bpm = json.bpm ?? undefined;
+    song.comment = json.comment ?? undefined;
+    song.rating = json.rating ?? undefined;
+    song.musicBrainzTrackId = json.musicBrainzTrackId ?? undefined;
+    song.musicBrainzAlbumId = json.musicBrainzAlbumId ?? undefined;
+    song.musicBrainzArtistId = json.musicBrainzArtistId ?? undefined;
+    song.replayGain = json.replayGain ?? undefined;
+    song.bitrateMode = json.bitrateMode ?? undefined;
+    song.encoder = json.encoder ?? undefined;
+
+    if (json.cueTrack !== null && json.cueTrack !== undefined) {
+      song.cueTrack = json.cueTrack;
+      song.startMs = json.startMs ?? undefined;
+      song.endMs = json.endMs ?? undefined;
+      song._id = Song.getId(song.fileName, json.cueTrack);
     }
-    
-    length = parseInt(json.length);
-    bitRate = parseInt(json.bitrate);
-    sampleRate = parseInt(json.samplerate);
-    size = parseInt(json.size);
-    filePath = json.filename;
-    artPath = json.albumpath;
-
-    return new Song(title, album, artist, composer, albumArtist, releaseYear ? parseInt(releaseYear) : -1, length, bitRate, sampleRate, size, filePath, artPath, lastPlayedOn, numTimesPlayed, genre, trackNumber, totalTracks);
+
+    return song;
   }
 }
\ No newline at end of file
@@ -134,6 +134,7 @@ export type UserProfile = {
 
   blacklistedFolders: string[];
   filterSongDuration: number;
+  tagWriting: TagWriteSettings;
   selectedLanguage: string;
 
   cache: {
@@ -232,6 +233,15 @@ const DEFAULT_PROFILE_MOBILE: UserProfile = {
 
   "blacklistedFolders": [],
   "filterSongDuration": 30,
+  "tagWriting": {
+    "id3Version": "keep",
+    "id3v1": "keep",
+    "ape": "keep",
+    "artMaxDimension": 0,
+    "artJpegQuality": 0,
+    "playCounts": "off",
+    "lrcSidecar": false
+  },
   "selectedLanguage": "system",
 
   "cache": {
@@ -329,6 +339,15 @@ const DEFAULT_PROFILE_DESKTOP: UserProfile = {
 
   "blacklistedFolders": [],
   "filterSongDuration": 30,
+  "tagWriting": {
+    "id3Version": "keep",
+    "id3v1": "keep",
+    "ape": "keep",
+    "artMaxDimension": 0,
+    "artJpegQuality": 0,
+    "playCounts": "off",
+    "lrcSidecar": false
+  },
   "selectedLanguage": "system",
 
   "cache": {
@@ -36,16 +36,237 @@ declare namespace svelte.JSX {
 	}
 }
 
+type LibraryDiff = {
+  added: string[];
+  changed: string[];
+  removed: string[];
+}
+
+type ReplayGain = {
+  trackGain: number | null;
+  trackPeak: number | null;
+  albumGain: number | null;
+  albumPeak: number | null;
+}
+
+type NormalizationMode = "off" | "track" | "album";
+
+type BitrateMode = "cbr" | "vbr" | "abr";
+
+type SongEntry = {
+  filePath: string;
+  title: string | null;
+  artists: string[];
+  album: string | null;
+  albumArtist: string | null;
+  trackNumber: number | null;
+  trackTotal: number | null;
+  discNumber: number | null;
+  discTotal: number | null;
+  compilation: boolean;
+  year: number | null;
+  genres: string[];
+  composers: string[];
+  sortArtist: string | null;
+  sortAlbum: string | null;
+  sortTitle: string | null;
+  bpm: number | null;
+  comment: string | null;
+  /** The rating in stars, from 0.5 to 5 in half steps. */
+  rating: number | null;
+  playCount: number | null;
+  musicBrainzTrackId: string | null;
+  musicBrainzAlbumId: string | null;
+  musicBrainzArtistId: string | null;
+  replayGain: ReplayGain;
+  duration: number;
+  codec: string;
+  bitrate: number;
+  bitrateMode: BitrateMode | null;
+  encoder: string | null;
+  sampleRate: number;
+  channels: number | null;
+  bitsPerSample: number | null;
+  size: number;
+  artPath: string | null;
+  hasLyrics: boolean;
+  cueTrack: number | null;
+  startMs: number | null;
+  endMs: number | null;
+  rawTags: Record<string, string | string[]>;
+}
+
+type ScanRules = {
+  include?: string[];
+  exclude?: string[];
+  skipHidden?: boolean;
+  maxDepth?: number | null;
+  coverNames?: string[] | null;
+}
+
+type ScanError = {
+  path: string;
+  stage: "discover" | "open" | "probe" | "tracks" | "length";
+  message: string;
+}
+
+type LibraryScan = {
+  entries: SongEntry[];
+  errors: ScanError[];
+  diff: LibraryDiff;
+  cancelled: boolean;
+}
+
+type FileErrorKind = "permissionDenied" | "notFound" | "unsupportedFormat" | "tagParse" | "other";
+
+type FileError = {
+  kind: FileErrorKind;
+  message: string;
+}
+
+/**
+ * The outcome of writing or deleting a single file. Successful files have no error.
+ */
+type FileResult = {
+  path: string;
+  error: FileError | null;
+}
+
+/**
+ * A field whose value would change. The artwork is described by its mime type, size and hash,
+ * and the ID3v1 and APE tags by their size and hash. Writing can also convert the ID3 version
+ * and export a .lrc file, which show up as their own fields.
+ */
+type FieldChange = {
+  field: keyof SongEditFields | "id3Version" | "id3v1" | "ape" | "lrcSidecar";
+  oldValue: string | null;
+  newValue: string | null;
+}
+
+/**
+ * The changes writing would make to a single file. Files that can't be written have an error instead.
+ */
+type FilePreview = {
+  path: string;
+  changes: FieldChange[];
+  error: FileError | null;
+}
+
+/**
+ * The ID3v2 version MP3 files are saved as. "keep" leaves each file at the version it already has.
+ */
+type Id3Version = "keep" | "v2.3" | "v2.4";
+
+/**
+ * What to do with the ID3v1 or APE tag at the end of an MP3 file when it's saved. Syncing only updates tags that already exist.
+ */
+type TrailerMode = "keep" | "remove" | "sync";
+
+/**
+ * Where play counts get written in MP3 files. Vorbis comments always use FMPS_PLAYCOUNT, and nothing is written when it's off.
+ */
+type PlayCountMode = "off" | "pcnt" | "popm";
+
+/**
+ * How tags get saved, beyond the fields being edited.
+ */
+type TagWriteSettings = {
+  id3Version: Id3Version;
+  id3v1: TrailerMode;
+  ape: TrailerMode;
+  /** Embedded artwork larger than this, in pixels, gets downscaled. 0 keeps its size. */
+  artMaxDimension: number;
+  /** Recompresses embedded artwork as a JPEG of this quality, from 1 to 100. 0 keeps it as it is. */
+  artJpegQuality: number;
+  playCounts: PlayCountMode;
+  /** Also saves synced lyrics to a .lrc file next to the song. */
+  lrcSidecar: boolean;
+}
+
+type TransformField = Exclude<keyof SongEditFields, "artPath">;
+
+/**
+ * A single step of a batch tag transform. Steps run in order, each seeing the values left by the last.
+ */
+type TagTransform =
+  | { type: "regexReplace", fields: TransformField[], pattern: string, replacement: string }
+  | { type: "titleCase", fields: TransformField[] }
+  | { type: "sentenceCase", fields: TransformField[] }
+  | { type: "trimWhitespace", fields: TransformField[] }
+  | { type: "autoNumber", start?: number, setTotal: boolean }
+  | { type: "copyField", from: TransformField, to: TransformField };
+
+type FileMove = {
+  from: string;
+  to: string;
+}
+
+/**
+ * The outcome of organizing a single file. Files that can't be moved have an error instead of a new path.
+ */
+type OrganizeResult = {
+  path: string;
+  newPath: string | null;
+  sidecars: FileMove[];
+  error: FileError | null;
+}
+
+type OperationKind = "edit" | "delete" | "organize";
+
+type JournalEntry = {
+  filePath: string;
+  backupKind: "id3" | "flac" | "trash" | "moved" | "contents";
+}
+
+type Operation = {
+  id: string;
+  kind: OperationKind;
+  timestamp: number;
+  entries: JournalEntry[];
+}
+
+type LyricLine = {
+  timeMs: number;
+  text: string;
+}
+
+type Lyrics = {
+  plain: string | null;
+  synced: LyricLine[];
+}
+
+/**
+ * An edit to a single field. Leaving it undefined keeps the field as is, null clears it, and a value sets it.
+ */
+type FieldEdit<T> = T | null | undefined;
+
 type SongEditFields = {
-  artPath: string | undefined;
-  title: string | undefined;
-  album: string | undefined;
-  composer: string | undefined;
-  albumArtist: string | undefined;
-  artist: string | undefined;
-  releaseYear: number | undefined;
-  genre: string | undefined;
-  trackNumber: number | undefined;
+  artPath?: FieldEdit<string>;
+  title?: FieldEdit<string>;
+  album?: FieldEdit<string>;
+  composers?: FieldEdit<string[]>;
+  albumArtist?: FieldEdit<string>;
+  artists?: FieldEdit<string[]>;
+  releaseYear?: FieldEdit<number>;
+  genres?: FieldEdit<string[]>;
+  trackNumber?: FieldEdit<number>;
+  trackTotal?: FieldEdit<number>;
+  discNumber?: FieldEdit<number>;
+  discTotal?: FieldEdit<number>;
+  compilation?: FieldEdit<boolean>;
+  sortArtist?: FieldEdit<string>;
+  sortAlbum?: FieldEdit<string>;
+  sortTitle?: FieldEdit<string>;
+  bpm?: FieldEdit<number>;
+  comment?: FieldEdit<string>;
+  rating?: FieldEdit<number>;
+  lyrics?: FieldEdit<string>;
+  /** LRC formatted text, like "[01:23.45]A line". */
+  syncedLyrics?: FieldEdit<string>;
+  playCount?: FieldEdit<number>;
+  musicBrainzTrackId?: FieldEdit<string>;
+  musicBrainzAlbumId?: FieldEdit<string>;
+  musicBrainzArtistId?: FieldEdit<string>;
 }
 
 type AlbumEditFields = {
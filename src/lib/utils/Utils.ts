@@ -84,12 +84,27 @@ export function formatTime(totalSeconds: number): string {
   return `${hours !== 0 ? hours + ":" + prefixIfNeeded(minutes) : minutes}:${prefixIfNeeded(seconds)}`;
 }
 
+/**
+ * Formats timed lyrics as LRC text, with timestamps in hundredths of a second.
+ * @param lines The lines to format.
+ * @returns The LRC text.
+ */
+export function formatLrc(lines: LyricLine[]): string {
+  return lines.map((line) => {
+    const minutes = Math.floor(line.timeMs / 60000);
+    const seconds = Math.floor(line.timeMs / 1000) % 60;
+    const hundredths = Math.floor((line.timeMs % 1000) / 10);
+
+    return `[${prefixIfNeeded(minutes)}:${prefixIfNeeded(seconds)}.${prefixIfNeeded(hundredths)}]${line.text}`;
+  }).join("\n");
+}
+
 /**
  * Checks if an artist is singular.
  * @param artist The artist to check
  */
 export function artistIsSingular(artist: string) {
-  if (artist.includes(" and ") || artist.includes(" & ") || artist.includes(",") || artist.includes("/")) return false;
+  if (artist.includes(" and ") || artist.includes(" & ") || artist.includes(",") || artist.includes(";") || artist.includes("/")) return false;
   return true;
 }
 
@@ -101,6 +116,15 @@ export function getAllArtistNames(artist: string): string[] {
   return artist.split(/\s*(?:\s*(?:and|&|,|;|\/|\\)\s*)\s*/);
 }
 
+/**
+ * Splits a multi-valued tag string, like "Rock; Pop", into its values.
+ * @param value The string to split.
+ */
+export function splitTagValues(value: string | undefined): string[] {
+  if (!value) return [];
+  return value.split(";").map((part) => part.trim()).filter((part) => part !== "");
+}
+
 /**
  * Randomly selects n elements from an array.
  * @param arr The array to get elements from.
@@ -47,6 +47,13 @@ export const queue = writable<string[]>([]);
 
 export const blacklistedFolders = writable<string[]>([]);
 export const filterSongDuration = writable(30);
+export const id3Version = writable<Id3Version>("keep");
+export const id3v1Mode = writable<TrailerMode>("keep");
+export const apeMode = writable<TrailerMode>("keep");
+export const artMaxDimension = writable(0);
+export const artJpegQuality = writable(0);
+export const playCountMode = writable<PlayCountMode>("off");
+export const lrcSidecar = writable(false);
 
 export const artists = writable<Artist[]>([]);
 export const artistsMap = derived(artists, (artists: Artist[]) => {